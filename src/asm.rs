@@ -0,0 +1,1013 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::errors::AsmError;
+use crate::LOWER_MEMORY_BOUNDARY;
+
+const MAX_INCLUDE_DEPTH: usize = 16;
+const ADDRESS_SPACE: i64 = 0x10000;
+const ASSIGNMENTS: [&str; 9] = [":=", "+=", "-=", "|=", "&=", "^=", ">>=", "<<=", "=-"];
+
+// An assembler for the Octo dialect of CHIP-8 assembly, as written by the
+// disassembler's Octo syntax:
+//
+//     :const SPEED 2          # named constants
+//     : main                  # labels
+//         i := ball
+//         v0 += { SPEED * 2 } # expressions in braces
+//         sprite v0 v1 4
+//         jump main
+//     : ball
+//         :byte 0x60          # data, with or without :byte
+//         0xF0 0xF0 0x60
+//     :include "lib.8o"       # paths are relative to the including file
+//
+// A bare label name calls that subroutine, and `:calc NAME { ... }` is
+// accepted as a synonym for `:const`. Everything is assembled in order from
+// 0x200, so the program starts with whatever comes first.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Word,
+    Expr,
+    Str,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: Kind,
+    text: String,
+    file: Option<Rc<str>>,
+    line: usize,
+    column: usize,
+}
+
+impl Token {
+    fn error(&self, message: String) -> AsmError {
+        AsmError {
+            file: self.file.as_ref().map(|f| f.to_string()),
+            line: self.line,
+            column: self.column,
+            message,
+        }
+    }
+
+    // `offset` counts characters into the token's text
+    fn error_at(&self, offset: usize, message: String) -> AsmError {
+        let mut error = self.error(message);
+
+        error.column += match self.kind {
+            Kind::Expr => offset + 1, // skip the opening brace
+            _ => offset,
+        };
+
+        error
+    }
+}
+
+// Where an instruction's single non-register operand goes in the opcode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Nnn,  // 12-bit address in the low bits
+    Nn,   // 8-bit immediate in the low byte
+    N,    // 4-bit immediate in the low nibble
+    X,    // the XO-CHIP plane mask, in the X nibble
+    Long, // 16-bit address in the word after the opcode
+}
+
+impl Field {
+    fn range(self) -> (i64, i64) {
+        match self {
+            Field::Nnn => (0, 0xFFF),
+            Field::Nn => (-128, 0xFF),
+            Field::N => (0, 0xF),
+            Field::X => (0, 0xF),
+            Field::Long => (0, 0xFFFF),
+        }
+    }
+}
+
+enum Item {
+    Label(String),
+    Const(String, Token),
+    Byte(Token),
+    Code(u16, Option<(Field, Token)>),
+}
+
+struct Statement {
+    item: Item,
+    token: Token,
+}
+
+// Assembles Octo-style source into a ROM ready for `Chip8::load_program`.
+// `path` is only used to name the file in errors and to find includes;
+// without it, includes are looked up in the current directory.
+pub fn assemble(source: &str, path: Option<&Path>) -> Result<Vec<u8>, AsmError> {
+    let file = path.map(|p| Rc::from(p.display().to_string().as_str()));
+    let base = path
+        .and_then(Path::parent)
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let tokens = tokenize(source, file, &base, 0)?;
+    let statements = Parser::new(tokens).statements()?;
+
+    emit(&statements, &layout(&statements)?)
+}
+
+fn tokenize(
+    source: &str,
+    file: Option<Rc<str>>,
+    base: &Path,
+    depth: usize,
+) -> Result<Vec<Token>, AsmError> {
+    let mut tokens: Vec<Token> = vec![];
+
+    for (l, text) in source.lines().enumerate() {
+        let chars: Vec<char> = text.chars().collect();
+        let mut c = 0;
+
+        while c < chars.len() {
+            if chars[c].is_whitespace() {
+                c += 1;
+                continue;
+            }
+
+            // comments run to the end of the line
+            if chars[c] == '#' {
+                break;
+            }
+
+            let start = c;
+            let mut token = Token {
+                kind: Kind::Word,
+                text: String::new(),
+                file: file.clone(),
+                line: l + 1,
+                column: start + 1,
+            };
+
+            match chars[c] {
+                '{' => {
+                    let mut depth = 0;
+
+                    while c < chars.len() {
+                        match chars[c] {
+                            '{' => depth += 1,
+                            '}' => depth -= 1,
+                            _ => {}
+                        }
+
+                        c += 1;
+
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+
+                    if depth != 0 {
+                        return Err(token.error("unterminated expression".to_string()));
+                    }
+
+                    token.kind = Kind::Expr;
+                    token.text = chars[start + 1..c - 1].iter().collect();
+                }
+                '"' => {
+                    c += 1;
+
+                    while c < chars.len() && chars[c] != '"' {
+                        c += 1;
+                    }
+
+                    if c == chars.len() {
+                        return Err(token.error("unterminated string".to_string()));
+                    }
+
+                    c += 1;
+                    token.kind = Kind::Str;
+                    token.text = chars[start + 1..c - 1].iter().collect();
+                }
+                _ => {
+                    while c < chars.len() && !chars[c].is_whitespace() {
+                        c += 1;
+                    }
+
+                    token.text = chars[start..c].iter().collect();
+                }
+            }
+
+            // splice included files in place of the directive
+            if tokens
+                .last()
+                .is_some_and(|t| t.kind == Kind::Word && t.text == ":include")
+            {
+                let directive = tokens.pop().unwrap();
+
+                if token.kind != Kind::Str {
+                    return Err(token.error("expected a quoted file name".to_string()));
+                }
+
+                if depth >= MAX_INCLUDE_DEPTH {
+                    return Err(directive.error("includes are nested too deeply".to_string()));
+                }
+
+                tokens.extend(include(&token, base, depth)?);
+                continue;
+            }
+
+            tokens.push(token);
+        }
+    }
+
+    if let Some(t) = tokens.last() {
+        if t.kind == Kind::Word && t.text == ":include" {
+            return Err(t.error("expected a quoted file name".to_string()));
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn include(name: &Token, base: &Path, depth: usize) -> Result<Vec<Token>, AsmError> {
+    let path: PathBuf = base.join(&name.text);
+    let source = fs::read_to_string(&path)
+        .map_err(|e| name.error(format!("can't read {}: {}", path.display(), e)))?;
+    let file = Rc::from(path.display().to_string().as_str());
+    let base = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+    tokenize(&source, Some(file), &base, depth + 1)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    consts: HashSet<String>,
+}
+
+impl Parser {
+    fn new(tokens: Vec<Token>) -> Parser {
+        Parser {
+            tokens,
+            pos: 0,
+            consts: HashSet::new(),
+        }
+    }
+
+    fn statements(mut self) -> Result<Vec<Statement>, AsmError> {
+        let mut statements = vec![];
+
+        while self.pos < self.tokens.len() {
+            statements.push(self.statement()?);
+        }
+
+        Ok(statements)
+    }
+
+    fn next(&mut self, expected: &str) -> Result<Token, AsmError> {
+        match self.tokens.get(self.pos) {
+            Some(token) => {
+                self.pos += 1;
+                Ok(token.clone())
+            }
+            None => {
+                let last = &self.tokens[self.tokens.len() - 1];
+
+                Err(last.error_at(
+                    last.text.chars().count(),
+                    format!("expected {} after '{}'", expected, last.text),
+                ))
+            }
+        }
+    }
+
+    fn peek_is(&self, text: &str) -> bool {
+        self.tokens
+            .get(self.pos)
+            .is_some_and(|t| t.kind == Kind::Word && t.text == text)
+    }
+
+    fn peek_register(&self) -> Option<u16> {
+        self.tokens.get(self.pos).and_then(register)
+    }
+
+    fn expect(&mut self, text: &str) -> Result<Token, AsmError> {
+        let token = self.next(&format!("'{}'", text))?;
+
+        if token.kind == Kind::Word && token.text == text {
+            Ok(token)
+        } else {
+            Err(token.error(format!("expected '{}', found '{}'", text, token.text)))
+        }
+    }
+
+    fn register(&mut self) -> Result<u16, AsmError> {
+        let token = self.next("a register")?;
+
+        register(&token).ok_or_else(|| {
+            token.error(format!(
+                "expected a register (v0-vf), found '{}'",
+                token.text
+            ))
+        })
+    }
+
+    fn value(&mut self) -> Result<Token, AsmError> {
+        let token = self.next("a value")?;
+
+        if token.kind == Kind::Str {
+            return Err(token.error("expected a value, found a string".to_string()));
+        }
+
+        Ok(token)
+    }
+
+    fn name(&mut self) -> Result<Token, AsmError> {
+        let token = self.next("a name")?;
+
+        if token.kind != Kind::Word || !is_name(&token.text) {
+            return Err(token.error(format!("'{}' is not a valid name", token.text)));
+        }
+
+        Ok(token)
+    }
+
+    fn statement(&mut self) -> Result<Statement, AsmError> {
+        let token = self.next("a statement")?;
+
+        let code = |opcode: u16| Item::Code(opcode, None);
+        let operand =
+            |opcode: u16, field: Field, value: Token| Item::Code(opcode, Some((field, value)));
+        let x = |r: u16| r << 8;
+        let xy = |rx: u16, ry: u16| rx << 8 | ry << 4;
+
+        if token.kind == Kind::Str {
+            return Err(token.error(format!("unexpected string \"{}\"", token.text)));
+        }
+
+        if token.kind == Kind::Expr {
+            return Ok(Statement {
+                item: Item::Byte(token.clone()),
+                token,
+            });
+        }
+
+        let item = match token.text.as_str() {
+            ":" => Item::Label(self.name()?.text),
+            ":const" | ":calc" => {
+                let name = self.name()?.text;
+                let value = self.value()?;
+
+                self.consts.insert(name.clone());
+                Item::Const(name, value)
+            }
+            ":byte" => Item::Byte(self.value()?),
+            ":call" => operand(0x2000, Field::Nnn, self.value()?),
+            "clear" => code(0x00E0),
+            "return" | ";" => code(0x00EE),
+            "scroll-down" => operand(0x00C0, Field::N, self.value()?),
+            "scroll-right" => code(0x00FB),
+            "scroll-left" => code(0x00FC),
+            "exit" => code(0x00FD),
+            "lores" => code(0x00FE),
+            "hires" => code(0x00FF),
+            "audio" => code(0xF002),
+            "jump" => operand(0x1000, Field::Nnn, self.value()?),
+            "jump0" => operand(0xB000, Field::Nnn, self.value()?),
+            "sprite" => {
+                let vx = self.register()?;
+                let vy = self.register()?;
+
+                operand(0xD000 | xy(vx, vy), Field::N, self.value()?)
+            }
+            "bcd" => code(0xF033 | x(self.register()?)),
+            "saveflags" => code(0xF075 | x(self.register()?)),
+            "loadflags" => code(0xF085 | x(self.register()?)),
+            "save" | "load" => {
+                let vx = self.register()?;
+                let store = token.text == "save";
+
+                if self.peek_is("-") {
+                    self.pos += 1;
+                    let vy = self.register()?;
+
+                    code(if store { 0x5002 } else { 0x5003 } | xy(vx, vy))
+                } else {
+                    code(if store { 0xF055 } else { 0xF065 } | x(vx))
+                }
+            }
+            "plane" => operand(0xF001, Field::X, self.value()?),
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let vx = self.register()?;
+
+                code(
+                    match token.text.as_str() {
+                        "delay" => 0xF015,
+                        "buzzer" => 0xF018,
+                        _ => 0xF03A,
+                    } | x(vx),
+                )
+            }
+            "i" => {
+                let op = self.next("':=' or '+='")?;
+
+                match op.text.as_str() {
+                    ":=" if self.peek_is("long") => {
+                        self.pos += 1;
+                        operand(0xF000, Field::Long, self.value()?)
+                    }
+                    ":=" if self.peek_is("hex") => {
+                        self.pos += 1;
+                        code(0xF029 | x(self.register()?))
+                    }
+                    ":=" if self.peek_is("bighex") => {
+                        self.pos += 1;
+                        code(0xF030 | x(self.register()?))
+                    }
+                    ":=" => operand(0xA000, Field::Nnn, self.value()?),
+                    "+=" => code(0xF01E | x(self.register()?)),
+                    _ => return Err(op.error(format!("unknown operator '{}' for i", op.text))),
+                }
+            }
+            "if" => {
+                let vx = self.register()?;
+                let op = self.next("a comparison")?;
+
+                let item = match op.text.as_str() {
+                    "key" => code(0xE0A1 | x(vx)),
+                    "-key" => code(0xE09E | x(vx)),
+                    "==" | "!=" => {
+                        let equal = op.text == "==";
+
+                        match self.peek_register() {
+                            Some(vy) => {
+                                self.pos += 1;
+                                code(if equal { 0x9000 } else { 0x5000 } | xy(vx, vy))
+                            }
+                            None => {
+                                let base = if equal { 0x4000 } else { 0x3000 };
+
+                                operand(base | x(vx), Field::Nn, self.value()?)
+                            }
+                        }
+                    }
+                    _ => {
+                        return Err(op.error(format!("unknown comparison '{}'", op.text)));
+                    }
+                };
+
+                self.expect("then")?;
+                item
+            }
+            _ if register(&token).is_some() => {
+                let vx = register(&token).unwrap();
+                let op = self.next("an operator")?;
+                let vy = self.peek_register();
+
+                if vy.is_some() {
+                    self.pos += 1;
+                }
+
+                match (op.text.as_str(), vy) {
+                    (":=", Some(vy)) => code(0x8000 | xy(vx, vy)),
+                    ("|=", Some(vy)) => code(0x8001 | xy(vx, vy)),
+                    ("&=", Some(vy)) => code(0x8002 | xy(vx, vy)),
+                    ("^=", Some(vy)) => code(0x8003 | xy(vx, vy)),
+                    ("+=", Some(vy)) => code(0x8004 | xy(vx, vy)),
+                    ("-=", Some(vy)) => code(0x8005 | xy(vx, vy)),
+                    (">>=", Some(vy)) => code(0x8006 | xy(vx, vy)),
+                    ("=-", Some(vy)) => code(0x8007 | xy(vx, vy)),
+                    ("<<=", Some(vy)) => code(0x800E | xy(vx, vy)),
+                    (":=", None) if self.peek_is("random") => {
+                        self.pos += 1;
+                        operand(0xC000 | x(vx), Field::Nn, self.value()?)
+                    }
+                    (":=", None) if self.peek_is("delay") => {
+                        self.pos += 1;
+                        code(0xF007 | x(vx))
+                    }
+                    (":=", None) if self.peek_is("key") => {
+                        self.pos += 1;
+                        code(0xF00A | x(vx))
+                    }
+                    (":=", None) => operand(0x6000 | x(vx), Field::Nn, self.value()?),
+                    ("+=", None) => operand(0x7000 | x(vx), Field::Nn, self.value()?),
+                    ("|=", None)
+                    | ("&=", None)
+                    | ("^=", None)
+                    | ("-=", None)
+                    | (">>=", None)
+                    | ("=-", None)
+                    | ("<<=", None) => {
+                        let found = self.next("a register")?;
+
+                        return Err(found.error(format!(
+                            "expected a register (v0-vf), found '{}'",
+                            found.text
+                        )));
+                    }
+                    _ => {
+                        return Err(op.error(format!("unknown operator '{}'", op.text)));
+                    }
+                }
+            }
+            text if text.starts_with(':') => {
+                return Err(token.error(format!("unknown directive '{}'", text)));
+            }
+            _ if ASSIGNMENTS.iter().any(|op| self.peek_is(op)) => {
+                return Err(token.error(format!("'{}' is not a register", token.text)));
+            }
+            // a bare constant or number is a byte of data, a bare label a call
+            text if self.consts.contains(text) || !is_name(text) => Item::Byte(token.clone()),
+            _ => operand(0x2000, Field::Nnn, token.clone()),
+        };
+
+        Ok(Statement { item, token })
+    }
+}
+
+fn register(token: &Token) -> Option<u16> {
+    let mut chars = token.text.chars();
+
+    match (token.kind, chars.next(), chars.next(), chars.next()) {
+        (Kind::Word, Some('v'), Some(r), None) | (Kind::Word, Some('V'), Some(r), None) => {
+            r.to_digit(16).map(|r| r as u16)
+        }
+        _ => None,
+    }
+}
+
+fn is_name(text: &str) -> bool {
+    let mut chars = text.chars();
+    let first = chars.next();
+
+    first.is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        && !(text.len() == 2
+            && (text.starts_with('v') || text.starts_with('V'))
+            && text[1..].chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+// First pass: works out the address of every label and the value of every
+// constant, in source order.
+fn layout(statements: &[Statement]) -> Result<HashMap<String, i64>, AsmError> {
+    let mut symbols = HashMap::new();
+    let mut address = LOWER_MEMORY_BOUNDARY as i64;
+
+    for statement in statements.iter() {
+        let (name, value) = match &statement.item {
+            Item::Label(name) => (name, address),
+            Item::Const(name, value) => (name, evaluate(value, &symbols, address)?),
+            Item::Byte(_) => {
+                address += 1;
+                continue;
+            }
+            Item::Code(_, operand) => {
+                address += match operand {
+                    Some((Field::Long, _)) => 4,
+                    _ => 2,
+                };
+                continue;
+            }
+        };
+
+        if symbols.insert(name.clone(), value).is_some() {
+            return Err(statement
+                .token
+                .error(format!("'{}' is already defined", name)));
+        }
+    }
+
+    if address > ADDRESS_SPACE {
+        let last = &statements[statements.len() - 1].token;
+
+        return Err(last.error("program is too large to fit in memory".to_string()));
+    }
+
+    Ok(symbols)
+}
+
+// Second pass: evaluates every operand and writes out the bytes.
+fn emit(statements: &[Statement], symbols: &HashMap<String, i64>) -> Result<Vec<u8>, AsmError> {
+    let mut rom = vec![];
+
+    for statement in statements.iter() {
+        let here = (LOWER_MEMORY_BOUNDARY + rom.len()) as i64;
+
+        match &statement.item {
+            Item::Label(_) | Item::Const(_, _) => {}
+            Item::Byte(value) => {
+                let byte = checked(value, evaluate(value, symbols, here)?, (-128, 0xFF))?;
+
+                rom.push(byte as u8);
+            }
+            Item::Code(opcode, None) => rom.extend_from_slice(&opcode.to_be_bytes()),
+            Item::Code(opcode, Some((field, value))) => {
+                let v = checked(value, evaluate(value, symbols, here)?, field.range())?;
+
+                let opcode = match field {
+                    Field::Nnn => opcode | v as u16,
+                    Field::Nn => opcode | (v as u16 & 0xFF),
+                    Field::N => opcode | v as u16,
+                    Field::X => opcode | (v as u16) << 8,
+                    Field::Long => {
+                        rom.extend_from_slice(&opcode.to_be_bytes());
+                        v as u16
+                    }
+                };
+
+                rom.extend_from_slice(&opcode.to_be_bytes());
+            }
+        }
+    }
+
+    Ok(rom)
+}
+
+fn checked(token: &Token, value: i64, (min, max): (i64, i64)) -> Result<i64, AsmError> {
+    if value < min || value > max {
+        return Err(token.error(format!(
+            "value {} is out of range ({} to {})",
+            value, min, max
+        )));
+    }
+
+    Ok(value)
+}
+
+fn evaluate(token: &Token, symbols: &HashMap<String, i64>, here: i64) -> Result<i64, AsmError> {
+    // names can contain '-', so look a whole word up before treating it as
+    // an expression
+    if token.kind == Kind::Word {
+        if let Some(value) = symbols.get(&token.text) {
+            return Ok(*value);
+        }
+    }
+
+    let mut expression = Expression {
+        chars: token.text.chars().collect(),
+        pos: 0,
+        token,
+        symbols,
+        here,
+    };
+
+    let value = expression.or()?;
+
+    expression.skip_whitespace();
+
+    match expression.chars.get(expression.pos) {
+        Some(c) => Err(expression.error(format!("unexpected '{}'", c))),
+        None => Ok(value),
+    }
+}
+
+// A recursive descent parser for C-style integer expressions. From lowest
+// to highest precedence: | ^ & (<< >>) (+ -) (* / %) and unary - ~.
+struct Expression<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    token: &'a Token,
+    symbols: &'a HashMap<String, i64>,
+    here: i64,
+}
+
+impl<'a> Expression<'a> {
+    fn error(&self, message: String) -> AsmError {
+        self.token.error_at(self.pos, message)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    // consumes `op` if it comes next
+    fn eat(&mut self, op: &str) -> bool {
+        self.skip_whitespace();
+
+        let matches = op
+            .chars()
+            .enumerate()
+            .all(|(i, c)| self.chars.get(self.pos + i) == Some(&c));
+
+        if matches {
+            self.pos += op.chars().count();
+        }
+
+        matches
+    }
+
+    fn arithmetic(&self, value: Option<i64>) -> Result<i64, AsmError> {
+        value.ok_or_else(|| self.error("arithmetic overflow".to_string()))
+    }
+
+    fn or(&mut self) -> Result<i64, AsmError> {
+        let mut value = self.xor()?;
+
+        while self.eat("|") {
+            value |= self.xor()?;
+        }
+
+        Ok(value)
+    }
+
+    fn xor(&mut self) -> Result<i64, AsmError> {
+        let mut value = self.and()?;
+
+        while self.eat("^") {
+            value ^= self.and()?;
+        }
+
+        Ok(value)
+    }
+
+    fn and(&mut self) -> Result<i64, AsmError> {
+        let mut value = self.shift()?;
+
+        while self.eat("&") {
+            value &= self.shift()?;
+        }
+
+        Ok(value)
+    }
+
+    fn shift(&mut self) -> Result<i64, AsmError> {
+        let mut value = self.additive()?;
+
+        loop {
+            if self.eat("<<") {
+                let by = self.additive()?;
+                value =
+                    self.arithmetic(u32::try_from(by).ok().and_then(|b| value.checked_shl(b)))?;
+            } else if self.eat(">>") {
+                let by = self.additive()?;
+                value =
+                    self.arithmetic(u32::try_from(by).ok().and_then(|b| value.checked_shr(b)))?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn additive(&mut self) -> Result<i64, AsmError> {
+        let mut value = self.multiplicative()?;
+
+        loop {
+            if self.eat("+") {
+                let rhs = self.multiplicative()?;
+                value = self.arithmetic(value.checked_add(rhs))?;
+            } else if self.eat("-") {
+                let rhs = self.multiplicative()?;
+                value = self.arithmetic(value.checked_sub(rhs))?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<i64, AsmError> {
+        let mut value = self.unary()?;
+
+        loop {
+            if self.eat("*") {
+                let rhs = self.unary()?;
+                value = self.arithmetic(value.checked_mul(rhs))?;
+            } else if self.eat("/") {
+                let rhs = self.unary()?;
+                value = self.arithmetic(value.checked_div(rhs))?;
+            } else if self.eat("%") {
+                let rhs = self.unary()?;
+                value = self.arithmetic(value.checked_rem(rhs))?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<i64, AsmError> {
+        if self.eat("-") {
+            let value = self.unary()?;
+            self.arithmetic(value.checked_neg())
+        } else if self.eat("~") {
+            Ok(!self.unary()?)
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<i64, AsmError> {
+        self.skip_whitespace();
+
+        if self.eat("(") {
+            let value = self.or()?;
+
+            if !self.eat(")") {
+                return Err(self.error("expected ')'".to_string()));
+            }
+
+            return Ok(value);
+        }
+
+        let start = self.pos;
+
+        while self
+            .chars
+            .get(self.pos)
+            .is_some_and(|c| c.is_ascii_alphanumeric() || *c == '_')
+        {
+            self.pos += 1;
+        }
+
+        let word: String = self.chars[start..self.pos].iter().collect();
+
+        if word.is_empty() {
+            self.pos = start;
+
+            return Err(match self.chars.get(self.pos) {
+                Some(c) => self.error(format!("unexpected '{}'", c)),
+                None => self.error("expected a value".to_string()),
+            });
+        }
+
+        let value = if word.starts_with(|c: char| c.is_ascii_digit()) {
+            let lower = word.to_lowercase();
+
+            if let Some(hex) = lower.strip_prefix("0x") {
+                i64::from_str_radix(hex, 16).ok()
+            } else if let Some(binary) = lower.strip_prefix("0b") {
+                i64::from_str_radix(binary, 2).ok()
+            } else {
+                lower.parse::<i64>().ok()
+            }
+        } else if word == "HERE" {
+            Some(self.here)
+        } else {
+            let value = self.symbols.get(&word).copied();
+
+            if value.is_none() {
+                self.pos = start;
+                return Err(self.error(format!("undefined name '{}'", word)));
+            }
+
+            value
+        };
+
+        value.ok_or_else(|| {
+            self.pos = start;
+            self.error(format!("invalid number '{}'", word))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::assemble;
+    use crate::{disassemble, AsmError, Syntax};
+    use std::fs;
+
+    fn error(source: &str) -> AsmError {
+        assemble(source, None).unwrap_err()
+    }
+
+    #[test]
+    fn test_assemble_instructions() {
+        let source = "
+            : main
+                clear
+                v0 := 0x0A
+                v1 += v2
+                i := long 0x1234
+                if v3 != 7 then
+                sprite v0 v1 5
+                jump main
+        ";
+
+        let rom = assemble(source, None).unwrap();
+
+        assert_eq!(
+            rom,
+            vec![
+                0x00, 0xE0, 0x60, 0x0A, 0x81, 0x24, 0xF0, 0x00, 0x12, 0x34, 0x33, 0x07, 0xD0, 0x15,
+                0x12, 0x00
+            ]
+        );
+    }
+
+    #[test]
+    fn test_labels_and_calls() {
+        let source = "
+            draw   # a bare label is a call
+            :call draw
+            exit
+            : draw
+            return
+        ";
+
+        let rom = assemble(source, None).unwrap();
+
+        assert_eq!(rom, vec![0x22, 0x06, 0x22, 0x06, 0x00, 0xFD, 0x00, 0xEE]);
+    }
+
+    #[test]
+    fn test_constants_and_expressions() {
+        let source = "
+            :const SPEED 3
+            :calc DOUBLE { SPEED * 2 + 1 }
+            v0 := DOUBLE
+            v1 := { ( SPEED << 4 ) | 0x0F }
+            v2 += -1
+            i := { data + 1 }
+            : data
+            SPEED
+            :byte { HERE & 0xFF }
+            0b1010
+        ";
+
+        let rom = assemble(source, None).unwrap();
+
+        assert_eq!(
+            rom,
+            vec![0x60, 0x07, 0x61, 0x3F, 0x72, 0xFF, 0xA2, 0x09, 0x03, 0x09, 0x0A]
+        );
+    }
+
+    #[test]
+    fn test_include() {
+        let dir = std::env::temp_dir().join(format!("chip-8-asm-{}", std::process::id()));
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        fs::write(dir.join("lib/sprites.8o"), ": dot\n0x80\n").unwrap();
+        fs::write(dir.join("lib.8o"), ":include \"lib/sprites.8o\"\n").unwrap();
+
+        let main = dir.join("main.8o");
+        let rom = assemble("i := dot\n:include \"lib.8o\"\n", Some(&main)).unwrap();
+
+        assert_eq!(rom, vec![0xA2, 0x02, 0x80]);
+
+        let err = assemble(":include \"missing.8o\"", Some(&main)).unwrap_err();
+        assert_eq!((err.line, err.column), (1, 10));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_error_positions() {
+        let err = error("clear\n  v0 := nowhere");
+        assert_eq!((err.line, err.column), (2, 9));
+        assert_eq!(err.message, "undefined name 'nowhere'");
+
+        let err = error("v0 := { 1 + }");
+        assert_eq!((err.line, err.column), (1, 13));
+
+        let err = error("  sprite v0 v1 16");
+        assert_eq!((err.line, err.column), (1, 16));
+
+        let err = error("vg := 1");
+        assert_eq!((err.line, err.column), (1, 1));
+
+        let err = error(": a\n: a");
+        assert_eq!((err.line, err.column), (2, 1));
+        assert_eq!(err.to_string(), "2:1: 'a' is already defined");
+
+        let err = error("v0 |= 1");
+        assert_eq!((err.line, err.column), (1, 7));
+
+        let err = error("if v0 == 1");
+        assert_eq!((err.line, err.column), (1, 11));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let rom: Vec<u8> = vec![
+            0x22, 0x08, // call
+            0x3A, 0x01, // skip
+            0x12, 0x00, // jump
+            0x00, 0xFD, // exit
+            0xA2, 0x14, // i := sprite
+            0xD0, 0x13, // sprite
+            0x8A, 0xB7, // =-
+            0xC0, 0xFF, // random
+            0xB2, 0x00, // jump0
+            0x00, 0xEE, // return
+            0x3C, 0x42, 0x3C, // sprite data
+            0x01, 0x02, 0x03, // unreached
+        ];
+
+        let listing = disassemble(&rom, true, Syntax::Octo);
+
+        assert_eq!(assemble(&listing, None).unwrap(), rom, "{}", listing);
+    }
+
+    #[test]
+    fn test_round_trip_every_opcode() {
+        for opcode in 0..=0xFFFFu16 {
+            let rom = vec![(opcode >> 8) as u8, opcode as u8, 0x12, 0x34];
+            let listing = disassemble(&rom, true, Syntax::Octo);
+
+            assert_eq!(assemble(&listing, None).unwrap(), rom, "{}", listing);
+        }
+    }
+}
@@ -0,0 +1,348 @@
+use crate::errors::CommandError;
+use crate::Chip8;
+
+// Breakpoint conditions, such as `v3 == 0x10 && i > 0x300`.
+//
+//     or      := and ('||' and)*
+//     and     := compare ('&&' compare)*
+//     compare := sum (('==' | '!=' | '<' | '<=' | '>' | '>=') sum)?
+//     sum     := unary (('+' | '-' | '&') unary)*
+//     unary   := '!' unary | primary
+//     primary := NUMBER | REGISTER | '[' or ']' | '(' or ')'
+//
+// Registers are v0-vf, i, pc, sp, dt and st; `[ADDR]` is the byte in memory
+// at ADDR. Comparisons are 1 when true and 0 when false, and the condition
+// holds when the whole expression isn't 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    text: String,
+    expression: Expression,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operand {
+    V(usize),
+    I,
+    Pc,
+    Sp,
+    Dt,
+    St,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    BitAnd,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expression {
+    Number(u32),
+    Register(Operand),
+    Memory(Box<Expression>),
+    Not(Box<Expression>),
+    Binary(Operator, Box<Expression>, Box<Expression>),
+}
+
+// longest first, so that `<=` isn't read as `<` followed by `=`
+const SYMBOLS: [&str; 16] = [
+    "||", "&&", "==", "!=", "<=", ">=", "<", ">", "+", "-", "&", "!", "(", ")", "[", "]",
+];
+
+impl Condition {
+    pub fn parse(text: &str) -> Result<Condition, CommandError> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            position: 0,
+        };
+        let expression = parser.or()?;
+
+        if let Some(token) = parser.peek() {
+            return Err(invalid(format!("unexpected '{}' in condition", token)));
+        }
+
+        Ok(Condition {
+            text: text.trim().to_string(),
+            expression,
+        })
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn holds(&self, chip8: &Chip8) -> bool {
+        evaluate(&self.expression, chip8) != 0
+    }
+}
+
+fn invalid(message: String) -> CommandError {
+    CommandError::Invalid(message)
+}
+
+fn tokenize(text: &str) -> Result<Vec<String>, CommandError> {
+    let mut tokens = vec![];
+    let mut rest = text.trim_start();
+
+    while let Some(c) = rest.chars().next() {
+        let length = if c.is_ascii_alphanumeric() {
+            rest.find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(rest.len())
+        } else if let Some(symbol) = SYMBOLS.iter().find(|s| rest.starts_with(*s)) {
+            symbol.len()
+        } else {
+            return Err(invalid(format!("unexpected '{}' in condition", c)));
+        };
+
+        tokens.push(rest[..length].to_lowercase());
+        rest = rest[length..].trim_start();
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [String],
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.position).map(String::as_str)
+    }
+
+    fn next(&mut self) -> Result<&'a str, CommandError> {
+        let token = self
+            .peek()
+            .ok_or_else(|| invalid("condition ends too soon".to_string()))?;
+
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), CommandError> {
+        match self.next()? {
+            token if token == expected => Ok(()),
+            token => Err(invalid(format!(
+                "expected '{}' in condition, found '{}'",
+                expected, token
+            ))),
+        }
+    }
+
+    // Parses a run of operands separated by any of `operators`, grouping to
+    // the left.
+    fn binary(
+        &mut self,
+        operators: &[(&str, Operator)],
+        operand: fn(&mut Self) -> Result<Expression, CommandError>,
+    ) -> Result<Expression, CommandError> {
+        let mut left = operand(self)?;
+
+        while let Some(&(_, operator)) = operators.iter().find(|(s, _)| self.peek() == Some(s)) {
+            self.position += 1;
+            left = Expression::Binary(operator, Box::new(left), Box::new(operand(self)?));
+        }
+
+        Ok(left)
+    }
+
+    fn or(&mut self) -> Result<Expression, CommandError> {
+        self.binary(&[("||", Operator::Or)], Self::and)
+    }
+
+    fn and(&mut self) -> Result<Expression, CommandError> {
+        self.binary(&[("&&", Operator::And)], Self::compare)
+    }
+
+    fn compare(&mut self) -> Result<Expression, CommandError> {
+        let left = self.sum()?;
+        let operator = match self.peek() {
+            Some("==") => Operator::Eq,
+            Some("!=") => Operator::Ne,
+            Some("<") => Operator::Lt,
+            Some("<=") => Operator::Le,
+            Some(">") => Operator::Gt,
+            Some(">=") => Operator::Ge,
+            _ => return Ok(left),
+        };
+
+        self.position += 1;
+        Ok(Expression::Binary(
+            operator,
+            Box::new(left),
+            Box::new(self.sum()?),
+        ))
+    }
+
+    fn sum(&mut self) -> Result<Expression, CommandError> {
+        self.binary(
+            &[
+                ("+", Operator::Add),
+                ("-", Operator::Sub),
+                ("&", Operator::BitAnd),
+            ],
+            Self::unary,
+        )
+    }
+
+    fn unary(&mut self) -> Result<Expression, CommandError> {
+        if self.peek() == Some("!") {
+            self.position += 1;
+            return Ok(Expression::Not(Box::new(self.unary()?)));
+        }
+
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expression, CommandError> {
+        let token = self.next()?;
+
+        match token {
+            "(" => {
+                let inner = self.or()?;
+
+                self.expect(")")?;
+                Ok(inner)
+            }
+            "[" => {
+                let address = self.or()?;
+
+                self.expect("]")?;
+                Ok(Expression::Memory(Box::new(address)))
+            }
+            _ => {
+                if let Some(operand) = operand(token) {
+                    Ok(Expression::Register(operand))
+                } else if let Some(number) = number(token) {
+                    Ok(Expression::Number(number))
+                } else {
+                    Err(invalid(format!(
+                        "expected a value in condition, found '{}'",
+                        token
+                    )))
+                }
+            }
+        }
+    }
+}
+
+fn operand(token: &str) -> Option<Operand> {
+    match token {
+        "i" => Some(Operand::I),
+        "pc" => Some(Operand::Pc),
+        "sp" => Some(Operand::Sp),
+        "dt" => Some(Operand::Dt),
+        "st" => Some(Operand::St),
+        _ => token
+            .strip_prefix('v')
+            .filter(|r| r.len() == 1)
+            .and_then(|r| usize::from_str_radix(r, 16).ok())
+            .map(Operand::V),
+    }
+}
+
+fn number(token: &str) -> Option<u32> {
+    if let Some(hex) = token.strip_prefix("0x") {
+        u32::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = token.strip_prefix("0b") {
+        u32::from_str_radix(binary, 2).ok()
+    } else {
+        token.parse().ok()
+    }
+}
+
+fn evaluate(expression: &Expression, chip8: &Chip8) -> u32 {
+    match expression {
+        Expression::Number(n) => *n,
+        Expression::Register(operand) => match operand {
+            Operand::V(x) => chip8.v()[*x] as u32,
+            Operand::I => chip8.i() as u32,
+            Operand::Pc => chip8.pc() as u32,
+            Operand::Sp => chip8.sp() as u32,
+            Operand::Dt => chip8.delay_timer() as u32,
+            Operand::St => chip8.sound_timer as u32,
+        },
+        // reading outside of memory gives 0 rather than an error, since
+        // conditions are checked over and over while the program runs
+        Expression::Memory(address) => {
+            let address = evaluate(address, chip8) as usize;
+
+            chip8.memory().get(address).copied().unwrap_or(0) as u32
+        }
+        Expression::Not(inner) => (evaluate(inner, chip8) == 0) as u32,
+        Expression::Binary(operator, left, right) => {
+            let left = evaluate(left, chip8);
+
+            // && and || only look at the right hand side when they need to
+            match operator {
+                Operator::Or if left != 0 => return 1,
+                Operator::And if left == 0 => return 0,
+                _ => {}
+            }
+
+            let right = evaluate(right, chip8);
+
+            match operator {
+                Operator::Or | Operator::And => (right != 0) as u32,
+                Operator::Eq => (left == right) as u32,
+                Operator::Ne => (left != right) as u32,
+                Operator::Lt => (left < right) as u32,
+                Operator::Le => (left <= right) as u32,
+                Operator::Gt => (left > right) as u32,
+                Operator::Ge => (left >= right) as u32,
+                Operator::Add => left.wrapping_add(right),
+                Operator::Sub => left.wrapping_sub(right),
+                Operator::BitAnd => left & right,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Condition;
+    use crate::{Chip8, Quirks};
+
+    fn holds(chip8: &Chip8, text: &str) -> bool {
+        Condition::parse(text).unwrap().holds(chip8)
+    }
+
+    #[test]
+    fn test_conditions() {
+        let mut chip8 = Chip8::new(Quirks::default());
+
+        chip8.set_v(3, 0x10);
+        chip8.set_i(0x310);
+        chip8.memory_mut()[0x310] = 7;
+
+        assert!(holds(&chip8, "v3 == 0x10 && i > 0x300"));
+        assert!(holds(&chip8, "V3==16&&I>0x300"));
+        assert!(!holds(&chip8, "v3 == 0x10 && i > 0x400"));
+        assert!(holds(&chip8, "v3 != 0x10 || [i] == 7"));
+        assert!(holds(&chip8, "!(v0 >= 1) && [i + 1] == 0"));
+        assert!(holds(&chip8, "v3 & 0b10000"));
+        assert!(holds(&chip8, "pc - 0x200 <= v0"));
+        assert!(!holds(&chip8, "0"));
+
+        // out of range memory reads as 0
+        assert!(holds(&chip8, "[0xFFFF] == 0"));
+    }
+
+    #[test]
+    fn test_bad_conditions() {
+        for text in ["", "v3 ==", "vg == 1", "(v3", "v3 = 1", "v3 == 1 v4", "[i"].iter() {
+            assert!(Condition::parse(text).is_err(), "{}", text);
+        }
+    }
+}
@@ -0,0 +1,1029 @@
+use std::convert::TryFrom;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::sync::mpsc::{self, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+
+use crate::debugger::STEP_LIMIT;
+use crate::{
+    mnemonic, Chip8, Chip8Fault, Debugger, Instruction, Quirks, StepOutcome, Stop, Syntax,
+};
+
+const CYCLES_PER_SECOND: u32 = 500;
+const FRAMES_PER_SECOND: u32 = 60;
+// a CHIP-8 program has a single thread of execution
+const THREAD_ID: u32 = 1;
+const REGISTERS_REFERENCE: u32 = 1;
+const TIMERS_REFERENCE: u32 = 2;
+const STACK_REFERENCE: u32 = 3;
+// more instructions than there are in the biggest memory, so a bigger
+// disassemble request can only be a mistake
+const DISASSEMBLY_LIMIT: u64 = 0x8000;
+// far bigger than any request a client has reason to send
+const MESSAGE_LIMIT: usize = 1 << 20;
+
+// A Debug Adapter Protocol session, so editors such as VS Code can launch
+// and debug a ROM. Requests go in through `handle`, and everything the
+// adapter has to say back, responses and events alike, comes out as JSON
+// messages. While the program is running, `run_frame` has to be called 60
+// times a second. `serve_dap` does all of that over a pair of streams.
+//
+// There are no source files to map to, so breakpoints are set by address
+// (setInstructionBreakpoints), and the disassembly view shows the code.
+pub struct DapSession {
+    seq: u64,
+    target: Option<Target>,
+    breakpoints: Vec<u16>,
+    stop_on_entry: bool,
+    configured: bool,
+    finished: bool,
+}
+
+struct Target {
+    chip8: Chip8,
+    debugger: Debugger,
+}
+
+#[derive(Clone, Copy)]
+enum StepKind {
+    In,
+    Over,
+    Out,
+}
+
+impl Default for DapSession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DapSession {
+    pub fn new() -> DapSession {
+        DapSession {
+            seq: 0,
+            target: None,
+            breakpoints: vec![],
+            stop_on_entry: false,
+            configured: false,
+            finished: false,
+        }
+    }
+
+    // whether the program is running, and so needs `run_frame` calling
+    pub fn is_running(&self) -> bool {
+        self.target
+            .as_ref()
+            .is_some_and(|t| !t.debugger.is_paused())
+    }
+
+    // whether the client has disconnected
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    // Handles one request, returning the response and any events.
+    pub fn handle(&mut self, request: &Value) -> Vec<Value> {
+        let command = request["command"].as_str().unwrap_or_default();
+        let arguments = &request["arguments"];
+        let mut messages = vec![];
+
+        let result = match command {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsInstructionBreakpoints": true,
+                "supportsDisassembleRequest": true,
+                "supportsSetVariable": true,
+            })),
+            "launch" => self.launch(arguments),
+            "setBreakpoints" => {
+                // there are no source lines to put breakpoints on
+                let lines = arguments["breakpoints"].as_array().map_or(0, Vec::len);
+                let breakpoints = vec![json!({"verified": false}); lines];
+
+                Ok(json!({ "breakpoints": breakpoints }))
+            }
+            "setInstructionBreakpoints" => self.set_breakpoints(arguments),
+            "configurationDone" => {
+                self.configured = true;
+                Ok(Value::Null)
+            }
+            "threads" => Ok(json!({"threads": [{"id": THREAD_ID, "name": "CHIP-8"}]})),
+            "stackTrace" => self.with_target(stack_trace),
+            "scopes" => Ok(json!({
+                "scopes": [
+                    scope("Registers", REGISTERS_REFERENCE),
+                    scope("Timers", TIMERS_REFERENCE),
+                    scope("Stack", STACK_REFERENCE),
+                ]
+            })),
+            "variables" => {
+                let reference = arguments["variablesReference"].as_u64().unwrap_or_default();
+
+                self.with_target(|target| variables(target, reference as u32))
+            }
+            "setVariable" => self.set_variable(arguments),
+            "disassemble" => self.with_target(|target| disassemble(target, arguments)),
+            "evaluate" => self.evaluate(arguments),
+            "continue" => self.with_target(|target| {
+                target.debugger.resume();
+                Ok(json!({"allThreadsContinued": true}))
+            }),
+            "pause" => self.with_target(|target| {
+                target.debugger.pause();
+                Ok(Value::Null)
+            }),
+            "next" | "stepIn" | "stepOut" => {
+                let kind = match command {
+                    "next" => StepKind::Over,
+                    "stepIn" => StepKind::In,
+                    _ => StepKind::Out,
+                };
+
+                match self.target.as_mut() {
+                    Some(target) => {
+                        let stop = step(target, kind);
+
+                        messages.push(self.response(request, Ok(Value::Null)));
+                        messages.extend(self.stopped(stop, "step"));
+                        return messages;
+                    }
+                    None => Err("no program has been launched".to_string()),
+                }
+            }
+            "disconnect" | "terminate" => {
+                self.finished = true;
+                Ok(Value::Null)
+            }
+            _ => Err(format!("unsupported request '{}'", command)),
+        };
+
+        messages.push(self.response(request, result));
+
+        match command {
+            "initialize" => messages.push(self.event("initialized", Value::Null)),
+            "pause" if self.target.is_some() => {
+                messages.push(self.stopped_event("pause", None));
+            }
+            "disconnect" | "terminate" => messages.push(self.event("terminated", Value::Null)),
+            _ => {}
+        }
+
+        // the program starts once it has been both launched and configured
+        if matches!(command, "launch" | "configurationDone") && self.configured {
+            if let Some(target) = self.target.as_mut() {
+                if self.stop_on_entry {
+                    messages.push(self.stopped_event("entry", None));
+                } else {
+                    target.debugger.resume();
+                }
+            }
+        }
+
+        messages
+    }
+
+    // Runs one frame of a running program, returning any events about it
+    // stopping.
+    pub fn run_frame(&mut self) -> Vec<Value> {
+        let target = match self.target.as_mut() {
+            Some(target) if !target.debugger.is_paused() => target,
+            _ => return vec![],
+        };
+
+        let stop = target.debugger.run_frame(&mut target.chip8);
+
+        if stop.is_err() {
+            target.debugger.pause();
+        }
+
+        self.stopped(stop, "")
+    }
+
+    fn launch(&mut self, arguments: &Value) -> Result<Value, String> {
+        let path = arguments["program"]
+            .as_str()
+            .ok_or_else(|| "launch needs a 'program' to run".to_string())?;
+        let quirks = match arguments["quirks"].as_str() {
+            Some(name) => Quirks::from_name(name)
+                .ok_or_else(|| format!("unknown quirks profile: {}", name))?,
+            None => Quirks::default(),
+        };
+        let cycles_per_second = arguments["cyclesPerSecond"]
+            .as_u64()
+            .map_or(CYCLES_PER_SECOND, |c| c as u32);
+
+        let mut chip8 = if arguments["xoChip"].as_bool().unwrap_or_default() {
+            Chip8::new_xo_chip(quirks)
+        } else {
+            Chip8::new(quirks)
+        };
+
+        if let Some(seed) = arguments["seed"].as_u64() {
+            chip8.seed(seed);
+        }
+
+        let program = fs::read(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+
+        chip8
+            .load_program(program)
+            .map_err(|e| format!("can't load {}: {}", path, e))?;
+
+        let mut debugger = Debugger::new((cycles_per_second / FRAMES_PER_SECOND).max(1));
+
+        for address in &self.breakpoints {
+            debugger.add_breakpoint(*address);
+        }
+
+        self.stop_on_entry = arguments["stopOnEntry"].as_bool().unwrap_or_default();
+        self.target = Some(Target { chip8, debugger });
+
+        Ok(Value::Null)
+    }
+
+    // Breakpoints can arrive before the program is launched, so they're
+    // kept here as well as in the debugger.
+    fn set_breakpoints(&mut self, arguments: &Value) -> Result<Value, String> {
+        let requested = arguments["breakpoints"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        let mut addresses = vec![];
+        let mut breakpoints = vec![];
+
+        // the old breakpoints stay if any of the new ones can't be worked out
+        for breakpoint in &requested {
+            let offset = breakpoint["offset"].as_i64().unwrap_or_default();
+            let address = match breakpoint["instructionReference"]
+                .as_str()
+                .and_then(parse_address)
+            {
+                Some(address) => Some(
+                    address
+                        .checked_add(offset)
+                        .ok_or_else(|| "breakpoint offset out of range".to_string())?,
+                ),
+                None => None,
+            };
+
+            addresses.push(address);
+        }
+
+        self.breakpoints.clear();
+
+        for address in addresses {
+            match address {
+                Some(address) if (0..=0xFFFF).contains(&address) => {
+                    self.breakpoints.push(address as u16);
+                    breakpoints.push(json!({
+                        "verified": true,
+                        "instructionReference": format!("{:#05X}", address),
+                    }));
+                }
+                _ => breakpoints.push(json!({
+                    "verified": false,
+                    "message": "not an address",
+                })),
+            }
+        }
+
+        if let Some(target) = self.target.as_mut() {
+            target.debugger.clear_breakpoints();
+
+            for address in &self.breakpoints {
+                target.debugger.add_breakpoint(*address);
+            }
+        }
+
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+
+    fn set_variable(&mut self, arguments: &Value) -> Result<Value, String> {
+        let name = arguments["name"].as_str().unwrap_or_default().to_string();
+        let value = arguments["value"].as_str().unwrap_or_default().to_string();
+
+        self.with_target(|target| {
+            target
+                .debugger
+                .command(&mut target.chip8, &format!("set {} {}", name, value))
+                .map_err(|e| e.to_string())?;
+
+            let value = registers(&target.chip8)
+                .into_iter()
+                .chain(timers(&target.chip8))
+                .find(|(n, _)| n.eq_ignore_ascii_case(&name))
+                .map(|(_, v)| v)
+                .unwrap_or(value);
+
+            Ok(json!({ "value": value }))
+        })
+    }
+
+    // Debug console input is run as a debugger command, such as `mem 0x300`
+    // or `poke 0x300 1 2 3`.
+    fn evaluate(&mut self, arguments: &Value) -> Result<Value, String> {
+        let expression = arguments["expression"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+
+        self.with_target(|target| {
+            let result = target
+                .debugger
+                .command(&mut target.chip8, &expression)
+                .map_err(|e| e.to_string())?;
+
+            Ok(json!({"result": result, "variablesReference": 0}))
+        })
+    }
+
+    fn with_target<F>(&mut self, f: F) -> Result<Value, String>
+    where
+        F: FnOnce(&mut Target) -> Result<Value, String>,
+    {
+        match self.target.as_mut() {
+            Some(target) => f(target),
+            None => Err("no program has been launched".to_string()),
+        }
+    }
+
+    // The events for the end of a step or frame: a stopped event, or exited
+    // and terminated events if the program exited. `reason` is used when it
+    // stopped for no reason in particular.
+    fn stopped(&mut self, stop: Result<Option<Stop>, Chip8Fault>, reason: &str) -> Vec<Value> {
+        match stop {
+            Ok(Some(Stop::Exited)) => {
+                let exited = self.event("exited", json!({"exitCode": 0}));
+
+                vec![exited, self.event("terminated", Value::Null)]
+            }
+            Ok(Some(Stop::Breakpoint(_))) => {
+                vec![self.stopped_event("instruction breakpoint", None)]
+            }
+            Ok(Some(stop @ Stop::Watchpoint { .. })) => {
+                vec![self.stopped_event("data breakpoint", Some(stop.to_string()))]
+            }
+            Ok(None) if reason.is_empty() => vec![],
+            Ok(None) => vec![self.stopped_event(reason, None)],
+            Err(fault) => vec![self.stopped_event("exception", Some(fault.to_string()))],
+        }
+    }
+
+    fn stopped_event(&mut self, reason: &str, description: Option<String>) -> Value {
+        let mut body = json!({
+            "reason": reason,
+            "threadId": THREAD_ID,
+            "allThreadsStopped": true,
+        });
+
+        if let Some(description) = description {
+            body["description"] = json!(description.clone());
+            body["text"] = json!(description);
+        }
+
+        self.event("stopped", body)
+    }
+
+    fn response(&mut self, request: &Value, result: Result<Value, String>) -> Value {
+        let mut response = json!({
+            "seq": self.next_seq(),
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+        });
+
+        match result {
+            Ok(body) => {
+                response["success"] = json!(true);
+
+                if !body.is_null() {
+                    response["body"] = body;
+                }
+            }
+            Err(message) => {
+                response["success"] = json!(false);
+                response["message"] = json!(message);
+            }
+        }
+
+        response
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> Value {
+        let mut message = json!({
+            "seq": self.next_seq(),
+            "type": "event",
+            "event": event,
+        });
+
+        if !body.is_null() {
+            message["body"] = body;
+        }
+
+        message
+    }
+
+    fn next_seq(&mut self) -> u64 {
+        self.seq += 1;
+        self.seq
+    }
+}
+
+fn scope(name: &str, reference: u32) -> Value {
+    json!({"name": name, "variablesReference": reference, "expensive": false})
+}
+
+fn registers(chip8: &Chip8) -> Vec<(String, String)> {
+    let mut registers: Vec<(String, String)> = chip8
+        .v()
+        .iter()
+        .enumerate()
+        .map(|(x, v)| (format!("V{:X}", x), format!("{:#04X}", v)))
+        .collect();
+
+    registers.push(("I".to_string(), format!("{:#05X}", chip8.i())));
+    registers.push(("PC".to_string(), format!("{:#05X}", chip8.pc())));
+    registers.push(("SP".to_string(), format!("{}", chip8.sp())));
+    registers
+}
+
+fn timers(chip8: &Chip8) -> Vec<(String, String)> {
+    vec![
+        ("DT".to_string(), format!("{:#04X}", chip8.delay_timer())),
+        ("ST".to_string(), format!("{:#04X}", chip8.sound_timer)),
+    ]
+}
+
+fn variables(target: &mut Target, reference: u32) -> Result<Value, String> {
+    let chip8 = &target.chip8;
+    let variables: Vec<(String, String)> = match reference {
+        REGISTERS_REFERENCE => registers(chip8),
+        TIMERS_REFERENCE => timers(chip8),
+        STACK_REFERENCE => chip8
+            .stack()
+            .iter()
+            .take(chip8.sp() as usize)
+            .enumerate()
+            .map(|(depth, address)| (format!("[{}]", depth), format!("{:#05X}", address)))
+            .collect(),
+        _ => return Err(format!("unknown variables reference {}", reference)),
+    };
+    let variables: Vec<Value> = variables
+        .into_iter()
+        .map(|(name, value)| json!({"name": name, "value": value, "variablesReference": 0}))
+        .collect();
+
+    Ok(json!({ "variables": variables }))
+}
+
+// The frames are the current instruction, then the calls that led to it,
+// innermost first.
+fn stack_trace(target: &mut Target) -> Result<Value, String> {
+    let chip8 = &target.chip8;
+    let depth = (chip8.sp() as usize).min(chip8.stack().len());
+    let addresses = std::iter::once(chip8.pc()).chain(chip8.stack()[..depth].iter().rev().copied());
+    let frames: Vec<Value> = addresses
+        .enumerate()
+        .map(|(id, address)| {
+            json!({
+                "id": id,
+                "name": format!("{:#05X}  {}", address, instruction_text(chip8, address as i64).0),
+                "line": 0,
+                "column": 0,
+                "instructionPointerReference": format!("{:#05X}", address),
+            })
+        })
+        .collect();
+
+    Ok(json!({"stackFrames": frames, "totalFrames": depth + 1}))
+}
+
+fn disassemble(target: &mut Target, arguments: &Value) -> Result<Value, String> {
+    let chip8 = &target.chip8;
+    let base = arguments["memoryReference"]
+        .as_str()
+        .and_then(parse_address)
+        .ok_or_else(|| "disassemble needs a memoryReference".to_string())?;
+    let count = arguments["instructionCount"].as_u64().unwrap_or_default();
+
+    if count > DISASSEMBLY_LIMIT {
+        return Err(format!(
+            "can't disassemble more than {} instructions",
+            DISASSEMBLY_LIMIT
+        ));
+    }
+
+    // instructions are counted as two bytes when working out where to
+    // start, since there's no telling where earlier instructions begin
+    let mut address = arguments["instructionOffset"]
+        .as_i64()
+        .unwrap_or_default()
+        .checked_mul(2)
+        .and_then(|offset| offset.checked_add(arguments["offset"].as_i64().unwrap_or_default()))
+        .and_then(|offset| offset.checked_add(base))
+        .ok_or_else(|| "disassembly offset out of range".to_string())?;
+    let mut instructions = vec![];
+
+    for _ in 0..count {
+        let (text, size) = instruction_text(chip8, address);
+        let mut instruction = json!({
+            "address": format!("{:#05X}", address),
+            "instruction": text,
+        });
+
+        if size == 0 {
+            instruction["presentationHint"] = json!("invalid");
+            address = address.saturating_add(2);
+        } else {
+            let bytes = &chip8.memory()[address as usize..address as usize + size];
+
+            instruction["instructionBytes"] = json!(bytes
+                .iter()
+                .map(|b| format!("{:02X}", b))
+                .collect::<Vec<_>>()
+                .join(" "));
+            address = address.saturating_add(size as i64);
+        }
+
+        instructions.push(instruction);
+    }
+
+    Ok(json!({ "instructions": instructions }))
+}
+
+// The mnemonic for the instruction at `address` and its size in bytes,
+// which is 0 when the address is outside of memory.
+fn instruction_text(chip8: &Chip8, address: i64) -> (String, usize) {
+    let memory = chip8.memory();
+    let word = |a: i64| -> Option<u16> {
+        let a = usize::try_from(a).ok()?;
+
+        Some((*memory.get(a)? as u16) << 8 | *memory.get(a + 1)? as u16)
+    };
+    let opcode = match word(address) {
+        Some(opcode) => opcode,
+        None => return (String::new(), 0),
+    };
+
+    match Instruction::decode(opcode) {
+        Ok(instruction) if !instruction.is_xo_chip() || chip8.is_xo_chip() => {
+            let operand = word(address + 2).unwrap_or_default();
+            let size = if word(address + 2).is_some() {
+                instruction.size() as usize
+            } else {
+                2
+            };
+
+            (mnemonic(instruction, operand, Syntax::Octo), size)
+        }
+        _ => (format!("0x{:02X} 0x{:02X}", opcode >> 8, opcode & 0xFF), 2),
+    }
+}
+
+// Runs a single instruction, or for `next` a whole subroutine call, or for
+// `stepOut` the rest of the current subroutine. Breakpoints are only checked
+// in the instructions after the first.
+fn step(target: &mut Target, kind: StepKind) -> Result<Option<Stop>, Chip8Fault> {
+    let chip8 = &mut target.chip8;
+    let debugger = &mut target.debugger;
+    let (start_pc, start_sp) = (chip8.pc(), chip8.sp());
+    let done: Box<dyn Fn(&Chip8) -> bool> = match kind {
+        StepKind::Over if matches!(chip8.fetch(), Ok(Instruction::Call { .. })) => {
+            Box::new(move |c: &Chip8| c.pc() == start_pc.wrapping_add(2) && c.sp() == start_sp)
+        }
+        StepKind::Out if start_sp > 0 => Box::new(move |c: &Chip8| c.sp() < start_sp),
+        _ => Box::new(|_: &Chip8| true),
+    };
+
+    for _ in 0..STEP_LIMIT {
+        if debugger.step(chip8)? == StepOutcome::Exited {
+            return Ok(Some(Stop::Exited));
+        }
+
+        if done(chip8) {
+            break;
+        }
+
+        if debugger.breakpoint_hit(chip8) {
+            return Ok(Some(Stop::Breakpoint(chip8.pc())));
+        }
+    }
+
+    Ok(None)
+}
+
+// Addresses are hex, with or without a 0x prefix, as editors send them.
+fn parse_address(text: &str) -> Option<i64> {
+    let digits = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .unwrap_or(text);
+
+    i64::from_str_radix(digits, 16).ok()
+}
+
+// Reads one message, or `None` at the end of the input.
+fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<Value>> {
+    let mut length = None;
+
+    loop {
+        let mut line = String::new();
+
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim_end();
+
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let length = length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "message has no Content-Length")
+    })?;
+
+    if length > MESSAGE_LIMIT {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "message is too long",
+        ));
+    }
+
+    let mut body = vec![0; length];
+
+    input.read_exact(&mut body)?;
+
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_message<W: Write>(output: &mut W, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+// Runs a session over a pair of streams, usually stdin and stdout, until
+// the client disconnects or the input ends. Input is read on its own
+// thread, so that requests such as `pause` get through while the program
+// runs.
+pub fn serve_dap<R, W>(input: R, mut output: W) -> io::Result<()>
+where
+    R: Read + Send + 'static,
+    W: Write,
+{
+    let (sender, receiver) = mpsc::channel();
+    let frame_duration = Duration::from_secs(1) / FRAMES_PER_SECOND;
+    let mut session = DapSession::new();
+
+    thread::spawn(move || {
+        let mut input = BufReader::new(input);
+
+        while let Ok(Some(message)) = read_message(&mut input) {
+            if sender.send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    while !session.is_finished() {
+        let request = if session.is_running() {
+            match receiver.try_recv() {
+                Ok(request) => Some(request),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => break,
+            }
+        } else {
+            match receiver.recv() {
+                Ok(request) => Some(request),
+                Err(_) => break,
+            }
+        };
+
+        // requests are answered before the next frame runs
+        if let Some(request) = request {
+            for message in session.handle(&request) {
+                write_message(&mut output, &message)?;
+            }
+
+            continue;
+        }
+
+        let start = Instant::now();
+
+        for message in session.run_frame() {
+            write_message(&mut output, &message)?;
+        }
+
+        if let Some(remaining) = frame_duration.checked_sub(start.elapsed()) {
+            thread::sleep(remaining);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use serde_json::{json, Value};
+
+    use super::{read_message, serve_dap, write_message, DapSession};
+
+    // a subroutine at 0x206 that sets v0, called in a loop
+    const PROGRAM: [u8; 10] = [0x22, 0x06, 0x71, 0x01, 0x12, 0x00, 0x60, 0x05, 0x00, 0xEE];
+
+    fn rom_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("chip8-dap-{}.ch8", name));
+
+        std::fs::write(&path, PROGRAM).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn request(seq: u64, command: &str, arguments: Value) -> Value {
+        json!({"seq": seq, "type": "request", "command": command, "arguments": arguments})
+    }
+
+    // Sends a request, checks it succeeded, and returns the response body
+    // and the names of any events.
+    fn send(session: &mut DapSession, command: &str, arguments: Value) -> (Value, Vec<String>) {
+        let messages = session.handle(&request(1, command, arguments));
+        let response = &messages[0];
+
+        assert_eq!(response["success"], json!(true), "{}", response);
+
+        let events = messages[1..]
+            .iter()
+            .map(|m| match m["event"].as_str().unwrap() {
+                "stopped" => format!("stopped: {}", m["body"]["reason"].as_str().unwrap()),
+                event => event.to_string(),
+            })
+            .collect();
+
+        (response["body"].clone(), events)
+    }
+
+    fn launch(name: &str) -> DapSession {
+        let mut session = DapSession::new();
+        let (_, events) = send(&mut session, "initialize", json!({"adapterID": "chip-8"}));
+
+        assert_eq!(events, vec!["initialized"]);
+
+        send(
+            &mut session,
+            "launch",
+            json!({"program": rom_path(name), "stopOnEntry": true}),
+        );
+
+        let (_, events) = send(&mut session, "configurationDone", json!({}));
+
+        assert_eq!(events, vec!["stopped: entry"]);
+        session
+    }
+
+    #[test]
+    fn test_breakpoints_and_variables() {
+        let mut session = launch("breakpoints");
+
+        let (body, _) = send(
+            &mut session,
+            "setInstructionBreakpoints",
+            json!({"breakpoints": [{"instructionReference": "0x206"}, {"instructionReference": "nowhere"}]}),
+        );
+
+        assert_eq!(body["breakpoints"][0]["verified"], json!(true));
+        assert_eq!(body["breakpoints"][1]["verified"], json!(false));
+
+        send(&mut session, "continue", json!({"threadId": 1}));
+        assert!(session.is_running());
+
+        let events = session.run_frame();
+
+        assert_eq!(events[0]["body"]["reason"], json!("instruction breakpoint"));
+        assert!(!session.is_running());
+
+        let (body, _) = send(&mut session, "stackTrace", json!({"threadId": 1}));
+        let frames = body["stackFrames"].as_array().unwrap();
+
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0]["instructionPointerReference"], json!("0x206"));
+        assert_eq!(frames[1]["instructionPointerReference"], json!("0x200"));
+
+        let (body, _) = send(&mut session, "scopes", json!({"frameId": 0}));
+        let stack = body["scopes"][2]["variablesReference"].clone();
+        let (body, _) = send(
+            &mut session,
+            "variables",
+            json!({"variablesReference": stack}),
+        );
+
+        assert_eq!(body["variables"][0]["value"], json!("0x200"));
+
+        let (body, _) = send(
+            &mut session,
+            "setVariable",
+            json!({"variablesReference": 1, "name": "V3", "value": "0x2A"}),
+        );
+
+        assert_eq!(body["value"], json!("0x2A"));
+
+        let (body, _) = send(&mut session, "variables", json!({"variablesReference": 1}));
+
+        assert_eq!(
+            body["variables"][3],
+            json!({"name": "V3", "value": "0x2A", "variablesReference": 0})
+        );
+    }
+
+    #[test]
+    fn test_stepping() {
+        let mut session = launch("stepping");
+
+        // stepping over the call runs the whole subroutine
+        let (_, events) = send(&mut session, "next", json!({"threadId": 1}));
+
+        assert_eq!(events, vec!["stopped: step"]);
+
+        let (body, _) = send(&mut session, "stackTrace", json!({"threadId": 1}));
+
+        assert_eq!(
+            body["stackFrames"][0]["instructionPointerReference"],
+            json!("0x202")
+        );
+
+        let (body, _) = send(&mut session, "variables", json!({"variablesReference": 1}));
+
+        assert_eq!(body["variables"][0]["value"], json!("0x05"));
+
+        // into it, and back out again
+        send(&mut session, "next", json!({"threadId": 1}));
+        send(&mut session, "next", json!({"threadId": 1}));
+        send(&mut session, "stepIn", json!({"threadId": 1}));
+        send(&mut session, "stepOut", json!({"threadId": 1}));
+
+        let (body, _) = send(&mut session, "stackTrace", json!({"threadId": 1}));
+
+        assert_eq!(
+            body["stackFrames"][0]["instructionPointerReference"],
+            json!("0x202")
+        );
+        assert_eq!(body["totalFrames"], json!(1));
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let mut session = launch("disassemble");
+
+        let (body, _) = send(
+            &mut session,
+            "disassemble",
+            json!({"memoryReference": "0x204", "instructionOffset": -2, "instructionCount": 4}),
+        );
+        let instructions = body["instructions"].as_array().unwrap();
+
+        assert_eq!(instructions[0]["address"], json!("0x200"));
+        assert_eq!(instructions[0]["instructionBytes"], json!("22 06"));
+        assert_eq!(instructions[3]["instruction"], json!("v0 := 0x05"));
+
+        let (body, _) = send(
+            &mut session,
+            "disassemble",
+            json!({"memoryReference": "0xFFE", "instructionCount": 2}),
+        );
+
+        assert_eq!(
+            body["instructions"][1]["presentationHint"],
+            json!("invalid")
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut session = DapSession::new();
+
+        for (command, arguments) in [
+            ("stackTrace", json!({"threadId": 1})),
+            ("launch", json!({"program": "/nonexistent/rom.ch8"})),
+            ("launch", json!({})),
+            ("readMemory", json!({})),
+        ]
+        .iter()
+        {
+            let messages = session.handle(&request(7, command, arguments.clone()));
+
+            assert_eq!(messages[0]["success"], json!(false));
+            assert_eq!(messages[0]["request_seq"], json!(7));
+            assert!(messages[0]["message"].is_string());
+        }
+    }
+
+    #[test]
+    fn test_overflowing_offsets() {
+        let mut session = launch("overflow");
+
+        for (command, arguments) in [
+            (
+                "setInstructionBreakpoints",
+                json!({"breakpoints": [{"instructionReference": "0x200", "offset": i64::MAX}]}),
+            ),
+            (
+                "disassemble",
+                json!({"memoryReference": "0x200", "offset": i64::MAX, "instructionCount": 1}),
+            ),
+            (
+                "disassemble",
+                json!({"memoryReference": "0x200", "instructionOffset": i64::MIN, "instructionCount": 1}),
+            ),
+            (
+                "disassemble",
+                json!({"memoryReference": "0x200", "instructionCount": u64::MAX}),
+            ),
+        ]
+        .iter()
+        {
+            let messages = session.handle(&request(3, command, arguments.clone()));
+
+            assert_eq!(messages[0]["success"], json!(false), "{}", messages[0]);
+        }
+    }
+
+    #[test]
+    fn test_oversized_message() {
+        let mut input = Cursor::new(b"Content-Length: 99999999999\r\n\r\n{}".to_vec());
+
+        assert!(read_message(&mut input).is_err());
+    }
+
+    #[test]
+    fn test_serve() {
+        let mut input = vec![];
+
+        for (seq, (command, arguments)) in [
+            ("initialize", json!({})),
+            (
+                "launch",
+                json!({"program": rom_path("serve"), "stopOnEntry": true}),
+            ),
+            ("configurationDone", json!({})),
+            ("threads", json!({})),
+            ("disconnect", json!({})),
+        ]
+        .iter()
+        .enumerate()
+        {
+            write_message(
+                &mut input,
+                &request(seq as u64 + 1, command, arguments.clone()),
+            )
+            .unwrap();
+        }
+
+        let mut output = vec![];
+
+        serve_dap(Cursor::new(input), &mut output).unwrap();
+
+        let mut output = Cursor::new(output);
+        let mut messages = vec![];
+
+        while let Some(message) = read_message(&mut output).unwrap() {
+            messages.push(message);
+        }
+
+        let kinds: Vec<String> = messages
+            .iter()
+            .map(|m| {
+                m["command"]
+                    .as_str()
+                    .or_else(|| m["event"].as_str())
+                    .unwrap()
+                    .to_string()
+            })
+            .collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                "initialize",
+                "initialized",
+                "launch",
+                "configurationDone",
+                "stopped",
+                "threads",
+                "disconnect",
+                "terminated"
+            ]
+        );
+        assert_eq!(messages[5]["body"]["threads"][0]["name"], json!("CHIP-8"));
+    }
+}
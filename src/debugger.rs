@@ -0,0 +1,956 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::condition::Condition;
+use crate::errors::CommandError;
+use crate::{Access, Chip8, Chip8Fault, Instruction, StepOutcome};
+
+// The most instructions a single step command runs, so a mistyped count
+// can't lock the emulator up; also how far DAP's `next` and `stepOut` go
+// before giving up, in case the subroutine never returns or is waiting on a
+// key.
+pub(crate) const STEP_LIMIT: u32 = 1_000_000;
+
+const COMMAND_HELP: &str = "commands: break ADDR [if COND], delete [ADDR], \
+                            watch|rwatch|awatch ADDR[-ADDR]|REG, unwatch [ADDR[-ADDR]|REG], \
+                            set REG VALUE, poke ADDR BYTE..., mem ADDR|i, step [N], frame, \
+                            continue";
+
+// Why `Debugger::run_frame` handed control back before the frame was over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    // Execution reached a breakpoint; the instruction there hasn't run yet.
+    Breakpoint(u16),
+    // The instruction at `pc` touched a watched location; it has already run.
+    Watchpoint {
+        pc: u16,
+        location: Location,
+        access: Access,
+    },
+    // 00FD asked the interpreter to exit.
+    Exited,
+}
+
+// A register that can be watched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    V(u8),
+    I,
+}
+
+// A single byte of memory or a register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+    Memory(u16),
+    Register(Register),
+}
+
+// What a watchpoint watches; memory ranges include both ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchTarget {
+    Memory { start: u16, end: u16 },
+    Register(Register),
+}
+
+// The accesses that trigger a watchpoint, named after gdb's `rwatch`,
+// `watch` and `awatch`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    Access,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub target: WatchTarget,
+    pub kind: WatchKind,
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stop::Breakpoint(address) => write!(f, "breakpoint at {:#05X}", address),
+            Stop::Watchpoint {
+                pc,
+                location,
+                access,
+            } => {
+                let verb = match access {
+                    Access::Read => "read",
+                    Access::Write => "written",
+                };
+
+                write!(f, "{} {} at {:#05X}", location, verb, pc)
+            }
+            Stop::Exited => write!(f, "program exited"),
+        }
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Register::V(x) => write!(f, "v{:x}", x),
+            Register::I => write!(f, "i"),
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Location::Memory(address) => write!(f, "{:#05X}", address),
+            Location::Register(register) => write!(f, "{}", register),
+        }
+    }
+}
+
+impl fmt::Display for WatchTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WatchTarget::Memory { start, end } if start == end => write!(f, "{:#05X}", start),
+            WatchTarget::Memory { start, end } => write!(f, "{:#05X}-{:#05X}", start, end),
+            WatchTarget::Register(register) => write!(f, "{}", register),
+        }
+    }
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let command = match self.kind {
+            WatchKind::Read => "rwatch",
+            WatchKind::Write => "watch",
+            WatchKind::Access => "awatch",
+        };
+
+        write!(f, "{} {}", command, self.target)
+    }
+}
+
+impl WatchKind {
+    fn triggered_by(self, access: Access) -> bool {
+        match self {
+            WatchKind::Read => access == Access::Read,
+            WatchKind::Write => access == Access::Write,
+            WatchKind::Access => true,
+        }
+    }
+}
+
+impl WatchTarget {
+    fn contains(&self, location: Location) -> bool {
+        match (*self, location) {
+            (WatchTarget::Memory { start, end }, Location::Memory(address)) => {
+                start <= address && address <= end
+            }
+            (WatchTarget::Register(watched), Location::Register(register)) => watched == register,
+            _ => false,
+        }
+    }
+}
+
+// Run control for an interactive debugger: pausing, stepping, breakpoints
+// (optionally with a condition) and watchpoints, plus a small command
+// language for inspecting and editing the machine. Frontends drive it a
+// frame at a time instead of calling `Chip8::run_frame` directly.
+pub struct Debugger {
+    instructions_per_frame: u32,
+    breakpoints: BTreeMap<u16, Option<Condition>>,
+    watchpoints: Vec<Watchpoint>,
+    paused: bool,
+    // set when execution carries on from a breakpoint, so that the
+    // instruction under it gets to run instead of stopping again
+    stepping_off: bool,
+    // address shown in the memory view; follows I when not set
+    memory_view: Option<u16>,
+}
+
+impl Debugger {
+    // Debugging sessions start paused, so breakpoints can be set before the
+    // program runs.
+    pub fn new(instructions_per_frame: u32) -> Debugger {
+        Debugger {
+            instructions_per_frame,
+            breakpoints: BTreeMap::new(),
+            watchpoints: vec![],
+            paused: true,
+            stepping_off: false,
+            memory_view: None,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.stepping_off = true;
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.keys().copied()
+    }
+
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.breakpoints.insert(address, None);
+    }
+
+    // Adds a breakpoint that only stops when `condition` holds, such as
+    // `v3 == 0x10 && i > 0x300`.
+    pub fn add_conditional_breakpoint(
+        &mut self,
+        address: u16,
+        condition: &str,
+    ) -> Result<(), CommandError> {
+        let condition = Condition::parse(condition)?;
+
+        self.breakpoints.insert(address, Some(condition));
+        Ok(())
+    }
+
+    // the condition on the breakpoint at `address`, if it has one
+    pub fn condition(&self, address: u16) -> Option<&str> {
+        self.breakpoints
+            .get(&address)?
+            .as_ref()
+            .map(Condition::text)
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.remove(&address).is_some()
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    // Returns whether there is a breakpoint at `address` afterwards.
+    pub fn toggle_breakpoint(&mut self, address: u16) -> bool {
+        if self.breakpoints.remove(&address).is_none() {
+            self.breakpoints.insert(address, None);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        if !self.watchpoints.contains(&watchpoint) {
+            self.watchpoints.push(watchpoint);
+        }
+    }
+
+    // Removes every kind of watchpoint on `target`, returning whether there
+    // were any.
+    pub fn remove_watchpoint(&mut self, target: WatchTarget) -> bool {
+        let before = self.watchpoints.len();
+
+        self.watchpoints.retain(|w| w.target != target);
+        self.watchpoints.len() != before
+    }
+
+    pub fn memory_view(&self, chip8: &Chip8) -> u16 {
+        self.memory_view.unwrap_or_else(|| chip8.i())
+    }
+
+    // Runs exactly one instruction, ignoring breakpoints. Timers don't tick.
+    pub fn step(&mut self, chip8: &mut Chip8) -> Result<StepOutcome, Chip8Fault> {
+        self.paused = true;
+        self.stepping_off = false;
+
+        chip8.execute_cycle()
+    }
+
+    // Runs a single frame and stays paused afterwards.
+    pub fn step_frame(&mut self, chip8: &mut Chip8) -> Result<Option<Stop>, Chip8Fault> {
+        self.paused = true;
+        self.stepping_off = true;
+
+        self.run_frame(chip8)
+    }
+
+    // Runs one frame like `Chip8::run_frame`, but stops in front of any
+    // instruction with a breakpoint on it whose condition holds, and after
+    // any instruction that touches a watched location, and pauses. The
+    // timers only tick when the frame isn't cut short by a stop.
+    pub fn run_frame(&mut self, chip8: &mut Chip8) -> Result<Option<Stop>, Chip8Fault> {
+        let watches_registers = self.start_watching(chip8);
+
+        for _ in 0..self.instructions_per_frame {
+            let stepping_off = std::mem::replace(&mut self.stepping_off, false);
+
+            match self.run_instruction(chip8, watches_registers, !stepping_off)? {
+                Ok(StepOutcome::Executed) => {}
+                Ok(StepOutcome::WaitingForKey) => break,
+                Ok(StepOutcome::Exited) => return Ok(Some(Stop::Exited)),
+                Err(stop) => return Ok(Some(stop)),
+            }
+        }
+
+        chip8.tick_timers();
+
+        Ok(None)
+    }
+
+    // Turns on the tracking the watchpoints need, returning whether any of
+    // them watch registers.
+    fn start_watching(&self, chip8: &mut Chip8) -> bool {
+        let watches_memory = self
+            .watchpoints
+            .iter()
+            .any(|w| matches!(w.target, WatchTarget::Memory { .. }));
+
+        chip8.set_access_tracking(watches_memory);
+
+        self.watchpoints
+            .iter()
+            .any(|w| matches!(w.target, WatchTarget::Register(_)))
+    }
+
+    // Runs the instruction at PC, unless `check_breakpoint` is set and
+    // there's a breakpoint on it, and pauses on a breakpoint or watchpoint.
+    fn run_instruction(
+        &mut self,
+        chip8: &mut Chip8,
+        watches_registers: bool,
+        check_breakpoint: bool,
+    ) -> Result<Result<StepOutcome, Stop>, Chip8Fault> {
+        let pc = chip8.pc();
+
+        if check_breakpoint && self.breakpoint_hit(chip8) {
+            self.paused = true;
+            return Ok(Err(Stop::Breakpoint(pc)));
+        }
+
+        // which registers an instruction uses can only be worked out
+        // before it runs
+        let registers = if watches_registers {
+            register_accesses(chip8, chip8.fetch()?)
+        } else {
+            vec![]
+        };
+
+        let outcome = chip8.execute_cycle()?;
+        let memory = chip8
+            .memory_accesses()
+            .iter()
+            .map(|a| (Location::Memory(a.address as u16), a.access));
+
+        if let Some((location, access)) = registers
+            .into_iter()
+            .map(|(r, access)| (Location::Register(r), access))
+            .chain(memory)
+            .find(|(location, access)| self.watching(*location, *access))
+        {
+            self.paused = true;
+            return Ok(Err(Stop::Watchpoint {
+                pc,
+                location,
+                access,
+            }));
+        }
+
+        Ok(Ok(outcome))
+    }
+
+    // whether there's a breakpoint at PC whose condition holds
+    pub fn breakpoint_hit(&self, chip8: &Chip8) -> bool {
+        match self.breakpoints.get(&chip8.pc()) {
+            Some(Some(condition)) => condition.holds(chip8),
+            Some(None) => true,
+            None => false,
+        }
+    }
+
+    fn watching(&self, location: Location, access: Access) -> bool {
+        self.watchpoints
+            .iter()
+            .any(|w| w.target.contains(location) && w.kind.triggered_by(access))
+    }
+
+    // Runs one command line and describes what it did.
+    pub fn command(&mut self, chip8: &mut Chip8, line: &str) -> Result<String, CommandError> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        let message = match words.as_slice() {
+            [] => String::new(),
+            ["break", address] | ["b", address] => {
+                let address = parse_address(address)?;
+
+                self.add_breakpoint(address);
+                format!("breakpoint set at {:#05X}", address)
+            }
+            ["break", address, "if", condition @ ..] | ["b", address, "if", condition @ ..] => {
+                let address = parse_address(address)?;
+                let condition = condition.join(" ");
+
+                self.add_conditional_breakpoint(address, &condition)?;
+                format!("breakpoint set at {:#05X} if {}", address, condition)
+            }
+            ["delete"] | ["d"] => {
+                self.clear_breakpoints();
+                "deleted all breakpoints".to_string()
+            }
+            ["delete", address] | ["d", address] => {
+                let address = parse_address(address)?;
+
+                if !self.remove_breakpoint(address) {
+                    return Err(invalid(format!("no breakpoint at {:#05X}", address)));
+                }
+
+                format!("deleted breakpoint at {:#05X}", address)
+            }
+            [command @ "watch", target]
+            | [command @ "rwatch", target]
+            | [command @ "awatch", target] => {
+                let kind = match *command {
+                    "rwatch" => WatchKind::Read,
+                    "watch" => WatchKind::Write,
+                    _ => WatchKind::Access,
+                };
+                let watchpoint = Watchpoint {
+                    target: parse_watch_target(target)?,
+                    kind,
+                };
+
+                self.add_watchpoint(watchpoint);
+                format!("{} set", watchpoint)
+            }
+            ["unwatch"] => {
+                self.watchpoints.clear();
+                "deleted all watchpoints".to_string()
+            }
+            ["unwatch", target] => {
+                let target = parse_watch_target(target)?;
+
+                if !self.remove_watchpoint(target) {
+                    return Err(invalid(format!("no watchpoint on {}", target)));
+                }
+
+                format!("deleted watchpoints on {}", target)
+            }
+            ["set", register, value] => {
+                let value = parse_number(value)?;
+
+                set_register(chip8, register, value)?;
+                format!("{} = {:#X}", register.to_lowercase(), value)
+            }
+            ["poke", address, bytes @ ..] if !bytes.is_empty() => {
+                let address = parse_address(address)? as usize;
+
+                if address + bytes.len() > chip8.memory().len() {
+                    return Err(invalid(format!("{:#05X} is out of memory", address)));
+                }
+
+                for (offset, byte) in bytes.iter().enumerate() {
+                    chip8.memory_mut()[address + offset] = parse_byte(byte)?;
+                }
+
+                format!("wrote {} bytes at {:#05X}", bytes.len(), address)
+            }
+            ["mem", "i"] => {
+                self.memory_view = None;
+                "memory view follows I".to_string()
+            }
+            ["mem", address] => {
+                let address = parse_address(address)?;
+
+                self.memory_view = Some(address);
+                format!("memory view at {:#05X}", address)
+            }
+            ["step"] | ["s"] => self.step_many(chip8, 1)?,
+            ["step", count] | ["s", count] => {
+                let count = parse_number(count)?;
+
+                self.step_many(chip8, count)?
+            }
+            ["frame"] | ["f"] => match self.step_frame(chip8)? {
+                Some(stop) => stop.to_string(),
+                None => "ran one frame".to_string(),
+            },
+            ["continue"] | ["c"] => {
+                self.resume();
+                "running".to_string()
+            }
+            ["help"] | ["h"] => COMMAND_HELP.to_string(),
+            _ => return Err(invalid(format!("unknown command '{}'", line.trim()))),
+        };
+
+        Ok(message)
+    }
+
+    // Runs up to `STEP_LIMIT` instructions, stopping early at a breakpoint
+    // or watchpoint. The first instruction runs even if it has a breakpoint.
+    fn step_many(&mut self, chip8: &mut Chip8, count: u32) -> Result<String, CommandError> {
+        let watches_registers = self.start_watching(chip8);
+
+        self.paused = true;
+        self.stepping_off = false;
+
+        for n in 0..count.min(STEP_LIMIT) {
+            match self.run_instruction(chip8, watches_registers, n > 0)? {
+                Ok(StepOutcome::Exited) => return Ok("program exited".to_string()),
+                Ok(_) => {}
+                Err(stop) => return Ok(stop.to_string()),
+            }
+        }
+
+        Ok(format!("stepped to {:#05X}", chip8.pc()))
+    }
+}
+
+fn invalid(message: String) -> CommandError {
+    CommandError::Invalid(message)
+}
+
+fn set_register(chip8: &mut Chip8, register: &str, value: u32) -> Result<(), CommandError> {
+    let name = register.to_lowercase();
+    let too_big = || invalid(format!("{:#X} doesn't fit in {}", value, name));
+    let byte = || u8::try_from(value).map_err(|_| too_big());
+    let address = || u16::try_from(value).map_err(|_| too_big());
+
+    match name.as_str() {
+        "i" => chip8.set_i(address()?),
+        "pc" => chip8.set_pc(address()?),
+        "dt" => chip8.set_delay_timer(byte()?),
+        "st" => chip8.sound_timer = byte()?,
+        _ => {
+            // v0-vf
+            let x = name
+                .strip_prefix('v')
+                .filter(|r| r.len() == 1)
+                .and_then(|r| usize::from_str_radix(r, 16).ok());
+
+            match x {
+                Some(x) => chip8.set_v(x, byte()?),
+                None => return Err(invalid(format!("unknown register '{}'", register))),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_register(text: &str) -> Option<Register> {
+    let name = text.to_lowercase();
+
+    if name == "i" {
+        return Some(Register::I);
+    }
+
+    name.strip_prefix('v')
+        .filter(|r| r.len() == 1)
+        .and_then(|r| u8::from_str_radix(r, 16).ok())
+        .map(Register::V)
+}
+
+// A register, an address or an inclusive START-END range of addresses.
+fn parse_watch_target(text: &str) -> Result<WatchTarget, CommandError> {
+    if let Some(register) = parse_register(text) {
+        return Ok(WatchTarget::Register(register));
+    }
+
+    let (start, end) = match text.split_once('-') {
+        Some((start, end)) => (parse_address(start)?, parse_address(end)?),
+        None => {
+            let address = parse_address(text)?;
+
+            (address, address)
+        }
+    };
+
+    if start > end {
+        return Err(invalid(format!("{} is an empty range", text)));
+    }
+
+    Ok(WatchTarget::Memory { start, end })
+}
+
+// The registers an instruction reads and writes, given the quirks in effect.
+// FX0A only writes VX once the key has been released, but it's counted
+// anyway: the debugger ignores instructions that are still waiting.
+fn register_accesses(chip8: &Chip8, instruction: Instruction) -> Vec<(Register, Access)> {
+    use Access::{Read, Write};
+    use Instruction::*;
+
+    let quirks = chip8.quirks();
+    let v = Register::V;
+    let range = |x: u8, y: u8| if x <= y { x..=y } else { y..=x };
+    let flag = (v(0xF), Write);
+
+    match instruction {
+        Sys { .. }
+        | ClearScreen
+        | Return
+        | ScrollDown { .. }
+        | ScrollRight
+        | ScrollLeft
+        | Exit
+        | LoRes
+        | HiRes
+        | Jump { .. }
+        | Call { .. }
+        | SelectPlanes { .. } => vec![],
+        SkipEqImm { x, .. }
+        | SkipNeImm { x, .. }
+        | SkipKeyDown { x }
+        | SkipKeyUp { x }
+        | SetDelay { x }
+        | SetSound { x }
+        | SetPitch { x } => vec![(v(x), Read)],
+        SkipEqReg { x, y } | SkipNeReg { x, y } => vec![(v(x), Read), (v(y), Read)],
+        SaveRange { x, y } => std::iter::once((Register::I, Read))
+            .chain(range(x, y).map(|r| (v(r), Read)))
+            .collect(),
+        LoadRange { x, y } => std::iter::once((Register::I, Read))
+            .chain(range(x, y).map(|r| (v(r), Write)))
+            .collect(),
+        SetImm { x, .. } | Random { x, .. } | GetDelay { x } | WaitKey { x } => {
+            vec![(v(x), Write)]
+        }
+        AddImm { x, .. } => vec![(v(x), Read), (v(x), Write)],
+        Move { x, y } => vec![(v(y), Read), (v(x), Write)],
+        Or { x, y } | And { x, y } | Xor { x, y } => {
+            let mut accesses = vec![(v(x), Read), (v(y), Read), (v(x), Write)];
+
+            if quirks.logic_resets_vf {
+                accesses.push(flag);
+            }
+
+            accesses
+        }
+        Add { x, y } | Sub { x, y } | SubReverse { x, y } => {
+            vec![(v(x), Read), (v(y), Read), (v(x), Write), flag]
+        }
+        ShiftRight { x, y } | ShiftLeft { x, y } => {
+            let source = if quirks.shift_uses_vy { y } else { x };
+
+            vec![(v(source), Read), (v(x), Write), flag]
+        }
+        SetI { .. } | SetLongI => vec![(Register::I, Write)],
+        JumpOffset { nnn } => {
+            let x = if quirks.jump_uses_vx {
+                (nnn >> 8) as u8
+            } else {
+                0
+            };
+
+            vec![(v(x), Read)]
+        }
+        Draw { x, y, .. } => vec![(v(x), Read), (v(y), Read), (Register::I, Read), flag],
+        LoadAudio => vec![(Register::I, Read)],
+        AddI { x } => vec![
+            (v(x), Read),
+            (Register::I, Read),
+            (Register::I, Write),
+            flag,
+        ],
+        Font { x } | BigFont { x } => vec![(v(x), Read), (Register::I, Write)],
+        Bcd { x } => vec![(v(x), Read), (Register::I, Read)],
+        Store { x } | Load { x } => {
+            let access = match instruction {
+                Store { .. } => Read,
+                _ => Write,
+            };
+            let mut accesses: Vec<_> = std::iter::once((Register::I, Read))
+                .chain((0..=x).map(|r| (v(r), access)))
+                .collect();
+
+            if quirks.load_store_increments_i {
+                accesses.push((Register::I, Write));
+            }
+
+            accesses
+        }
+        SaveFlags { x } => (0..=x).map(|r| (v(r), Read)).collect(),
+        LoadFlags { x } => (0..=x).map(|r| (v(r), Write)).collect(),
+    }
+}
+
+// Accepts 0x-prefixed hex, 0b-prefixed binary or plain decimal numbers.
+fn parse_number(text: &str) -> Result<u32, CommandError> {
+    let lower = text.to_lowercase();
+
+    let number = if let Some(hex) = lower.strip_prefix("0x") {
+        u32::from_str_radix(hex, 16)
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        u32::from_str_radix(binary, 2)
+    } else {
+        lower.parse::<u32>()
+    };
+
+    number.map_err(|_| invalid(format!("invalid number '{}'", text)))
+}
+
+fn parse_address(text: &str) -> Result<u16, CommandError> {
+    let number = parse_number(text)?;
+
+    if number > 0xFFFF {
+        return Err(invalid(format!("{:#X} is not an address", number)));
+    }
+
+    Ok(number as u16)
+}
+
+fn parse_byte(text: &str) -> Result<u8, CommandError> {
+    let number = parse_number(text)?;
+
+    if number > 0xFF {
+        return Err(invalid(format!("{:#X} doesn't fit in a byte", number)));
+    }
+
+    Ok(number as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Debugger, Location, Register, Stop, WatchKind, WatchTarget, Watchpoint};
+    use crate::{Access, Chip8, CommandError, Quirks};
+
+    // v0 += 1 in a loop, with a CLS at 0x204 to break on
+    const PROGRAM: [u8; 8] = [0x70, 0x01, 0x12, 0x04, 0x00, 0xE0, 0x12, 0x00];
+
+    fn create_and_load() -> Chip8 {
+        let mut chip8 = Chip8::new(Quirks::default());
+
+        chip8.load_program(PROGRAM.to_vec()).unwrap();
+        chip8
+    }
+
+    #[test]
+    fn test_breakpoint_stops_before_instruction() {
+        let mut chip8 = create_and_load();
+        let mut debugger = Debugger::new(10);
+
+        debugger.add_breakpoint(0x204);
+        debugger.resume();
+
+        assert_eq!(
+            debugger.run_frame(&mut chip8).unwrap(),
+            Some(Stop::Breakpoint(0x204))
+        );
+        assert!(debugger.is_paused());
+        assert_eq!(chip8.pc(), 0x204);
+
+        // carrying on runs the instruction under the breakpoint
+        debugger.resume();
+
+        assert_eq!(
+            debugger.run_frame(&mut chip8).unwrap(),
+            Some(Stop::Breakpoint(0x204))
+        );
+        assert_eq!(chip8.v()[0], 2);
+    }
+
+    #[test]
+    fn test_conditional_breakpoint() {
+        let mut chip8 = create_and_load();
+        let mut debugger = Debugger::new(10);
+
+        debugger
+            .command(&mut chip8, "break 0x204 if v0 == 3 && i == 0")
+            .unwrap();
+        assert_eq!(debugger.condition(0x204), Some("v0 == 3 && i == 0"));
+
+        debugger.resume();
+
+        // v0 goes up by one per pass through the loop, three instructions a pass
+        assert_eq!(debugger.run_frame(&mut chip8).unwrap(), None);
+        assert_eq!(
+            debugger.run_frame(&mut chip8).unwrap(),
+            Some(Stop::Breakpoint(0x204))
+        );
+        assert_eq!(chip8.v()[0], 3);
+
+        assert!(debugger
+            .command(&mut chip8, "break 0x204 if v0 ==")
+            .is_err());
+    }
+
+    #[test]
+    fn test_memory_watchpoints() {
+        // I = 0x300; BCD of V0 to 0x300-0x302; load V0-V1 from 0x300-0x301
+        let program = vec![0xA3, 0x00, 0xF0, 0x33, 0xF1, 0x65, 0x12, 0x00];
+        let mut chip8 = Chip8::new(Quirks::default());
+        let mut debugger = Debugger::new(10);
+
+        chip8.load_program(program).unwrap();
+        chip8.set_v(0, 123);
+        debugger.command(&mut chip8, "watch 0x302").unwrap();
+        debugger.command(&mut chip8, "rwatch 0x301-0x310").unwrap();
+        debugger.resume();
+
+        assert_eq!(
+            debugger.run_frame(&mut chip8).unwrap(),
+            Some(Stop::Watchpoint {
+                pc: 0x202,
+                location: Location::Memory(0x302),
+                access: Access::Write,
+            })
+        );
+        assert_eq!(chip8.pc(), 0x204);
+
+        debugger.resume();
+
+        assert_eq!(
+            debugger.run_frame(&mut chip8).unwrap(),
+            Some(Stop::Watchpoint {
+                pc: 0x204,
+                location: Location::Memory(0x301),
+                access: Access::Read,
+            })
+        );
+
+        debugger.command(&mut chip8, "unwatch 0x301-0x310").unwrap();
+        assert_eq!(
+            debugger.watchpoints(),
+            &[Watchpoint {
+                target: WatchTarget::Memory {
+                    start: 0x302,
+                    end: 0x302
+                },
+                kind: WatchKind::Write,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_register_watchpoints() {
+        let mut chip8 = create_and_load();
+        let mut debugger = Debugger::new(10);
+
+        // the loop reads and writes v0, and never touches v1
+        debugger.command(&mut chip8, "awatch v1").unwrap();
+        debugger.resume();
+        assert_eq!(debugger.run_frame(&mut chip8).unwrap(), None);
+
+        debugger.command(&mut chip8, "rwatch V0").unwrap();
+        assert_eq!(
+            debugger.run_frame(&mut chip8).unwrap(),
+            Some(Stop::Watchpoint {
+                pc: 0x200,
+                location: Location::Register(Register::V(0)),
+                access: Access::Read,
+            })
+        );
+
+        assert_eq!(
+            debugger.watchpoints()[1].to_string(),
+            "rwatch v0".to_string()
+        );
+    }
+
+    #[test]
+    fn test_step() {
+        let mut chip8 = create_and_load();
+        let mut debugger = Debugger::new(10);
+
+        debugger.add_breakpoint(0x200);
+        debugger.step(&mut chip8).unwrap();
+
+        assert_eq!(chip8.pc(), 0x202);
+        assert_eq!(chip8.v()[0], 1);
+        assert!(debugger.is_paused());
+    }
+
+    #[test]
+    fn test_commands() {
+        let mut chip8 = create_and_load();
+        let mut debugger = Debugger::new(10);
+
+        debugger.command(&mut chip8, "set v3 0x10").unwrap();
+        debugger.command(&mut chip8, "set I 0x300").unwrap();
+        debugger.command(&mut chip8, "set dt 5").unwrap();
+        debugger.command(&mut chip8, "poke 0x300 1 2 0xFF").unwrap();
+        debugger.command(&mut chip8, "b 0x202").unwrap();
+
+        // stepping stops at the breakpoint, but can step off it
+        assert_eq!(
+            debugger.command(&mut chip8, "step 2").unwrap(),
+            "breakpoint at 0x202"
+        );
+        debugger.command(&mut chip8, "step").unwrap();
+
+        assert_eq!(chip8.v()[3], 0x10);
+        assert_eq!(chip8.i(), 0x300);
+        assert_eq!(chip8.delay_timer(), 5);
+        assert_eq!(&chip8.memory()[0x300..0x303], &[1, 2, 0xFF]);
+        assert_eq!(debugger.breakpoints().collect::<Vec<_>>(), vec![0x202]);
+        assert_eq!(chip8.pc(), 0x204);
+
+        debugger.command(&mut chip8, "mem 0x210").unwrap();
+        assert_eq!(debugger.memory_view(&chip8), 0x210);
+        debugger.command(&mut chip8, "mem i").unwrap();
+        assert_eq!(debugger.memory_view(&chip8), 0x300);
+    }
+
+    #[test]
+    fn test_step_many_stops_early() {
+        let mut chip8 = create_and_load();
+        let mut debugger = Debugger::new(10);
+
+        debugger.command(&mut chip8, "break 0x204").unwrap();
+
+        assert_eq!(
+            debugger.command(&mut chip8, "step 4000000000").unwrap(),
+            "breakpoint at 0x204"
+        );
+        assert_eq!(chip8.pc(), 0x204);
+
+        debugger.command(&mut chip8, "delete").unwrap();
+        debugger.command(&mut chip8, "watch v0").unwrap();
+
+        assert_eq!(
+            debugger.command(&mut chip8, "step 100").unwrap(),
+            "v0 written at 0x200"
+        );
+    }
+
+    #[test]
+    fn test_bad_commands() {
+        let mut chip8 = create_and_load();
+        let mut debugger = Debugger::new(10);
+
+        for line in [
+            "set vg 1",
+            "set v0 0x100",
+            "poke 0xFFF 1 2",
+            "break nowhere",
+            "delete 0x200",
+            "watch 0x300-0x200",
+            "unwatch v0",
+            "jump 0x200",
+        ]
+        .iter()
+        {
+            match debugger.command(&mut chip8, line) {
+                Err(CommandError::Invalid(_)) => {}
+                other => panic!("{}: {:?}", line, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_fault_while_stepping() {
+        let mut chip8 = Chip8::new(Quirks::default());
+        let mut debugger = Debugger::new(10);
+
+        chip8.load_program(vec![0x00, 0xEE]).unwrap();
+
+        match debugger.command(&mut chip8, "step") {
+            Err(CommandError::Fault(fault)) => assert_eq!(fault.pc(), 0x200),
+            other => panic!("{:?}", other),
+        }
+    }
+}
@@ -0,0 +1,604 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::{Instruction, LOWER_MEMORY_BOUNDARY};
+
+const SPRITE_BYTES_16X16: usize = 32;
+const MNEMONIC_WIDTH: usize = 28;
+
+// Octo syntax listings can be fed straight back into an Octo-compatible
+// assembler; Cowgod syntax follows the mnemonics of Cowgod's CHIP-8
+// technical reference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    Octo,
+    Cowgod,
+}
+
+impl Syntax {
+    pub const NAMES: [&'static str; 2] = ["octo", "cowgod"];
+
+    pub fn from_name(name: &str) -> Option<Syntax> {
+        match name.to_lowercase().as_str() {
+            "octo" => Some(Syntax::Octo),
+            "cowgod" => Some(Syntax::Cowgod),
+            _ => None,
+        }
+    }
+
+    fn comment(self) -> &'static str {
+        match self {
+            Syntax::Octo => "#",
+            Syntax::Cowgod => ";",
+        }
+    }
+}
+
+// Ordered by priority: when an address is reached in several ways, the
+// label describes the first kind in this list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum LabelKind {
+    Main,
+    Subroutine,
+    Jump,
+    Sprite,
+    Data,
+}
+
+enum Line {
+    Code(u16, Instruction),
+    Sprite(u16),
+    Data(u16, usize),
+}
+
+// What a recursive traversal from the entry point found out about a ROM.
+struct Analysis {
+    code: BTreeMap<u16, Instruction>,
+    labels: BTreeMap<u16, LabelKind>,
+    sprites: BTreeSet<u16>,
+}
+
+struct Rom<'a> {
+    program: &'a [u8],
+    xo_chip: bool,
+}
+
+impl<'a> Rom<'a> {
+    fn start(&self) -> usize {
+        LOWER_MEMORY_BOUNDARY
+    }
+
+    fn end(&self) -> usize {
+        LOWER_MEMORY_BOUNDARY + self.program.len()
+    }
+
+    fn byte(&self, address: usize) -> Option<u8> {
+        address
+            .checked_sub(LOWER_MEMORY_BOUNDARY)
+            .and_then(|offset| self.program.get(offset))
+            .copied()
+    }
+
+    fn word(&self, address: usize) -> Option<u16> {
+        Some((self.byte(address)? as u16) << 8 | self.byte(address + 1)? as u16)
+    }
+
+    // Decodes the instruction at `address`, if there is a whole one there
+    // that this interpreter would run.
+    fn decode(&self, address: usize) -> Option<Instruction> {
+        let instruction = Instruction::decode(self.word(address)?).ok()?;
+
+        if instruction.is_xo_chip() && !self.xo_chip {
+            return None;
+        }
+
+        if instruction == Instruction::SetLongI {
+            self.word(address + 2)?;
+        }
+
+        Some(instruction)
+    }
+
+    // Size of whatever instruction lives at `address`, for working out where
+    // a skip lands.
+    fn size(&self, address: usize) -> usize {
+        match self.decode(address) {
+            Some(instruction) => instruction.size() as usize,
+            None => 2,
+        }
+    }
+
+    // Follows every path through the program from the entry point. Flow stops
+    // at returns, at anything that doesn't decode, and at computed jumps
+    // (apart from their base address). The value of I is tracked along the
+    // way so that the memory DXYN draws from can be marked as sprite data.
+    fn analyse(&self) -> Analysis {
+        let mut code = BTreeMap::new();
+        let mut labels = BTreeMap::new();
+        let mut sprites = BTreeSet::new();
+        let mut work: Vec<(usize, Option<u16>)> = vec![(self.start(), None)];
+
+        label(&mut labels, self.start() as u16, LabelKind::Main);
+
+        while let Some((mut address, mut i)) = work.pop() {
+            while !code.contains_key(&(address as u16)) {
+                let instruction = match self.decode(address) {
+                    Some(instruction) => instruction,
+                    None => break,
+                };
+                let next = address + instruction.size() as usize;
+
+                code.insert(address as u16, instruction);
+
+                match instruction {
+                    Instruction::Jump { nnn } | Instruction::Sys { nnn } => {
+                        label(&mut labels, nnn, LabelKind::Jump);
+                        work.push((nnn as usize, i));
+                        break;
+                    }
+                    Instruction::JumpOffset { nnn } => {
+                        // only the base of a jump table can be known
+                        label(&mut labels, nnn, LabelKind::Jump);
+                        work.push((nnn as usize, i));
+                        break;
+                    }
+                    Instruction::Call { nnn } => {
+                        label(&mut labels, nnn, LabelKind::Subroutine);
+                        work.push((nnn as usize, None));
+
+                        // the subroutine may well have moved I
+                        i = None;
+                    }
+                    Instruction::Return | Instruction::Exit => break,
+                    Instruction::SkipEqImm { .. }
+                    | Instruction::SkipNeImm { .. }
+                    | Instruction::SkipEqReg { .. }
+                    | Instruction::SkipNeReg { .. }
+                    | Instruction::SkipKeyDown { .. }
+                    | Instruction::SkipKeyUp { .. } => {
+                        work.push((next + self.size(next), i));
+                    }
+                    Instruction::SetI { nnn } => {
+                        label(&mut labels, nnn, LabelKind::Data);
+                        i = Some(nnn);
+                    }
+                    Instruction::SetLongI => {
+                        let target = self.word(address + 2).unwrap_or_default();
+
+                        label(&mut labels, target, LabelKind::Data);
+                        i = Some(target);
+                    }
+                    Instruction::Draw { n, .. } => {
+                        if let Some(target) = i {
+                            let length = if n == 0 {
+                                SPRITE_BYTES_16X16
+                            } else {
+                                n as usize
+                            };
+
+                            label(&mut labels, target, LabelKind::Sprite);
+                            sprites.extend((0..length).map(|b| target.wrapping_add(b as u16)));
+                        }
+                    }
+                    Instruction::AddI { .. }
+                    | Instruction::Font { .. }
+                    | Instruction::BigFont { .. }
+                    | Instruction::Store { .. }
+                    | Instruction::Load { .. } => i = None,
+                    _ => {}
+                }
+
+                address = next;
+            }
+        }
+
+        Analysis {
+            code,
+            labels,
+            sprites,
+        }
+    }
+
+    // Splits the ROM into listing lines. Data runs are at most a word long
+    // and stop wherever code or a label starts.
+    fn lines(&self, analysis: &Analysis) -> Vec<Line> {
+        let mut lines = vec![];
+        let mut address = self.start();
+        let end = self.end();
+
+        while address < end {
+            let a = address as u16;
+
+            if let Some(instruction) = analysis.code.get(&a) {
+                lines.push(Line::Code(a, *instruction));
+                address += instruction.size() as usize;
+            } else if analysis.sprites.contains(&a) {
+                lines.push(Line::Sprite(a));
+                address += 1;
+            } else {
+                let mut length = 1;
+
+                while length < 2 && address + length < end {
+                    let b = (address + length) as u16;
+
+                    if analysis.code.contains_key(&b)
+                        || analysis.sprites.contains(&b)
+                        || analysis.labels.contains_key(&b)
+                    {
+                        break;
+                    }
+
+                    length += 1;
+                }
+
+                lines.push(Line::Data(a, length));
+                address += length;
+            }
+        }
+
+        lines
+    }
+
+    fn raw(&self, address: u16, length: usize) -> String {
+        (0..length)
+            .map(|b| {
+                format!(
+                    "{:02X}",
+                    self.byte(address as usize + b).unwrap_or_default()
+                )
+            })
+            .collect()
+    }
+}
+
+fn label(labels: &mut BTreeMap<u16, LabelKind>, address: u16, kind: LabelKind) {
+    let entry = labels.entry(address).or_insert(kind);
+    *entry = (*entry).min(kind);
+}
+
+fn label_name(address: u16, kind: LabelKind) -> String {
+    let prefix = match kind {
+        LabelKind::Main => return "main".to_string(),
+        LabelKind::Subroutine => "sub",
+        LabelKind::Jump => "label",
+        LabelKind::Sprite => "sprite",
+        LabelKind::Data => "data",
+    };
+
+    format!("{}_{:03X}", prefix, address)
+}
+
+fn pixels(byte: u8) -> String {
+    (0..8)
+        .map(|b| if byte & (0x80 >> b) != 0 { '#' } else { '.' })
+        .collect()
+}
+
+// Disassembles a ROM loaded at 0x200 into an annotated listing. Every line
+// shows the address and raw bytes alongside the mnemonic; subroutines, jump
+// targets and the memory that I points at get labels, and bytes drawn by
+// DXYN are shown as sprite rows.
+pub fn disassemble(program: &[u8], xo_chip: bool, syntax: Syntax) -> String {
+    let rom = Rom { program, xo_chip };
+    let analysis = rom.analyse();
+    let lines = rom.lines(&analysis);
+
+    // a label can only be written out where a line starts
+    let names: BTreeMap<u16, String> = lines
+        .iter()
+        .filter_map(|line| {
+            let address = match *line {
+                Line::Code(a, _) | Line::Sprite(a) | Line::Data(a, _) => a,
+            };
+
+            analysis
+                .labels
+                .get(&address)
+                .map(|kind| (address, label_name(address, *kind)))
+        })
+        .collect();
+    let target = |address: u16| match names.get(&address) {
+        Some(name) => name.clone(),
+        None => format!("{:#05X}", address),
+    };
+
+    let comment = syntax.comment();
+    let mut listing = String::new();
+
+    for line in lines.iter() {
+        let (address, text, raw, note) = match *line {
+            Line::Code(a, instruction) => {
+                let operand = rom.word(a as usize + 2).unwrap_or_default();
+                let text = format_instruction(instruction, operand, syntax, &target);
+
+                (
+                    a,
+                    text,
+                    rom.raw(a, instruction.size() as usize),
+                    String::new(),
+                )
+            }
+            Line::Sprite(a) => {
+                let byte = rom.byte(a as usize).unwrap_or_default();
+                let text = match syntax {
+                    Syntax::Octo => format!("{:#04X}", byte),
+                    Syntax::Cowgod => format!("DB {:#04X}", byte),
+                };
+
+                (a, text, rom.raw(a, 1), pixels(byte))
+            }
+            Line::Data(a, length) => {
+                let bytes: Vec<String> = (0..length)
+                    .map(|b| format!("{:#04X}", rom.byte(a as usize + b).unwrap_or_default()))
+                    .collect();
+                let text = match syntax {
+                    Syntax::Octo => bytes.join(" "),
+                    Syntax::Cowgod => format!("DB {}", bytes.join(", ")),
+                };
+
+                (a, text, rom.raw(a, length), String::new())
+            }
+        };
+
+        if let Some(name) = names.get(&address) {
+            match syntax {
+                Syntax::Octo => writeln!(listing, ": {}", name).unwrap(),
+                Syntax::Cowgod => writeln!(listing, "{}:", name).unwrap(),
+            }
+        }
+
+        // the address and raw bytes go in a trailing comment in Octo syntax,
+        // so that the listing still assembles
+        let text = match (syntax, note.is_empty()) {
+            (Syntax::Octo, _) => format!(
+                "    {:<width$} {} {:#05X}  {:<8}  {}",
+                text,
+                comment,
+                address,
+                raw,
+                note,
+                width = MNEMONIC_WIDTH
+            ),
+            (Syntax::Cowgod, true) => format!("    {:#05X}  {:<8}  {}", address, raw, text),
+            (Syntax::Cowgod, false) => format!(
+                "    {:#05X}  {:<8}  {:<width$} {} {}",
+                address,
+                raw,
+                text,
+                comment,
+                note,
+                width = MNEMONIC_WIDTH
+            ),
+        };
+
+        writeln!(listing, "{}", text.trim_end()).unwrap();
+    }
+
+    listing
+}
+
+// Formats a single instruction with plain numeric addresses. `operand` is the
+// word following the instruction, which only F000 NNNN uses.
+pub fn mnemonic(instruction: Instruction, operand: u16, syntax: Syntax) -> String {
+    format_instruction(instruction, operand, syntax, &|address| {
+        format!("{:#05X}", address)
+    })
+}
+
+fn format_instruction(
+    instruction: Instruction,
+    operand: u16,
+    syntax: Syntax,
+    target: &dyn Fn(u16) -> String,
+) -> String {
+    match syntax {
+        Syntax::Octo => octo(instruction, operand, target),
+        Syntax::Cowgod => cowgod(instruction, operand, target),
+    }
+}
+
+fn octo(instruction: Instruction, operand: u16, target: &dyn Fn(u16) -> String) -> String {
+    match instruction {
+        // Octo has no mnemonic for machine code calls, so emit the raw bytes
+        Instruction::Sys { nnn } => format!("{:#04X} {:#04X}", nnn >> 8, nnn & 0xFF),
+        Instruction::ClearScreen => "clear".to_string(),
+        Instruction::Return => "return".to_string(),
+        Instruction::ScrollDown { n } => format!("scroll-down {}", n),
+        Instruction::ScrollRight => "scroll-right".to_string(),
+        Instruction::ScrollLeft => "scroll-left".to_string(),
+        Instruction::Exit => "exit".to_string(),
+        Instruction::LoRes => "lores".to_string(),
+        Instruction::HiRes => "hires".to_string(),
+        Instruction::Jump { nnn } => format!("jump {}", target(nnn)),
+        Instruction::Call { nnn } => format!(":call {}", target(nnn)),
+        // Octo's conditionals say when the next instruction runs, which is
+        // the opposite of when it's skipped
+        Instruction::SkipEqImm { x, nn } => format!("if v{:x} != {:#04X} then", x, nn),
+        Instruction::SkipNeImm { x, nn } => format!("if v{:x} == {:#04X} then", x, nn),
+        Instruction::SkipEqReg { x, y } => format!("if v{:x} != v{:x} then", x, y),
+        Instruction::SaveRange { x, y } => format!("save v{:x} - v{:x}", x, y),
+        Instruction::LoadRange { x, y } => format!("load v{:x} - v{:x}", x, y),
+        Instruction::SetImm { x, nn } => format!("v{:x} := {:#04X}", x, nn),
+        Instruction::AddImm { x, nn } => format!("v{:x} += {:#04X}", x, nn),
+        Instruction::Move { x, y } => format!("v{:x} := v{:x}", x, y),
+        Instruction::Or { x, y } => format!("v{:x} |= v{:x}", x, y),
+        Instruction::And { x, y } => format!("v{:x} &= v{:x}", x, y),
+        Instruction::Xor { x, y } => format!("v{:x} ^= v{:x}", x, y),
+        Instruction::Add { x, y } => format!("v{:x} += v{:x}", x, y),
+        Instruction::Sub { x, y } => format!("v{:x} -= v{:x}", x, y),
+        Instruction::ShiftRight { x, y } => format!("v{:x} >>= v{:x}", x, y),
+        Instruction::SubReverse { x, y } => format!("v{:x} =- v{:x}", x, y),
+        Instruction::ShiftLeft { x, y } => format!("v{:x} <<= v{:x}", x, y),
+        Instruction::SkipNeReg { x, y } => format!("if v{:x} == v{:x} then", x, y),
+        Instruction::SetI { nnn } => format!("i := {}", target(nnn)),
+        Instruction::JumpOffset { nnn } => format!("jump0 {}", target(nnn)),
+        Instruction::Random { x, nn } => format!("v{:x} := random {:#04X}", x, nn),
+        Instruction::Draw { x, y, n } => format!("sprite v{:x} v{:x} {}", x, y, n),
+        Instruction::SkipKeyDown { x } => format!("if v{:x} -key then", x),
+        Instruction::SkipKeyUp { x } => format!("if v{:x} key then", x),
+        Instruction::SetLongI => format!("i := long {}", target(operand)),
+        Instruction::SelectPlanes { n } => format!("plane {}", n),
+        Instruction::LoadAudio => "audio".to_string(),
+        Instruction::GetDelay { x } => format!("v{:x} := delay", x),
+        Instruction::WaitKey { x } => format!("v{:x} := key", x),
+        Instruction::SetDelay { x } => format!("delay := v{:x}", x),
+        Instruction::SetSound { x } => format!("buzzer := v{:x}", x),
+        Instruction::AddI { x } => format!("i += v{:x}", x),
+        Instruction::Font { x } => format!("i := hex v{:x}", x),
+        Instruction::BigFont { x } => format!("i := bighex v{:x}", x),
+        Instruction::Bcd { x } => format!("bcd v{:x}", x),
+        Instruction::SetPitch { x } => format!("pitch := v{:x}", x),
+        Instruction::Store { x } => format!("save v{:x}", x),
+        Instruction::Load { x } => format!("load v{:x}", x),
+        Instruction::SaveFlags { x } => format!("saveflags v{:x}", x),
+        Instruction::LoadFlags { x } => format!("loadflags v{:x}", x),
+    }
+}
+
+fn cowgod(instruction: Instruction, operand: u16, target: &dyn Fn(u16) -> String) -> String {
+    match instruction {
+        Instruction::Sys { nnn } => format!("SYS {}", target(nnn)),
+        Instruction::ClearScreen => "CLS".to_string(),
+        Instruction::Return => "RET".to_string(),
+        Instruction::ScrollDown { n } => format!("SCD {}", n),
+        Instruction::ScrollRight => "SCR".to_string(),
+        Instruction::ScrollLeft => "SCL".to_string(),
+        Instruction::Exit => "EXIT".to_string(),
+        Instruction::LoRes => "LOW".to_string(),
+        Instruction::HiRes => "HIGH".to_string(),
+        Instruction::Jump { nnn } => format!("JP {}", target(nnn)),
+        Instruction::Call { nnn } => format!("CALL {}", target(nnn)),
+        Instruction::SkipEqImm { x, nn } => format!("SE V{:X}, {:#04X}", x, nn),
+        Instruction::SkipNeImm { x, nn } => format!("SNE V{:X}, {:#04X}", x, nn),
+        Instruction::SkipEqReg { x, y } => format!("SE V{:X}, V{:X}", x, y),
+        Instruction::SaveRange { x, y } => format!("SAVE V{:X} - V{:X}", x, y),
+        Instruction::LoadRange { x, y } => format!("LOAD V{:X} - V{:X}", x, y),
+        Instruction::SetImm { x, nn } => format!("LD V{:X}, {:#04X}", x, nn),
+        Instruction::AddImm { x, nn } => format!("ADD V{:X}, {:#04X}", x, nn),
+        Instruction::Move { x, y } => format!("LD V{:X}, V{:X}", x, y),
+        Instruction::Or { x, y } => format!("OR V{:X}, V{:X}", x, y),
+        Instruction::And { x, y } => format!("AND V{:X}, V{:X}", x, y),
+        Instruction::Xor { x, y } => format!("XOR V{:X}, V{:X}", x, y),
+        Instruction::Add { x, y } => format!("ADD V{:X}, V{:X}", x, y),
+        Instruction::Sub { x, y } => format!("SUB V{:X}, V{:X}", x, y),
+        Instruction::ShiftRight { x, y } => format!("SHR V{:X}, V{:X}", x, y),
+        Instruction::SubReverse { x, y } => format!("SUBN V{:X}, V{:X}", x, y),
+        Instruction::ShiftLeft { x, y } => format!("SHL V{:X}, V{:X}", x, y),
+        Instruction::SkipNeReg { x, y } => format!("SNE V{:X}, V{:X}", x, y),
+        Instruction::SetI { nnn } => format!("LD I, {}", target(nnn)),
+        Instruction::JumpOffset { nnn } => format!("JP V0, {}", target(nnn)),
+        Instruction::Random { x, nn } => format!("RND V{:X}, {:#04X}", x, nn),
+        Instruction::Draw { x, y, n } => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        Instruction::SkipKeyDown { x } => format!("SKP V{:X}", x),
+        Instruction::SkipKeyUp { x } => format!("SKNP V{:X}", x),
+        Instruction::SetLongI => format!("LD I, LONG {}", target(operand)),
+        Instruction::SelectPlanes { n } => format!("PLANE {}", n),
+        Instruction::LoadAudio => "AUDIO".to_string(),
+        Instruction::GetDelay { x } => format!("LD V{:X}, DT", x),
+        Instruction::WaitKey { x } => format!("LD V{:X}, K", x),
+        Instruction::SetDelay { x } => format!("LD DT, V{:X}", x),
+        Instruction::SetSound { x } => format!("LD ST, V{:X}", x),
+        Instruction::AddI { x } => format!("ADD I, V{:X}", x),
+        Instruction::Font { x } => format!("LD F, V{:X}", x),
+        Instruction::BigFont { x } => format!("LD HF, V{:X}", x),
+        Instruction::Bcd { x } => format!("LD B, V{:X}", x),
+        Instruction::SetPitch { x } => format!("PITCH V{:X}", x),
+        Instruction::Store { x } => format!("LD [I], V{:X}", x),
+        Instruction::Load { x } => format!("LD V{:X}, [I]", x),
+        Instruction::SaveFlags { x } => format!("LD R, V{:X}", x),
+        Instruction::LoadFlags { x } => format!("LD V{:X}, R", x),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{disassemble, mnemonic, Syntax};
+    use crate::Instruction;
+
+    // calls a subroutine that draws an 8x2 sprite, then loops forever
+    const PROGRAM: [u8; 12] = [
+        0x22, 0x04, // 0x200: call 0x204
+        0x12, 0x02, // 0x202: jump 0x202
+        0xA2, 0x0A, // 0x204: i := 0x20A
+        0xD0, 0x12, // 0x206: sprite v0 v1 2
+        0x00, 0xEE, // 0x208: return
+        0x3C, 0x81, // 0x20A: sprite data
+    ];
+
+    #[test]
+    fn test_mnemonic() {
+        let draw = Instruction::Draw { x: 1, y: 0xA, n: 5 };
+
+        assert_eq!(mnemonic(draw, 0, Syntax::Octo), "sprite v1 va 5");
+        assert_eq!(mnemonic(draw, 0, Syntax::Cowgod), "DRW V1, VA, 5");
+        assert_eq!(
+            mnemonic(Instruction::SkipEqImm { x: 3, nn: 0x10 }, 0, Syntax::Octo),
+            "if v3 != 0x10 then"
+        );
+        assert_eq!(
+            mnemonic(Instruction::SetLongI, 0x1234, Syntax::Cowgod),
+            "LD I, LONG 0x1234"
+        );
+    }
+
+    #[test]
+    fn test_octo_listing() {
+        let listing = disassemble(&PROGRAM, false, Syntax::Octo);
+        let lines: Vec<&str> = listing.lines().collect();
+
+        assert_eq!(lines[0], ": main");
+        assert!(lines[1].starts_with("    :call sub_204"));
+        assert!(lines[1].ends_with("# 0x200  2204"));
+        assert_eq!(lines[2], ": label_202");
+        assert!(lines[3].starts_with("    jump label_202"));
+        assert_eq!(lines[4], ": sub_204");
+        assert!(lines[5].starts_with("    i := sprite_20A"));
+        assert_eq!(lines[8], ": sprite_20A");
+        assert!(lines[9].starts_with("    0x3C"));
+        assert!(lines[9].ends_with("..####.."));
+        assert!(lines[10].ends_with("#......#"));
+    }
+
+    #[test]
+    fn test_cowgod_listing() {
+        let listing = disassemble(&PROGRAM, false, Syntax::Cowgod);
+        let lines: Vec<&str> = listing.lines().collect();
+
+        assert_eq!(lines[0], "main:");
+        assert!(lines[1].starts_with("    0x200  2204"));
+        assert!(lines[1].ends_with("CALL sub_204"));
+        assert!(lines[9].contains("DB 0x3C"));
+    }
+
+    #[test]
+    fn test_unreached_bytes_are_data() {
+        // the jump skips over two words that would decode as instructions,
+        // and targets an address outside the ROM
+        let program = [0x12, 0x06, 0x60, 0x01, 0x61, 0x02, 0x13, 0x00];
+        let listing = disassemble(&program, false, Syntax::Octo);
+        let lines: Vec<&str> = listing.lines().collect();
+
+        assert!(lines[2].starts_with("    0x60 0x01"));
+        assert!(lines[3].starts_with("    0x61 0x02"));
+        assert!(lines[5].starts_with("    jump 0x300"));
+    }
+
+    #[test]
+    fn test_xo_chip_long_load() {
+        let program = [0xF0, 0x00, 0x02, 0x06, 0x00, 0xFD, 0xFF, 0x00];
+
+        let listing = disassemble(&program, true, Syntax::Octo);
+        assert!(listing.contains("i := long data_206"));
+
+        // without XO-CHIP, F000 isn't an instruction at all
+        let listing = disassemble(&program, false, Syntax::Octo);
+        assert!(listing.contains("0xF0 0x00"));
+    }
+
+    #[test]
+    fn test_syntax_from_name() {
+        assert_eq!(Syntax::from_name("OCTO"), Some(Syntax::Octo));
+        assert_eq!(Syntax::from_name("cowgod"), Some(Syntax::Cowgod));
+        assert_eq!(Syntax::from_name("intel"), None);
+    }
+}
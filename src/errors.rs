@@ -16,3 +16,226 @@ impl error::Error for ProgramTooLargeError {
         None
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip8Fault {
+    UnknownOpcode {
+        pc: u16,
+        opcode: u16,
+    },
+    StackOverflow {
+        pc: u16,
+        opcode: u16,
+    },
+    StackUnderflow {
+        pc: u16,
+        opcode: u16,
+    },
+    MemoryOutOfRange {
+        pc: u16,
+        opcode: u16,
+        address: usize,
+    },
+    BadKey {
+        pc: u16,
+        opcode: u16,
+        key: u8,
+    },
+    // an `Instruction` built with a register or operand too big for its
+    // opcode, which `decode` never produces
+    BadOperand {
+        pc: u16,
+        opcode: u16,
+    },
+}
+
+impl Chip8Fault {
+    pub fn pc(&self) -> u16 {
+        match *self {
+            Chip8Fault::UnknownOpcode { pc, .. }
+            | Chip8Fault::StackOverflow { pc, .. }
+            | Chip8Fault::StackUnderflow { pc, .. }
+            | Chip8Fault::MemoryOutOfRange { pc, .. }
+            | Chip8Fault::BadKey { pc, .. }
+            | Chip8Fault::BadOperand { pc, .. } => pc,
+        }
+    }
+
+    pub fn opcode(&self) -> u16 {
+        match *self {
+            Chip8Fault::UnknownOpcode { opcode, .. }
+            | Chip8Fault::StackOverflow { opcode, .. }
+            | Chip8Fault::StackUnderflow { opcode, .. }
+            | Chip8Fault::MemoryOutOfRange { opcode, .. }
+            | Chip8Fault::BadKey { opcode, .. }
+            | Chip8Fault::BadOperand { opcode, .. } => opcode,
+        }
+    }
+}
+
+impl fmt::Display for Chip8Fault {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            Chip8Fault::UnknownOpcode { .. } => write!(f, "unknown opcode")?,
+            Chip8Fault::StackOverflow { .. } => write!(f, "stack overflow")?,
+            Chip8Fault::StackUnderflow { .. } => write!(f, "stack underflow")?,
+            Chip8Fault::MemoryOutOfRange { address, .. } => {
+                write!(f, "memory access out of range at {:#05X}", address)?
+            }
+            Chip8Fault::BadKey { key, .. } => write!(f, "bad key index {:#X}", key)?,
+            Chip8Fault::BadOperand { .. } => write!(f, "operand out of range")?,
+        }
+
+        write!(
+            f,
+            " (pc: {:#05X}, opcode: {:#06X})",
+            self.pc(),
+            self.opcode()
+        )
+    }
+}
+
+impl error::Error for Chip8Fault {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    ChecksumMismatch,
+    Malformed,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            StateError::BadMagic => write!(f, "not a save state"),
+            StateError::UnsupportedVersion(v) => {
+                write!(f, "unsupported save state version {}", v)
+            }
+            StateError::ChecksumMismatch => write!(f, "save state is corrupt (bad checksum)"),
+            StateError::Malformed => write!(f, "save state is malformed"),
+        }
+    }
+}
+
+impl error::Error for StateError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeError {
+    pub opcode: u16,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown opcode {:#06X}", self.opcode)
+    }
+}
+
+impl error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        None
+    }
+}
+
+// An assembly error, positioned at a 1-based line and column
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub file: Option<String>,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file)?;
+        }
+
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl error::Error for AsmError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandError {
+    Invalid(String),
+    Fault(Chip8Fault),
+}
+
+impl From<Chip8Fault> for CommandError {
+    fn from(fault: Chip8Fault) -> Self {
+        CommandError::Fault(fault)
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::Invalid(message) => write!(f, "{}", message),
+            CommandError::Fault(fault) => write!(f, "CPU fault: {}", fault),
+        }
+    }
+}
+
+impl error::Error for CommandError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        None
+    }
+}
+
+// A line of a headless key script that couldn't be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl error::Error for ScriptError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        None
+    }
+}
+
+// A replayed frame whose screen doesn't match the one in the movie
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DesyncError {
+    pub frame: u64,
+    pub expected: u32,
+    pub actual: u32,
+}
+
+impl fmt::Display for DesyncError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "frame {} doesn't match the movie (screen hash {:08x}, expected {:08x})",
+            self.frame, self.actual, self.expected
+        )
+    }
+}
+
+impl error::Error for DesyncError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        None
+    }
+}
@@ -0,0 +1,357 @@
+use crate::{Chip8, Debugger, Keypad};
+
+// Something the player did, as far as the hex keypad is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputEvent {
+    KeyDown(u8),
+    KeyUp(u8),
+}
+
+// Commands for the frontend itself, as opposed to keys on the hex keypad.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Hotkey {
+    SaveState,
+    LoadState,
+    NextSlot,
+    Rewind,
+    TogglePause,
+    Step,
+    StepFrame,
+    ToggleBreakpoint,
+    Screenshot,
+    ToggleRecording,
+    // a debugger command, as typed
+    Command(String),
+}
+
+// The display as a frontend sees it: one XO-CHIP color (0-3) per pixel, row
+// by row, along with the part of it that has changed since it was last
+// presented. Pixels outside `dirty` are as they were, unless the resolution
+// has changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Framebuffer<'a> {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: &'a [u8],
+    pub dirty: Region,
+}
+
+// A rectangle of pixels, `width` by `height` with its top left at `x`, `y`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Region {
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Region {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        (self.x..self.x + self.width).contains(&x) && (self.y..self.y + self.height).contains(&y)
+    }
+
+    // The smallest region covering both.
+    pub fn union(self, other: Region) -> Region {
+        if self.is_empty() {
+            return other;
+        }
+
+        if other.is_empty() {
+            return self;
+        }
+
+        let (x, y) = (self.x.min(other.x), self.y.min(other.y));
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+
+        Region::new(x, y, right - x, bottom - y)
+    }
+}
+
+impl Framebuffer<'_> {
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y * self.width + x]
+    }
+}
+
+// Where a machine's picture and sound go, and where its input comes from.
+// The terminal is one frontend; tests use `NullFrontend`. Hotkeys, the
+// status line and the debugger panel are optional.
+pub trait Frontend {
+    // Shows the display; only called when it may have changed.
+    fn present(&mut self, framebuffer: &Framebuffer<'_>);
+
+    // Everything that happened since the last call. Frontends that can't see
+    // key releases only report presses, and let the keypad release keys.
+    fn poll_input(&mut self) -> Vec<InputEvent>;
+
+    fn start_tone(&mut self);
+
+    fn stop_tone(&mut self);
+
+    // Whether the player has asked to quit.
+    fn quit_requested(&self) -> bool;
+
+    // The hotkeys pressed since the last call.
+    fn take_hotkeys(&mut self) -> Vec<Hotkey> {
+        vec![]
+    }
+
+    // A message for the player, shown until the next one.
+    fn show_status(&mut self, _status: String) {}
+
+    // Shows the debugger's view of the machine next to the display; only
+    // called when it may have changed.
+    fn draw_debugger(&mut self, _chip8: &Chip8, _debugger: &Debugger) {}
+
+    // Whether the display has to be presented every frame, even if the
+    // program hasn't drawn anything.
+    fn is_fading(&self) -> bool {
+        false
+    }
+
+    // What the player sees, for a screenshot.
+    fn shown<'a>(&'a self, chip8: &'a Chip8) -> Framebuffer<'a> {
+        chip8.framebuffer()
+    }
+
+    // Numbers the frame about to be presented, for frontends that show it.
+    fn set_frame(&mut self, _frame: u32) {}
+}
+
+// Moves input and output between a `Chip8` and a frontend. Input goes
+// through a keypad, the display is presented whenever it changes, and the
+// tone starts and stops with the sound timer.
+pub struct Io {
+    keypad: Keypad,
+    tone: bool,
+}
+
+impl Io {
+    pub fn new(keypad: Keypad) -> Self {
+        Io {
+            keypad,
+            tone: false,
+        }
+    }
+
+    pub fn keypad(&mut self) -> &mut Keypad {
+        &mut self.keypad
+    }
+
+    // Hands the frontend's input to the machine; call before running a frame.
+    pub fn input<F: Frontend + ?Sized>(&mut self, frontend: &mut F, chip8: &mut Chip8) {
+        for event in frontend.poll_input() {
+            match event {
+                InputEvent::KeyDown(key) => self.keypad.press(chip8, key),
+                InputEvent::KeyUp(key) => self.keypad.release(chip8, key),
+            }
+        }
+    }
+
+    // Lets the keypad release held keys; call after running a frame.
+    pub fn end_frame(&mut self, chip8: &mut Chip8) {
+        self.keypad.tick(chip8);
+    }
+
+    // Presents the display if it has changed, and starts or stops the tone.
+    // A paused machine passes `muted` so the tone doesn't drone on.
+    pub fn output<F: Frontend + ?Sized>(
+        &mut self,
+        frontend: &mut F,
+        chip8: &mut Chip8,
+        muted: bool,
+    ) {
+        if chip8.draw_flag {
+            chip8.draw_flag = false;
+            frontend.present(&chip8.framebuffer());
+            chip8.take_dirty_region();
+        }
+
+        let tone = chip8.sound_timer > 0 && !muted;
+
+        if tone != self.tone {
+            self.tone = tone;
+
+            if tone {
+                frontend.start_tone();
+            } else {
+                frontend.stop_tone();
+            }
+        }
+    }
+}
+
+// A frontend with no screen, speaker or keyboard. It keeps the last picture
+// it was given, and hands out whatever input it's been told to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NullFrontend {
+    input: Vec<InputEvent>,
+    hotkeys: Vec<Hotkey>,
+    status: String,
+    presented: usize,
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+    dirty: Region,
+    tone: bool,
+    quit: bool,
+}
+
+impl NullFrontend {
+    pub fn new() -> Self {
+        NullFrontend::default()
+    }
+
+    // Queues an event for the next `poll_input`.
+    pub fn push_input(&mut self, event: InputEvent) {
+        self.input.push(event);
+    }
+
+    // Queues a hotkey for the next `take_hotkeys`.
+    pub fn push_hotkey(&mut self, hotkey: Hotkey) {
+        self.hotkeys.push(hotkey);
+    }
+
+    // The last status shown.
+    pub fn status(&self) -> &str {
+        &self.status
+    }
+
+    pub fn request_quit(&mut self) {
+        self.quit = true;
+    }
+
+    // How many times the display has been presented.
+    pub fn presented(&self) -> usize {
+        self.presented
+    }
+
+    // The last picture presented.
+    pub fn framebuffer(&self) -> Framebuffer<'_> {
+        Framebuffer {
+            width: self.width,
+            height: self.height,
+            pixels: &self.pixels,
+            dirty: self.dirty,
+        }
+    }
+
+    pub fn is_tone_playing(&self) -> bool {
+        self.tone
+    }
+}
+
+impl Frontend for NullFrontend {
+    fn present(&mut self, framebuffer: &Framebuffer<'_>) {
+        self.presented += 1;
+        self.width = framebuffer.width;
+        self.height = framebuffer.height;
+        self.pixels = framebuffer.pixels.to_vec();
+        self.dirty = framebuffer.dirty;
+    }
+
+    fn poll_input(&mut self) -> Vec<InputEvent> {
+        std::mem::take(&mut self.input)
+    }
+
+    fn start_tone(&mut self) {
+        self.tone = true;
+    }
+
+    fn stop_tone(&mut self) {
+        self.tone = false;
+    }
+
+    fn quit_requested(&self) -> bool {
+        self.quit
+    }
+
+    fn take_hotkeys(&mut self) -> Vec<Hotkey> {
+        std::mem::take(&mut self.hotkeys)
+    }
+
+    fn show_status(&mut self, status: String) {
+        self.status = status;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Frontend, Hotkey, InputEvent, Io, NullFrontend};
+    use crate::{Chip8, Keypad, Quirks};
+
+    // waits for a key, draws its digit, beeps for 3 frames, then waits again
+    //
+    //     v0 := key; i := hex v0; clear; sprite v1 v1 5
+    //     v2 := 3; buzzer := v2; jump 0x200
+    const PROGRAM: [u8; 14] = [
+        0xF0, 0x0A, 0xF0, 0x29, 0x00, 0xE0, 0xD1, 0x15, 0x62, 0x03, 0xF2, 0x18, 0x12, 0x00,
+    ];
+
+    fn frame(io: &mut Io, frontend: &mut NullFrontend, chip8: &mut Chip8) {
+        io.input(frontend, chip8);
+        chip8.run_frame(10).unwrap();
+        io.end_frame(chip8);
+        io.output(frontend, chip8, false);
+    }
+
+    #[test]
+    fn test_null_frontend() {
+        let mut chip8 = Chip8::new(Quirks::default());
+        let mut frontend = NullFrontend::new();
+        let mut io = Io::new(Keypad::new(None));
+
+        chip8.load_program(PROGRAM.to_vec()).unwrap();
+
+        // nothing is presented until the program draws something
+        frame(&mut io, &mut frontend, &mut chip8);
+        assert_eq!(frontend.presented(), 0);
+
+        frontend.push_input(InputEvent::KeyDown(1));
+        frame(&mut io, &mut frontend, &mut chip8);
+        assert!(chip8.is_key_down(1));
+        assert_eq!(frontend.presented(), 0);
+
+        frontend.push_input(InputEvent::KeyUp(1));
+        frame(&mut io, &mut frontend, &mut chip8);
+
+        // the top of a 1 is a single pixel, two in from the left
+        let framebuffer = frontend.framebuffer();
+
+        assert_eq!(frontend.presented(), 1);
+        assert_eq!((framebuffer.width, framebuffer.height), (64, 32));
+        assert_eq!(&framebuffer.pixels[..4], &[0, 0, 1, 0]);
+        assert!(frontend.is_tone_playing());
+
+        for _ in 0..3 {
+            frame(&mut io, &mut frontend, &mut chip8);
+        }
+
+        assert!(!frontend.is_tone_playing());
+        assert!(!frontend.quit_requested());
+
+        frontend.push_hotkey(Hotkey::Screenshot);
+        assert_eq!(frontend.take_hotkeys(), vec![Hotkey::Screenshot]);
+        assert!(frontend.take_hotkeys().is_empty());
+
+        frontend.show_status("paused".to_string());
+        assert_eq!(frontend.status(), "paused");
+
+        frontend.request_quit();
+        assert!(frontend.quit_requested());
+    }
+}
@@ -0,0 +1,774 @@
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+
+use crate::{
+    Access, Chip8, Chip8Fault, Debugger, Location, StepOutcome, Stop, WatchKind, WatchTarget,
+    Watchpoint,
+};
+
+// The registers as gdb numbers them: V0-VF, then I, PC and SP. I and PC are
+// sent as two big-endian bytes, like everything else in CHIP-8 memory.
+const REGISTER_COUNT: usize = 19;
+const I_REGISTER: usize = 16;
+const PC_REGISTER: usize = 17;
+const SP_REGISTER: usize = 18;
+const PACKET_SIZE: usize = 0x1000;
+// ^C from the client
+const INTERRUPT: u8 = 0x03;
+// signals reported in stop replies
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+// A GDB remote serial protocol stub, so gdb or any other RSP client can
+// attach to the emulator over TCP. It never blocks: frontends call `poll`
+// once a frame to answer the client, and run frames through `run_frame`
+// rather than `Debugger::run_frame`, so that the client hears about
+// breakpoints, watchpoints and faults.
+//
+// One client is served at a time; execution pauses when it connects, and
+// carries on when it detaches.
+pub struct GdbStub {
+    listener: TcpListener,
+    client: Option<Client>,
+}
+
+struct Client {
+    stream: TcpStream,
+    input: Vec<u8>,
+    // cleared by QStartNoAckMode
+    acks: bool,
+    // sent again if the client says it arrived garbled
+    last_packet: Vec<u8>,
+    // the client sent `c` and is waiting for a stop reply
+    running: bool,
+}
+
+enum Incoming {
+    Packet(String),
+    Interrupt,
+}
+
+// what a packet asks the stub to do, beyond replying
+enum Response {
+    Reply(String),
+    Continue,
+    Detach,
+    Kill,
+}
+
+impl GdbStub {
+    pub fn bind<A: ToSocketAddrs>(address: A) -> io::Result<GdbStub> {
+        let listener = TcpListener::bind(address)?;
+
+        listener.set_nonblocking(true)?;
+
+        Ok(GdbStub {
+            listener,
+            client: None,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.client.is_some()
+    }
+
+    // Accepts a new client if there isn't one, then answers everything the
+    // client has sent since the last call. A client that goes away, or
+    // whose connection fails, is dropped and another can connect.
+    pub fn poll(&mut self, chip8: &mut Chip8, debugger: &mut Debugger) {
+        if self.client.is_none() {
+            let stream = match self.listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(_) => return,
+            };
+
+            if stream.set_nonblocking(true).is_err() {
+                return;
+            }
+
+            debugger.pause();
+            self.client = Some(Client {
+                stream,
+                input: vec![],
+                acks: true,
+                last_packet: vec![],
+                running: false,
+            });
+        }
+
+        if let Some(client) = self.client.as_mut() {
+            if client.serve(chip8, debugger).is_err() {
+                self.client = None;
+            }
+        }
+    }
+
+    // Runs a frame through the debugger, and tells a waiting client if it
+    // stopped.
+    pub fn run_frame(
+        &mut self,
+        chip8: &mut Chip8,
+        debugger: &mut Debugger,
+    ) -> Result<Option<Stop>, Chip8Fault> {
+        let result = debugger.run_frame(chip8);
+        let reply = match &result {
+            Ok(Some(stop)) => stop_reply(*stop),
+            Ok(None) => return result,
+            Err(fault) => fault_reply(fault),
+        };
+
+        if let Some(client) = self.client.as_mut() {
+            if client.running {
+                client.running = false;
+
+                if client.send(&reply).is_err() {
+                    self.client = None;
+                }
+            }
+        }
+
+        result
+    }
+}
+
+impl Client {
+    fn serve(&mut self, chip8: &mut Chip8, debugger: &mut Debugger) -> io::Result<()> {
+        let mut buffer = [0; PACKET_SIZE];
+
+        // anything past a couple of packets' worth waits in the socket until
+        // the packets before it have been answered
+        while self.input.len() < PACKET_SIZE * 2 {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+                Ok(n) => self.input.extend_from_slice(&buffer[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        while let Some(incoming) = self.next_incoming()? {
+            let packet = match incoming {
+                Incoming::Packet(packet) => packet,
+                Incoming::Interrupt => {
+                    // the reply to the `c` being interrupted is sent below
+                    debugger.pause();
+                    continue;
+                }
+            };
+
+            if packet == "QStartNoAckMode" {
+                self.send("OK")?;
+                self.acks = false;
+                continue;
+            }
+
+            match respond(chip8, debugger, &packet) {
+                Response::Reply(reply) => self.send(&reply)?,
+                Response::Continue => {
+                    debugger.resume();
+                    self.running = true;
+                }
+                Response::Detach => {
+                    self.send("OK")?;
+                    debugger.resume();
+                    return Err(ErrorKind::ConnectionAborted.into());
+                }
+                Response::Kill => return Err(ErrorKind::ConnectionAborted.into()),
+            }
+        }
+
+        // paused some other way, such as from the frontend's own debugger
+        if self.running && debugger.is_paused() {
+            self.running = false;
+            self.send(&format!("S{:02x}", SIGINT))?;
+        }
+
+        Ok(())
+    }
+
+    // Takes the next packet or interrupt out of the input, acknowledging
+    // packets as it goes. Returns `None` until a whole packet has arrived,
+    // and fails if one runs past the packet size the client was told.
+    fn next_incoming(&mut self) -> io::Result<Option<Incoming>> {
+        loop {
+            // acknowledgements, and anything else between packets, are skipped
+            let skipped = self
+                .input
+                .iter()
+                .position(|b| matches!(*b, b'$' | b'-' | INTERRUPT))
+                .unwrap_or(self.input.len());
+
+            self.input.drain(..skipped);
+
+            match self.input.first() {
+                None => return Ok(None),
+                Some(&INTERRUPT) => {
+                    self.input.drain(..1);
+                    return Ok(Some(Incoming::Interrupt));
+                }
+                Some(&b'-') => {
+                    self.input.drain(..1);
+                    let packet = self.last_packet.clone();
+                    self.write(&packet)?;
+                    continue;
+                }
+                _ => {}
+            }
+
+            let end = match self.input.iter().position(|b| *b == b'#') {
+                Some(end) if end >= PACKET_SIZE => return Err(ErrorKind::InvalidData.into()),
+                Some(end) if end + 2 < self.input.len() => end,
+                None if self.input.len() >= PACKET_SIZE => {
+                    return Err(ErrorKind::InvalidData.into())
+                }
+                _ => return Ok(None),
+            };
+            let packet: Vec<u8> = self.input.drain(..end + 3).collect();
+            let data = &packet[1..end];
+            let checksum = std::str::from_utf8(&packet[end + 1..])
+                .ok()
+                .and_then(|c| u8::from_str_radix(c, 16).ok());
+
+            if self.acks {
+                if checksum != Some(checksum_of(data)) {
+                    self.write(b"-")?;
+                    continue;
+                }
+
+                self.write(b"+")?;
+            }
+
+            return Ok(Some(Incoming::Packet(
+                String::from_utf8_lossy(data).into_owned(),
+            )));
+        }
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${}#{:02x}", data, checksum_of(data.as_bytes()));
+
+        self.last_packet = packet.clone().into_bytes();
+        self.write(packet.as_bytes())
+    }
+
+    // The socket doesn't block, but replies are small enough that the
+    // kernel's buffer takes them whole unless the client has stopped reading.
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.stream.write_all(bytes)?;
+        self.stream.flush()
+    }
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, b| sum.wrapping_add(*b))
+}
+
+// Handles a single packet. Anything not understood gets the empty reply,
+// which tells the client it isn't supported.
+fn respond(chip8: &mut Chip8, debugger: &mut Debugger, packet: &str) -> Response {
+    let reply = |r: &str| Response::Reply(r.to_string());
+    let error = || reply("E01");
+    let (command, arguments) = packet.split_at(packet.len().min(1));
+
+    match command {
+        "?" => reply(&format!("S{:02x}", SIGTRAP)),
+        "g" => {
+            let bytes: Vec<u8> = (0..REGISTER_COUNT)
+                .flat_map(|n| read_register(chip8, n))
+                .collect();
+
+            reply(&to_hex(&bytes))
+        }
+        "G" => {
+            let mut bytes = match from_hex(arguments) {
+                Some(bytes) => bytes,
+                None => return error(),
+            };
+
+            for n in 0..REGISTER_COUNT {
+                let size = register_size(n);
+
+                if bytes.len() < size || !write_register(chip8, n, &bytes[..size]) {
+                    return error();
+                }
+
+                bytes.drain(..size);
+            }
+
+            reply("OK")
+        }
+        "p" => match parse_hex(arguments) {
+            Some(n) if (n as usize) < REGISTER_COUNT => {
+                reply(&to_hex(&read_register(chip8, n as usize)))
+            }
+            _ => error(),
+        },
+        "P" => {
+            let written = arguments.split_once('=').and_then(|(n, value)| {
+                let n = parse_hex(n)? as usize;
+                let value = from_hex(value)?;
+
+                if n < REGISTER_COUNT && value.len() == register_size(n) {
+                    Some(write_register(chip8, n, &value))
+                } else {
+                    None
+                }
+            });
+
+            match written {
+                Some(true) => reply("OK"),
+                _ => error(),
+            }
+        }
+        "m" => {
+            let range = arguments
+                .split_once(',')
+                .and_then(|(address, length)| Some((parse_hex(address)?, parse_hex(length)?)));
+            let memory = chip8.memory();
+
+            match range {
+                // reads that run off the end of memory are cut short
+                Some((address, length)) if (address as usize) < memory.len() => {
+                    let start = address as usize;
+                    let end = (start + length as usize).min(memory.len());
+
+                    reply(&to_hex(&memory[start..end]))
+                }
+                _ => error(),
+            }
+        }
+        "M" => {
+            let write = arguments.split_once(':').and_then(|(range, data)| {
+                let (address, length) = range.split_once(',')?;
+                let address = parse_hex(address)? as usize;
+                let data = from_hex(data)?;
+
+                if data.len() != parse_hex(length)? as usize {
+                    return None;
+                }
+
+                let destination = chip8.memory_mut().get_mut(address..address + data.len())?;
+
+                destination.copy_from_slice(&data);
+                Some(())
+            });
+
+            match write {
+                Some(()) => reply("OK"),
+                None => error(),
+            }
+        }
+        "c" | "s" => {
+            if !arguments.is_empty() {
+                match parse_hex(arguments) {
+                    Some(address) if address <= 0xFFFF => chip8.set_pc(address as u16),
+                    _ => return error(),
+                }
+            }
+
+            if command == "c" {
+                return Response::Continue;
+            }
+
+            match debugger.step(chip8) {
+                Ok(StepOutcome::Exited) => reply("W00"),
+                Ok(_) => reply(&format!("S{:02x}", SIGTRAP)),
+                Err(fault) => reply(&fault_reply(&fault)),
+            }
+        }
+        "Z" | "z" => match parse_point(arguments) {
+            Some(point) => {
+                set_point(debugger, point, command == "Z");
+                reply("OK")
+            }
+            None => reply(""),
+        },
+        "H" => reply("OK"),
+        "D" => Response::Detach,
+        "k" => Response::Kill,
+        _ if packet.starts_with("qSupported") => {
+            reply(&format!("PacketSize={:x};QStartNoAckMode+", PACKET_SIZE))
+        }
+        _ if packet == "qAttached" => reply("1"),
+        _ => reply(""),
+    }
+}
+
+fn register_size(n: usize) -> usize {
+    match n {
+        I_REGISTER | PC_REGISTER => 2,
+        _ => 1,
+    }
+}
+
+fn read_register(chip8: &Chip8, n: usize) -> Vec<u8> {
+    match n {
+        I_REGISTER => chip8.i().to_be_bytes().to_vec(),
+        PC_REGISTER => chip8.pc().to_be_bytes().to_vec(),
+        SP_REGISTER => vec![chip8.sp()],
+        _ => vec![chip8.v()[n]],
+    }
+}
+
+// Returns false if the value can't go in the register; SP can't point past
+// the end of the stack.
+fn write_register(chip8: &mut Chip8, n: usize, bytes: &[u8]) -> bool {
+    match n {
+        I_REGISTER => chip8.set_i(u16::from_be_bytes([bytes[0], bytes[1]])),
+        PC_REGISTER => chip8.set_pc(u16::from_be_bytes([bytes[0], bytes[1]])),
+        SP_REGISTER => {
+            if bytes[0] as usize > chip8.stack().len() {
+                return false;
+            }
+
+            chip8.set_sp(bytes[0]);
+        }
+        _ => chip8.set_v(n, bytes[0]),
+    }
+
+    true
+}
+
+// A Z or z packet: `TYPE,ADDR,KIND`. Types 0 and 1 are breakpoints; 2, 3 and
+// 4 are write, read and access watchpoints over KIND bytes. Returns the type
+// and the first and last addresses covered.
+fn parse_point(arguments: &str) -> Option<(u32, u16, u16)> {
+    let mut fields = arguments.split(',');
+    let kind = parse_hex(fields.next()?)?;
+    let address = parse_hex(fields.next()?)?;
+    let length = parse_hex(fields.next()?.split(';').next()?)?;
+    let end = address.checked_add(length.max(1) - 1)?;
+
+    if kind > 4 || end > 0xFFFF {
+        return None;
+    }
+
+    Some((kind, address as u16, end as u16))
+}
+
+fn set_point(debugger: &mut Debugger, (kind, address, end): (u32, u16, u16), insert: bool) {
+    let watch = match kind {
+        0 | 1 => {
+            if insert {
+                debugger.add_breakpoint(address);
+            } else {
+                debugger.remove_breakpoint(address);
+            }
+
+            return;
+        }
+        2 => WatchKind::Write,
+        3 => WatchKind::Read,
+        _ => WatchKind::Access,
+    };
+    let target = WatchTarget::Memory {
+        start: address,
+        end,
+    };
+
+    if insert {
+        debugger.add_watchpoint(Watchpoint {
+            target,
+            kind: watch,
+        });
+    } else {
+        debugger.remove_watchpoint(target);
+    }
+}
+
+fn stop_reply(stop: Stop) -> String {
+    match stop {
+        Stop::Watchpoint {
+            location: Location::Memory(address),
+            access,
+            ..
+        } => {
+            let kind = match access {
+                Access::Read => "rwatch",
+                Access::Write => "watch",
+            };
+
+            format!("T{:02x}{}:{:x};", SIGTRAP, kind, address)
+        }
+        Stop::Breakpoint(_) | Stop::Watchpoint { .. } => format!("S{:02x}", SIGTRAP),
+        Stop::Exited => "W00".to_string(),
+    }
+}
+
+fn fault_reply(fault: &Chip8Fault) -> String {
+    let signal = match fault {
+        Chip8Fault::UnknownOpcode { .. } | Chip8Fault::BadOperand { .. } => SIGILL,
+        _ => SIGSEGV,
+    };
+
+    format!("S{:02x}", signal)
+}
+
+fn parse_hex(text: &str) -> Option<u32> {
+    u32::from_str_radix(text, 16).ok()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{ErrorKind, Read, Write};
+    use std::net::TcpStream;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    use super::{checksum_of, GdbStub, PACKET_SIZE};
+    use crate::{Chip8, Debugger, Quirks};
+
+    // v0 += 1 in a loop, with a CLS at 0x204 to break on
+    const PROGRAM: [u8; 8] = [0x70, 0x01, 0x12, 0x04, 0x00, 0xE0, 0x12, 0x00];
+
+    // A scripted client on the other end of a real socket.
+    struct Session {
+        stub: GdbStub,
+        client: TcpStream,
+        chip8: Chip8,
+        debugger: Debugger,
+    }
+
+    impl Session {
+        fn start() -> Session {
+            let stub = GdbStub::bind("127.0.0.1:0").unwrap();
+            let client = TcpStream::connect(stub.local_addr().unwrap()).unwrap();
+            let mut chip8 = Chip8::new(Quirks::default());
+
+            client.set_nonblocking(true).unwrap();
+            chip8.load_program(PROGRAM.to_vec()).unwrap();
+
+            let mut session = Session {
+                stub,
+                client,
+                chip8,
+                debugger: Debugger::new(10),
+            };
+
+            session.debugger.resume();
+            session.poll();
+            assert!(session.stub.is_connected());
+
+            session
+        }
+
+        fn poll(&mut self) {
+            self.stub.poll(&mut self.chip8, &mut self.debugger);
+        }
+
+        fn send(&mut self, packet: &str) {
+            let framed = format!("${}#{:02x}", packet, checksum_of(packet.as_bytes()));
+
+            self.client.write_all(framed.as_bytes()).unwrap();
+        }
+
+        // Polls the stub until a whole reply has come back, and returns what
+        // was in it, checking the acknowledgement and checksum on the way.
+        fn receive(&mut self) -> String {
+            let mut received = vec![];
+
+            for _ in 0..1000 {
+                self.poll();
+
+                let mut buffer = [0; 4096];
+
+                match self.client.read(&mut buffer) {
+                    Ok(n) => received.extend_from_slice(&buffer[..n]),
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                    Err(e) => panic!("{}", e),
+                }
+
+                let text = String::from_utf8_lossy(&received).into_owned();
+
+                if let Some(end) = text.find('#') {
+                    if text.len() >= end + 3 {
+                        let start = text.find('$').unwrap();
+                        let data = &text[start + 1..end];
+                        let checksum = u8::from_str_radix(&text[end + 1..end + 3], 16).unwrap();
+
+                        assert_eq!(checksum, checksum_of(data.as_bytes()));
+                        return data.to_string();
+                    }
+                }
+
+                sleep(Duration::from_millis(1));
+            }
+
+            panic!("no reply");
+        }
+
+        fn exchange(&mut self, packet: &str) -> String {
+            self.send(packet);
+            self.receive()
+        }
+    }
+
+    #[test]
+    fn test_registers() {
+        let mut session = Session::start();
+
+        // connecting pauses execution
+        assert!(session.debugger.is_paused());
+        assert_eq!(session.exchange("?"), "S05");
+
+        session.chip8.set_v(0xA, 0x42);
+        session.chip8.set_i(0x345);
+
+        let registers = session.exchange("g");
+
+        assert_eq!(registers.len(), (16 + 2 + 2 + 1) * 2);
+        assert_eq!(&registers[20..22], "42");
+        assert_eq!(&registers[32..], "0345020000");
+
+        assert_eq!(session.exchange("P3=7f"), "OK");
+        assert_eq!(session.exchange("P10=0123"), "OK");
+        assert_eq!(session.exchange("p3"), "7f");
+        assert_eq!(session.chip8.i(), 0x123);
+
+        // SP can't point past the end of the stack
+        assert_eq!(session.exchange("P12=11"), "E01");
+        assert_eq!(session.exchange("P12=01"), "OK");
+        assert_eq!(session.chip8.sp(), 1);
+    }
+
+    #[test]
+    fn test_memory() {
+        let mut session = Session::start();
+
+        assert_eq!(session.exchange("m200,4"), "70011204");
+        assert_eq!(session.exchange("M300,3:0102ff"), "OK");
+        assert_eq!(&session.chip8.memory()[0x300..0x303], &[1, 2, 0xFF]);
+
+        // reads are cut short at the end of memory, but writes aren't
+        assert_eq!(session.exchange("mffe,4"), "0000");
+        assert_eq!(session.exchange("m1000,1"), "E01");
+        assert_eq!(session.exchange("Mffe,4:01020304"), "E01");
+    }
+
+    #[test]
+    fn test_breakpoints_and_stepping() {
+        let mut session = Session::start();
+
+        assert_eq!(session.exchange("s"), "S05");
+        assert_eq!(session.chip8.pc(), 0x202);
+        assert_eq!(session.exchange("Z0,204,2"), "OK");
+
+        session.send("c");
+        session.poll();
+        assert!(!session.debugger.is_paused());
+
+        session
+            .stub
+            .run_frame(&mut session.chip8, &mut session.debugger)
+            .unwrap();
+
+        assert_eq!(session.receive(), "S05");
+        assert_eq!(session.chip8.pc(), 0x204);
+
+        assert_eq!(session.exchange("z0,204,2"), "OK");
+        assert_eq!(session.debugger.breakpoints().count(), 0);
+    }
+
+    #[test]
+    fn test_watchpoint_and_interrupt() {
+        let mut session = Session::start();
+
+        // BCD of V0 to I
+        session.chip8.memory_mut()[0x200..0x202].copy_from_slice(&[0xF0, 0x33]);
+        session.chip8.set_i(0x300);
+
+        assert_eq!(session.exchange("Z2,301,2"), "OK");
+
+        session.send("c");
+        session.poll();
+        session
+            .stub
+            .run_frame(&mut session.chip8, &mut session.debugger)
+            .unwrap();
+
+        assert_eq!(session.receive(), "T05watch:301;");
+        assert_eq!(session.exchange("z2,301,2"), "OK");
+
+        // watchpoints running past the end of memory aren't supported
+        assert_eq!(session.exchange("Z2,1,ffffffff"), "");
+        assert_eq!(session.exchange("Z2,ffff,2"), "");
+
+        // the loop that follows runs until the client interrupts it
+        session.send("c");
+        session.poll();
+        session
+            .stub
+            .run_frame(&mut session.chip8, &mut session.debugger)
+            .unwrap();
+        session.client.write_all(&[0x03]).unwrap();
+
+        assert_eq!(session.receive(), "S02");
+        assert!(session.debugger.is_paused());
+    }
+
+    #[test]
+    fn test_garbled_and_oversized_packets() {
+        let mut session = Session::start();
+
+        // a flood of packets with bad checksums is turned down one by one
+        session
+            .client
+            .write_all("$#ff".repeat(10_000).as_bytes())
+            .unwrap();
+
+        assert_eq!(session.exchange("?"), "S05");
+
+        // a packet with no end in sight gets the client dropped
+        session
+            .client
+            .write_all(&vec![b'$'; PACKET_SIZE * 2])
+            .unwrap();
+
+        for _ in 0..1000 {
+            session.poll();
+
+            if !session.stub.is_connected() {
+                break;
+            }
+
+            sleep(Duration::from_millis(1));
+        }
+
+        assert!(!session.stub.is_connected());
+    }
+
+    #[test]
+    fn test_unsupported_and_detach() {
+        let mut session = Session::start();
+
+        assert_eq!(session.exchange("vMustReplyEmpty"), "");
+        assert!(session
+            .exchange("qSupported:xmlRegisters=i386")
+            .contains("PacketSize"));
+        assert_eq!(session.exchange("D"), "OK");
+        assert!(!session.stub.is_connected());
+        assert!(!session.debugger.is_paused());
+    }
+}
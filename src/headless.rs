@@ -0,0 +1,279 @@
+use crate::errors::ScriptError;
+use crate::{Chip8, Chip8Fault, StepOutcome};
+
+// A key press or release, applied at the start of a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub frame: u64,
+    pub key: u8,
+    pub pressed: bool,
+}
+
+// Scripted input for headless runs, one event per line:
+//
+//     # frame  action   key
+//     30       press    5
+//     38       release  5
+//
+// Keys are the hex keypad digits 0-F, and `#` starts a comment. Events for
+// the same frame are applied in the order they're listed; frames must not
+// go backwards.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyScript {
+    events: Vec<KeyEvent>,
+}
+
+impl KeyScript {
+    pub fn parse(text: &str) -> Result<KeyScript, ScriptError> {
+        let mut events: Vec<KeyEvent> = vec![];
+
+        for (index, line) in text.lines().enumerate() {
+            let error = |message: String| ScriptError {
+                line: index + 1,
+                message,
+            };
+            let line = line.split('#').next().unwrap_or_default();
+            let words: Vec<&str> = line.split_whitespace().collect();
+
+            if words.is_empty() {
+                continue;
+            }
+
+            let event = parse_event(&words).map_err(error)?;
+
+            if events.last().is_some_and(|last| last.frame > event.frame) {
+                return Err(error(format!("frame {} is out of order", event.frame)));
+            }
+
+            events.push(event);
+        }
+
+        Ok(KeyScript { events })
+    }
+
+    pub(crate) fn from_events(events: Vec<KeyEvent>) -> KeyScript {
+        KeyScript { events }
+    }
+
+    pub fn events(&self) -> &[KeyEvent] {
+        &self.events
+    }
+}
+
+// parses the words of a `FRAME press|release KEY` line
+pub(crate) fn parse_event(words: &[&str]) -> Result<KeyEvent, String> {
+    if words.len() != 3 {
+        return Err("expected FRAME press|release KEY".to_string());
+    }
+
+    let frame = words[0]
+        .parse::<u64>()
+        .map_err(|_| format!("invalid frame: {}", words[0]))?;
+    let pressed = match words[1] {
+        "press" => true,
+        "release" => false,
+        action => return Err(format!("unknown action: {}", action)),
+    };
+    let key = match u8::from_str_radix(words[2], 16) {
+        Ok(key) if words[2].len() == 1 => key,
+        _ => return Err(format!("invalid key: {}", words[2])),
+    };
+
+    Ok(KeyEvent {
+        frame,
+        key,
+        pressed,
+    })
+}
+
+// Runs a ROM without a display or a clock, as fast as it will go, feeding
+// it scripted input. Each call to `run_frame` is one 60 Hz frame, as in the
+// terminal frontend; the run is over once the program exits or either limit
+// is reached.
+pub struct Headless {
+    instructions_per_frame: u32,
+    max_frames: Option<u64>,
+    max_cycles: Option<u64>,
+    keys: KeyScript,
+    next_event: usize,
+    frame: u64,
+    cycles: u64,
+    exited: bool,
+}
+
+impl Headless {
+    pub fn new(
+        instructions_per_frame: u32,
+        max_frames: Option<u64>,
+        max_cycles: Option<u64>,
+        keys: KeyScript,
+    ) -> Self {
+        Headless {
+            instructions_per_frame,
+            max_frames,
+            max_cycles,
+            keys,
+            next_event: 0,
+            frame: 0,
+            cycles: 0,
+            exited: false,
+        }
+    }
+
+    // The number of frames run so far.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    // Whether the program ran 00FD.
+    pub fn exited(&self) -> bool {
+        self.exited
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.exited
+            || self.max_frames.is_some_and(|max| self.frame >= max)
+            || self.max_cycles.is_some_and(|max| self.cycles >= max)
+    }
+
+    // Runs one frame, stopping partway through if it reaches the cycle limit.
+    // Does nothing once the run is finished.
+    pub fn run_frame(&mut self, chip8: &mut Chip8) -> Result<(), Chip8Fault> {
+        if self.is_finished() {
+            return Ok(());
+        }
+
+        let events = self.keys.events();
+
+        while let Some(event) = events.get(self.next_event) {
+            if event.frame > self.frame {
+                break;
+            }
+
+            if event.pressed {
+                chip8.key_down(event.key);
+            } else {
+                chip8.key_up(event.key);
+            }
+
+            self.next_event += 1;
+        }
+
+        let budget = self.max_cycles.map(|max| max - self.cycles);
+        let start = chip8.cycles();
+        let outcome = chip8.run_frame_within(self.instructions_per_frame, budget);
+
+        self.cycles += chip8.cycles() - start;
+        self.exited = outcome? == StepOutcome::Exited;
+        self.frame += 1;
+        Ok(())
+    }
+
+    // Runs frames until the run is finished.
+    pub fn run(&mut self, chip8: &mut Chip8) -> Result<(), Chip8Fault> {
+        while !self.is_finished() {
+            self.run_frame(chip8)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Headless, KeyEvent, KeyScript};
+    use crate::{Chip8, Quirks};
+
+    #[test]
+    fn test_parse_key_script() {
+        let script =
+            KeyScript::parse("# start\n\n3 press a\n3 release A  # tap\n10 press 0\n").unwrap();
+
+        assert_eq!(
+            script.events(),
+            &[
+                KeyEvent {
+                    frame: 3,
+                    key: 0xA,
+                    pressed: true
+                },
+                KeyEvent {
+                    frame: 3,
+                    key: 0xA,
+                    pressed: false
+                },
+                KeyEvent {
+                    frame: 10,
+                    key: 0,
+                    pressed: true
+                },
+            ]
+        );
+
+        for text in [
+            "1 press",
+            "x press 1",
+            "1 hold 1",
+            "1 press 10",
+            "5 press 1\n4 press 2",
+        ]
+        .iter()
+        {
+            assert!(KeyScript::parse(text).is_err(), "{}", text);
+        }
+
+        assert_eq!(
+            KeyScript::parse("1 press 1\n2 tap 1")
+                .unwrap_err()
+                .to_string(),
+            "line 2: unknown action: tap"
+        );
+    }
+
+    #[test]
+    fn test_run_limits() {
+        // v0 += 1; jump back to the add
+        let program = vec![0x70, 0x01, 0x12, 0x00];
+        let mut chip8 = Chip8::new(Quirks::default());
+
+        chip8.load_program(program.clone()).unwrap();
+
+        let mut headless = Headless::new(10, Some(3), None, KeyScript::default());
+
+        headless.run(&mut chip8).unwrap();
+
+        assert_eq!(headless.frame(), 3);
+        assert_eq!(chip8.cycles(), 30);
+        assert_eq!(chip8.v()[0], 15);
+
+        let mut chip8 = Chip8::new(Quirks::default());
+
+        chip8.load_program(program).unwrap();
+
+        let mut headless = Headless::new(10, Some(3), Some(25), KeyScript::default());
+
+        headless.run(&mut chip8).unwrap();
+
+        assert_eq!(headless.frame(), 3);
+        assert_eq!(chip8.cycles(), 25);
+        assert!(!headless.exited());
+    }
+
+    #[test]
+    fn test_scripted_keys() {
+        // v0 := key; exit
+        let program = vec![0xF0, 0x0A, 0x00, 0xFD];
+        let keys = KeyScript::parse("5 press 7\n6 release 7").unwrap();
+        let mut chip8 = Chip8::new(Quirks::default());
+
+        chip8.load_program(program).unwrap();
+
+        let mut headless = Headless::new(10, Some(60), None, keys);
+
+        headless.run(&mut chip8).unwrap();
+
+        assert!(headless.exited());
+        assert_eq!(headless.frame(), 7);
+        assert_eq!(chip8.v()[0], 7);
+    }
+}
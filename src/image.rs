@@ -0,0 +1,414 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::persistence::{fading, is_lit, SHADED_COLORS, SHADES};
+use crate::Framebuffer;
+
+// The largest scale images are drawn at. Screenshots, and every frame of a
+// recording, are drawn in full before they're encoded, so this keeps a
+// hi-res frame within `MAX_IMAGE_PIXELS`.
+pub const MAX_SCALE: u32 = 32;
+
+// the most pixels a single rendered frame may take up, a byte each
+const MAX_IMAGE_PIXELS: usize = 8 * 1024 * 1024;
+
+// The colors pixels are drawn in, indexed by XO-CHIP color (0-3): the
+// background, plane 1, plane 2 and both planes. Programs that don't use
+// XO-CHIP only ever draw the first two.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub colors: [[u8; 3]; 4],
+}
+
+impl Default for Palette {
+    // the colors the terminal frontend uses
+    fn default() -> Self {
+        Palette {
+            colors: [
+                [0x00, 0x00, 0x00],
+                [0xFF, 0xFF, 0xFF],
+                [0x00, 0xFF, 0xFF],
+                [0xFF, 0xFF, 0x00],
+            ],
+        }
+    }
+}
+
+impl Palette {
+    pub const NAMES: [&'static str; 3] = ["default", "octo", "lcd"];
+
+    pub fn from_name(name: &str) -> Option<Palette> {
+        let colors = match name.to_lowercase().as_str() {
+            "default" => return Some(Palette::default()),
+            // Octo's own defaults
+            "octo" => [0x996600, 0xFFCC00, 0xFF6600, 0x662200],
+            // a green handheld LCD
+            "lcd" => [0x9BBC0F, 0x0F380F, 0x306230, 0x8BAC0F],
+            _ => return None,
+        };
+
+        Some(Palette::from_rgb(colors))
+    }
+
+    // Parses a palette name, or two to four comma-separated RRGGBB hex
+    // colors; missing colors are taken from the default palette.
+    pub fn parse(text: &str) -> Option<Palette> {
+        if let Some(palette) = Palette::from_name(text) {
+            return Some(palette);
+        }
+
+        let colors: Vec<&str> = text.split(',').map(str::trim).collect();
+
+        if colors.len() < 2 || colors.len() > 4 {
+            return None;
+        }
+
+        let mut palette = Palette::default();
+
+        for (slot, color) in palette.colors.iter_mut().zip(colors) {
+            let color = color.strip_prefix('#').unwrap_or(color);
+
+            if color.len() != 6 {
+                return None;
+            }
+
+            *slot = rgb(u32::from_str_radix(color, 16).ok()?);
+        }
+
+        Some(palette)
+    }
+
+    fn from_rgb(colors: [u32; 4]) -> Palette {
+        let mut palette = Palette::default();
+
+        for (slot, color) in palette.colors.iter_mut().zip(colors.iter()) {
+            *slot = rgb(*color);
+        }
+
+        palette
+    }
+
+    // Every shaded color (see `Phosphor`): the four colors, then each lit
+    // one fading towards the background.
+    pub fn shaded(&self) -> Vec<[u8; 3]> {
+        let mut colors = self.colors.to_vec();
+        let background = self.colors[0];
+
+        for color in &self.colors[1..] {
+            for level in 1..=SHADES {
+                let mix = |c: usize| {
+                    let (from, to) = (background[c] as u32, color[c] as u32);
+                    let steps = SHADES as u32 + 1;
+
+                    ((from * (steps - level as u32) + to * level as u32) / steps) as u8
+                };
+
+                colors.push([mix(0), mix(1), mix(2)]);
+            }
+        }
+
+        debug_assert_eq!(colors.len(), SHADED_COLORS);
+        colors
+    }
+}
+
+// The brightness of a color, 0-255.
+fn luma([r, g, b]: [u8; 3]) -> u8 {
+    ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8
+}
+
+// splits 0xRRGGBB into its channels
+fn rgb(color: u32) -> [u8; 3] {
+    [(color >> 16) as u8, (color >> 8) as u8, color as u8]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    // black and white; the background is black if it's dark in the
+    // palette, and lit pixels are the opposite
+    Pbm,
+    // grayscale, with the brightness of the palette's colors
+    Pgm,
+    Png,
+}
+
+impl ImageFormat {
+    pub const NAMES: [&'static str; 3] = ["pbm", "pgm", "png"];
+
+    pub fn from_name(name: &str) -> Option<ImageFormat> {
+        match name.to_lowercase().as_str() {
+            "pbm" => Some(ImageFormat::Pbm),
+            "pgm" => Some(ImageFormat::Pgm),
+            "png" => Some(ImageFormat::Png),
+            _ => None,
+        }
+    }
+
+    // The format a file's extension asks for.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<ImageFormat> {
+        ImageFormat::from_name(path.as_ref().extension()?.to_str()?)
+    }
+}
+
+// The display scaled up by a whole number, up to `MAX_SCALE`, one shaded
+// color per pixel, row by row.
+pub(crate) fn render(framebuffer: &Framebuffer<'_>, scale: u32) -> (usize, usize, Vec<u8>) {
+    let scale = scale.clamp(1, MAX_SCALE) as usize;
+    let (width, height) = (framebuffer.width * scale, framebuffer.height * scale);
+
+    (width, height, render_to(framebuffer, width, height))
+}
+
+// The display stretched to `width` by `height` image pixels, for when the
+// image size was fixed before the resolution last changed.
+pub(crate) fn render_to(framebuffer: &Framebuffer<'_>, width: usize, height: usize) -> Vec<u8> {
+    debug_assert!(width * height <= MAX_IMAGE_PIXELS);
+
+    let mut pixels = Vec::with_capacity(width * height);
+
+    for y in 0..height {
+        for x in 0..width {
+            let color = framebuffer.pixel(
+                x * framebuffer.width / width,
+                y * framebuffer.height / height,
+            );
+
+            pixels.push(color.min(SHADED_COLORS as u8 - 1));
+        }
+    }
+
+    pixels
+}
+
+// The colors an image needs: shaded ones only if it has fading pixels.
+pub(crate) fn image_colors(palette: &Palette, shaded: bool) -> Vec<u8> {
+    if shaded {
+        palette.shaded().concat()
+    } else {
+        palette.colors.concat()
+    }
+}
+
+// Writes the display as an image, with every CHIP-8 pixel drawn as a
+// `scale` by `scale` square.
+pub fn write_image<W: Write>(
+    framebuffer: &Framebuffer<'_>,
+    mut output: W,
+    format: ImageFormat,
+    scale: u32,
+    palette: &Palette,
+) -> io::Result<()> {
+    let (width, height, pixels) = render(framebuffer, scale);
+
+    match format {
+        ImageFormat::Pbm => {
+            let background = luma(palette.colors[0]);
+
+            write!(output, "P4\n{} {}\n", width, height)?;
+
+            // 1 is black, and each row is padded out to a whole byte
+            for row in pixels.chunks(width) {
+                let bits: Vec<u8> = row
+                    .chunks(8)
+                    .map(|byte| {
+                        byte.iter().enumerate().fold(0, |bits, (i, color)| {
+                            let black = is_lit(*color) != (background < 128);
+
+                            bits | (black as u8) << (7 - i)
+                        })
+                    })
+                    .collect();
+
+                output.write_all(&bits)?;
+            }
+        }
+        ImageFormat::Pgm => {
+            write!(output, "P5\n{} {}\n255\n", width, height)?;
+
+            let colors = palette.shaded();
+            let grays: Vec<u8> = pixels
+                .iter()
+                .map(|color| luma(colors[*color as usize]))
+                .collect();
+
+            output.write_all(&grays)?;
+        }
+        ImageFormat::Png => {
+            let mut encoder = png::Encoder::new(&mut output, width as u32, height as u32);
+
+            encoder.set_color(png::ColorType::Indexed);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.set_palette(image_colors(
+                palette,
+                pixels.iter().any(|color| fading(*color).is_some()),
+            ));
+
+            let mut writer = encoder.write_header()?;
+
+            writer.write_image_data(&pixels)?;
+            writer.finish()?;
+        }
+    }
+
+    output.flush()
+}
+
+// Saves a screenshot, in the format the file's extension asks for.
+pub fn save_screenshot<P: AsRef<Path>>(
+    framebuffer: &Framebuffer<'_>,
+    path: P,
+    scale: u32,
+    palette: &Palette,
+) -> io::Result<()> {
+    let format = ImageFormat::from_path(&path).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "unknown image format; use {}",
+                ImageFormat::NAMES.join(", ")
+            ),
+        )
+    })?;
+    let file = BufWriter::new(File::create(path)?);
+
+    write_image(framebuffer, file, format, scale, palette)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, write_image, ImageFormat, Palette, MAX_IMAGE_PIXELS, MAX_SCALE};
+    use crate::persistence::{shade, SHADES};
+    use crate::{Chip8, Framebuffer, Quirks, Region, HIRES_GRAPHICS_COLUMNS, HIRES_GRAPHICS_ROWS};
+
+    // a 64x32 display with the top left pixel and the one to its right lit
+    fn chip8() -> Chip8 {
+        let mut chip8 = Chip8::new(Quirks::default());
+
+        chip8.gfx[0] = 1;
+        chip8.gfx[1] = 1;
+        chip8
+    }
+
+    fn image(format: ImageFormat, scale: u32, palette: &Palette) -> Vec<u8> {
+        let mut output = vec![];
+
+        write_image(&chip8().framebuffer(), &mut output, format, scale, palette).unwrap();
+        output
+    }
+
+    #[test]
+    fn test_scale_budget() {
+        let scale = MAX_SCALE as usize;
+        let (width, height) = (HIRES_GRAPHICS_COLUMNS * scale, HIRES_GRAPHICS_ROWS * scale);
+
+        assert!(width * height <= MAX_IMAGE_PIXELS);
+        assert!(width <= u16::MAX as usize && height <= u16::MAX as usize);
+
+        // bigger scales are cut down to the limit
+        let (width, height, pixels) = render(&chip8().framebuffer(), u32::MAX);
+
+        assert_eq!((width, height), (64 * scale, 32 * scale));
+        assert_eq!(pixels.len(), width * height);
+    }
+
+    #[test]
+    fn test_palettes() {
+        assert_eq!(Palette::parse("default"), Some(Palette::default()));
+        assert_eq!(
+            Palette::parse("#102030, 405060").unwrap().colors,
+            [
+                [0x10, 0x20, 0x30],
+                [0x40, 0x50, 0x60],
+                [0x00, 0xFF, 0xFF],
+                [0xFF, 0xFF, 0x00]
+            ]
+        );
+
+        for text in ["", "102030", "1020", "102030,zz0000", "1,2,3,4,5"].iter() {
+            assert_eq!(Palette::parse(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_pbm_and_pgm() {
+        let pbm = image(ImageFormat::Pbm, 2, &Palette::default());
+        let header = b"P4\n128 64\n";
+
+        // white on black, so lit pixels are 0 and the background is 1
+        assert_eq!(&pbm[..header.len()], header);
+        assert_eq!(pbm.len(), header.len() + 16 * 64);
+        assert_eq!(&pbm[header.len()..header.len() + 2], &[0x0F, 0xFF]);
+
+        let pgm = image(ImageFormat::Pgm, 1, &Palette::from_name("lcd").unwrap());
+        let header = b"P5\n64 32\n255\n";
+
+        assert_eq!(&pgm[..header.len()], header);
+        assert_eq!(&pgm[header.len()..header.len() + 3], &[39, 39, 158]);
+    }
+
+    #[test]
+    fn test_png() {
+        let png = image(ImageFormat::Png, 3, &Palette::default());
+        let mut decoder = png::Decoder::new(&png[..]);
+
+        decoder.set_transformations(png::Transformations::EXPAND);
+
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+
+        assert_eq!((info.width, info.height), (192, 96));
+        assert_eq!(info.color_type, png::ColorType::Rgb);
+        // the sixth pixel along is still lit, the seventh isn't
+        assert_eq!(&pixels[15..21], &[0xFF, 0xFF, 0xFF, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_shaded_pixels() {
+        let shaded = Palette::default().shaded();
+
+        assert_eq!(shaded.len(), 16);
+        assert_eq!(shaded[shade(1, SHADES) as usize], [204, 204, 204]);
+        assert_eq!(shaded[shade(3, 1) as usize], [51, 51, 0]);
+
+        // a lit pixel, then the same one fading: bright, then faint
+        let pixels = [1, shade(1, SHADES), shade(1, 1), 0];
+        let framebuffer = Framebuffer {
+            width: 4,
+            height: 1,
+            pixels: &pixels,
+            dirty: Region::default(),
+        };
+        let mut pgm = vec![];
+        let mut pbm = vec![];
+
+        write_image(
+            &framebuffer,
+            &mut pgm,
+            ImageFormat::Pgm,
+            1,
+            &Palette::default(),
+        )
+        .unwrap();
+        write_image(
+            &framebuffer,
+            &mut pbm,
+            ImageFormat::Pbm,
+            1,
+            &Palette::default(),
+        )
+        .unwrap();
+
+        assert_eq!(&pgm[pgm.len() - 4..], &[255, 204, 51, 0]);
+        // only the brighter half of the shades count as lit
+        assert_eq!(pbm.last(), Some(&0b0011_0000));
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(ImageFormat::from_path("pong.PNG"), Some(ImageFormat::Png));
+        assert_eq!(ImageFormat::from_path("pong.pbm"), Some(ImageFormat::Pbm));
+        assert_eq!(ImageFormat::from_path("pong"), None);
+    }
+}
@@ -0,0 +1,299 @@
+use crate::errors::DecodeError;
+
+// A single decoded CHIP-8 instruction. `x` and `y` are register numbers
+// (0-F), `nn` is an 8-bit immediate and `nnn` a 12-bit address.
+//
+// Decoding doesn't depend on the machine state: the XO-CHIP only
+// instructions always decode, and it's up to `Chip8::execute` to reject
+// them when XO-CHIP mode is off. Likewise BNNN always decodes to
+// `JumpOffset`, and the `jump_uses_vx` quirk decides at execution time
+// whether the offset comes from V0 or VX.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    Sys { nnn: u16 },             // 0NNN
+    ClearScreen,                  // 00E0
+    Return,                       // 00EE
+    ScrollDown { n: u8 },         // 00CN (SUPER-CHIP)
+    ScrollRight,                  // 00FB (SUPER-CHIP)
+    ScrollLeft,                   // 00FC (SUPER-CHIP)
+    Exit,                         // 00FD (SUPER-CHIP)
+    LoRes,                        // 00FE (SUPER-CHIP)
+    HiRes,                        // 00FF (SUPER-CHIP)
+    Jump { nnn: u16 },            // 1NNN
+    Call { nnn: u16 },            // 2NNN
+    SkipEqImm { x: u8, nn: u8 },  // 3XNN
+    SkipNeImm { x: u8, nn: u8 },  // 4XNN
+    SkipEqReg { x: u8, y: u8 },   // 5XY0
+    SaveRange { x: u8, y: u8 },   // 5XY2 (XO-CHIP)
+    LoadRange { x: u8, y: u8 },   // 5XY3 (XO-CHIP)
+    SetImm { x: u8, nn: u8 },     // 6XNN
+    AddImm { x: u8, nn: u8 },     // 7XNN
+    Move { x: u8, y: u8 },        // 8XY0
+    Or { x: u8, y: u8 },          // 8XY1
+    And { x: u8, y: u8 },         // 8XY2
+    Xor { x: u8, y: u8 },         // 8XY3
+    Add { x: u8, y: u8 },         // 8XY4
+    Sub { x: u8, y: u8 },         // 8XY5
+    ShiftRight { x: u8, y: u8 },  // 8XY6
+    SubReverse { x: u8, y: u8 },  // 8XY7
+    ShiftLeft { x: u8, y: u8 },   // 8XYE
+    SkipNeReg { x: u8, y: u8 },   // 9XY0
+    SetI { nnn: u16 },            // ANNN
+    JumpOffset { nnn: u16 },      // BNNN
+    Random { x: u8, nn: u8 },     // CXNN
+    Draw { x: u8, y: u8, n: u8 }, // DXYN
+    SkipKeyDown { x: u8 },        // EX9E
+    SkipKeyUp { x: u8 },          // EXA1
+    SetLongI,                     // F000 NNNN (XO-CHIP)
+    SelectPlanes { n: u8 },       // FN01 (XO-CHIP)
+    LoadAudio,                    // F002 (XO-CHIP)
+    GetDelay { x: u8 },           // FX07
+    WaitKey { x: u8 },            // FX0A
+    SetDelay { x: u8 },           // FX15
+    SetSound { x: u8 },           // FX18
+    AddI { x: u8 },               // FX1E
+    Font { x: u8 },               // FX29
+    BigFont { x: u8 },            // FX30 (SUPER-CHIP)
+    Bcd { x: u8 },                // FX33
+    SetPitch { x: u8 },           // FX3A (XO-CHIP)
+    Store { x: u8 },              // FX55
+    Load { x: u8 },               // FX65
+    SaveFlags { x: u8 },          // FX75 (SUPER-CHIP)
+    LoadFlags { x: u8 },          // FX85 (SUPER-CHIP)
+}
+
+impl Instruction {
+    pub fn decode(opcode: u16) -> Result<Instruction, DecodeError> {
+        let x = ((opcode & 0x0F00) >> 8) as u8;
+        let y = ((opcode & 0x00F0) >> 4) as u8;
+        let nnn = opcode & 0x0FFF;
+        let nn = (opcode & 0x00FF) as u8;
+        let n = (opcode & 0x000F) as u8;
+
+        let instruction = match opcode & 0xF000 {
+            0x0000 => match opcode {
+                0x00E0 => Instruction::ClearScreen,
+                0x00EE => Instruction::Return,
+                0x00C0..=0x00CF => Instruction::ScrollDown { n },
+                0x00FB => Instruction::ScrollRight,
+                0x00FC => Instruction::ScrollLeft,
+                0x00FD => Instruction::Exit,
+                0x00FE => Instruction::LoRes,
+                0x00FF => Instruction::HiRes,
+                _ => Instruction::Sys { nnn },
+            },
+            0x1000 => Instruction::Jump { nnn },
+            0x2000 => Instruction::Call { nnn },
+            0x3000 => Instruction::SkipEqImm { x, nn },
+            0x4000 => Instruction::SkipNeImm { x, nn },
+            0x5000 => match n {
+                0x0 => Instruction::SkipEqReg { x, y },
+                0x2 => Instruction::SaveRange { x, y },
+                0x3 => Instruction::LoadRange { x, y },
+                _ => return Err(DecodeError { opcode }),
+            },
+            0x6000 => Instruction::SetImm { x, nn },
+            0x7000 => Instruction::AddImm { x, nn },
+            0x8000 => match n {
+                0x0 => Instruction::Move { x, y },
+                0x1 => Instruction::Or { x, y },
+                0x2 => Instruction::And { x, y },
+                0x3 => Instruction::Xor { x, y },
+                0x4 => Instruction::Add { x, y },
+                0x5 => Instruction::Sub { x, y },
+                0x6 => Instruction::ShiftRight { x, y },
+                0x7 => Instruction::SubReverse { x, y },
+                0xE => Instruction::ShiftLeft { x, y },
+                _ => return Err(DecodeError { opcode }),
+            },
+            0x9000 if n == 0 => Instruction::SkipNeReg { x, y },
+            0xA000 => Instruction::SetI { nnn },
+            0xB000 => Instruction::JumpOffset { nnn },
+            0xC000 => Instruction::Random { x, nn },
+            0xD000 => Instruction::Draw { x, y, n },
+            0xE000 => match nn {
+                0x9E => Instruction::SkipKeyDown { x },
+                0xA1 => Instruction::SkipKeyUp { x },
+                _ => return Err(DecodeError { opcode }),
+            },
+            0xF000 => match nn {
+                0x00 if x == 0 => Instruction::SetLongI,
+                0x01 => Instruction::SelectPlanes { n: x },
+                0x02 if x == 0 => Instruction::LoadAudio,
+                0x07 => Instruction::GetDelay { x },
+                0x0A => Instruction::WaitKey { x },
+                0x15 => Instruction::SetDelay { x },
+                0x18 => Instruction::SetSound { x },
+                0x1E => Instruction::AddI { x },
+                0x29 => Instruction::Font { x },
+                0x30 => Instruction::BigFont { x },
+                0x33 => Instruction::Bcd { x },
+                0x3A => Instruction::SetPitch { x },
+                0x55 => Instruction::Store { x },
+                0x65 => Instruction::Load { x },
+                0x75 => Instruction::SaveFlags { x },
+                0x85 => Instruction::LoadFlags { x },
+                _ => return Err(DecodeError { opcode }),
+            },
+            _ => return Err(DecodeError { opcode }),
+        };
+
+        Ok(instruction)
+    }
+
+    // Encodes the instruction back into its opcode. For `SetLongI` this is
+    // just the leading F000 word; the address lives in the word after it.
+    // Fields too big for their nibbles are cut down to fit.
+    pub fn opcode(&self) -> u16 {
+        let nibble = |n: u8| (n & 0xF) as u16;
+        let xy = |base: u16, x: u8, y: u8| base | nibble(x) << 8 | nibble(y) << 4;
+        let xnn = |base: u16, x: u8, nn: u8| base | nibble(x) << 8 | nn as u16;
+        let fx = |x: u8, low: u16| 0xF000 | nibble(x) << 8 | low;
+
+        match *self {
+            Instruction::Sys { nnn } => nnn & 0x0FFF,
+            Instruction::ClearScreen => 0x00E0,
+            Instruction::Return => 0x00EE,
+            Instruction::ScrollDown { n } => 0x00C0 | (n & 0xF) as u16,
+            Instruction::ScrollRight => 0x00FB,
+            Instruction::ScrollLeft => 0x00FC,
+            Instruction::Exit => 0x00FD,
+            Instruction::LoRes => 0x00FE,
+            Instruction::HiRes => 0x00FF,
+            Instruction::Jump { nnn } => 0x1000 | (nnn & 0x0FFF),
+            Instruction::Call { nnn } => 0x2000 | (nnn & 0x0FFF),
+            Instruction::SkipEqImm { x, nn } => xnn(0x3000, x, nn),
+            Instruction::SkipNeImm { x, nn } => xnn(0x4000, x, nn),
+            Instruction::SkipEqReg { x, y } => xy(0x5000, x, y),
+            Instruction::SaveRange { x, y } => xy(0x5002, x, y),
+            Instruction::LoadRange { x, y } => xy(0x5003, x, y),
+            Instruction::SetImm { x, nn } => xnn(0x6000, x, nn),
+            Instruction::AddImm { x, nn } => xnn(0x7000, x, nn),
+            Instruction::Move { x, y } => xy(0x8000, x, y),
+            Instruction::Or { x, y } => xy(0x8001, x, y),
+            Instruction::And { x, y } => xy(0x8002, x, y),
+            Instruction::Xor { x, y } => xy(0x8003, x, y),
+            Instruction::Add { x, y } => xy(0x8004, x, y),
+            Instruction::Sub { x, y } => xy(0x8005, x, y),
+            Instruction::ShiftRight { x, y } => xy(0x8006, x, y),
+            Instruction::SubReverse { x, y } => xy(0x8007, x, y),
+            Instruction::ShiftLeft { x, y } => xy(0x800E, x, y),
+            Instruction::SkipNeReg { x, y } => xy(0x9000, x, y),
+            Instruction::SetI { nnn } => 0xA000 | (nnn & 0x0FFF),
+            Instruction::JumpOffset { nnn } => 0xB000 | (nnn & 0x0FFF),
+            Instruction::Random { x, nn } => xnn(0xC000, x, nn),
+            Instruction::Draw { x, y, n } => xy(0xD000, x, y) | (n & 0xF) as u16,
+            Instruction::SkipKeyDown { x } => xnn(0xE000, x, 0x9E),
+            Instruction::SkipKeyUp { x } => xnn(0xE000, x, 0xA1),
+            Instruction::SetLongI => 0xF000,
+            Instruction::SelectPlanes { n } => fx(n, 0x01),
+            Instruction::LoadAudio => 0xF002,
+            Instruction::GetDelay { x } => fx(x, 0x07),
+            Instruction::WaitKey { x } => fx(x, 0x0A),
+            Instruction::SetDelay { x } => fx(x, 0x15),
+            Instruction::SetSound { x } => fx(x, 0x18),
+            Instruction::AddI { x } => fx(x, 0x1E),
+            Instruction::Font { x } => fx(x, 0x29),
+            Instruction::BigFont { x } => fx(x, 0x30),
+            Instruction::Bcd { x } => fx(x, 0x33),
+            Instruction::SetPitch { x } => fx(x, 0x3A),
+            Instruction::Store { x } => fx(x, 0x55),
+            Instruction::Load { x } => fx(x, 0x65),
+            Instruction::SaveFlags { x } => fx(x, 0x75),
+            Instruction::LoadFlags { x } => fx(x, 0x85),
+        }
+    }
+
+    // Whether every field fits in its opcode, as it does for any instruction
+    // `decode` returns. Instructions built by hand might not.
+    pub fn is_valid(&self) -> bool {
+        matches!(Instruction::decode(self.opcode()), Ok(decoded) if decoded == *self)
+    }
+
+    // Size of the instruction in bytes, including any operand words.
+    pub fn size(&self) -> u16 {
+        match *self {
+            Instruction::SetLongI => 4,
+            _ => 2,
+        }
+    }
+
+    pub fn is_xo_chip(&self) -> bool {
+        matches!(
+            *self,
+            Instruction::SaveRange { .. }
+                | Instruction::LoadRange { .. }
+                | Instruction::SetLongI
+                | Instruction::SelectPlanes { .. }
+                | Instruction::LoadAudio
+                | Instruction::SetPitch { .. }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Instruction;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(
+            Instruction::decode(0x00E0).unwrap(),
+            Instruction::ClearScreen
+        );
+        assert_eq!(
+            Instruction::decode(0x0123).unwrap(),
+            Instruction::Sys { nnn: 0x123 }
+        );
+        assert_eq!(
+            Instruction::decode(0x00C7).unwrap(),
+            Instruction::ScrollDown { n: 7 }
+        );
+        assert_eq!(
+            Instruction::decode(0x8AB6).unwrap(),
+            Instruction::ShiftRight { x: 0xA, y: 0xB }
+        );
+        assert_eq!(
+            Instruction::decode(0xD12F).unwrap(),
+            Instruction::Draw { x: 1, y: 2, n: 0xF }
+        );
+        assert_eq!(
+            Instruction::decode(0xF301).unwrap(),
+            Instruction::SelectPlanes { n: 3 }
+        );
+        assert_eq!(Instruction::decode(0xF000).unwrap(), Instruction::SetLongI);
+    }
+
+    #[test]
+    fn test_decode_unknown() {
+        for opcode in [0x5121, 0x8008, 0x9001, 0xE09F, 0xF1FF, 0xF100, 0xF202].iter() {
+            let err = Instruction::decode(*opcode).unwrap_err();
+
+            assert_eq!(err.opcode, *opcode);
+        }
+    }
+
+    #[test]
+    fn test_opcode_round_trip() {
+        for opcode in 0..=0xFFFFu16 {
+            if let Ok(instruction) = Instruction::decode(opcode) {
+                assert_eq!(instruction.opcode(), opcode, "{:?}", instruction);
+            }
+        }
+    }
+
+    #[test]
+    fn test_is_valid() {
+        assert!(Instruction::SetImm { x: 0xF, nn: 1 }.is_valid());
+        assert!(!Instruction::SetImm { x: 20, nn: 1 }.is_valid());
+        assert!(!Instruction::Move { x: 1, y: 0x10 }.is_valid());
+        assert!(!Instruction::Draw { x: 1, y: 2, n: 16 }.is_valid());
+        assert!(!Instruction::Jump { nnn: 0x1234 }.is_valid());
+    }
+
+    #[test]
+    fn test_size() {
+        assert_eq!(Instruction::SetLongI.size(), 4);
+        assert_eq!(Instruction::ClearScreen.size(), 2);
+    }
+}
@@ -0,0 +1,128 @@
+use crate::Chip8;
+
+const KEYS: usize = 16;
+
+// Tracks the hex keypad on behalf of a frontend and forwards presses and
+// releases to a `Chip8`.
+//
+// Most terminals only report key presses, never releases. For those, the
+// keypad can synthesize a release once a key has gone `release_after`
+// frames without being pressed again; the terminal's auto-repeat keeps a
+// held key pressed. Frontends that do see real releases pass `None` and
+// call `release` themselves.
+pub struct Keypad {
+    release_after: Option<u32>,
+    frames_left: [Option<u32>; KEYS],
+}
+
+impl Keypad {
+    pub fn new(release_after: Option<u32>) -> Self {
+        Keypad {
+            release_after,
+            frames_left: [None; KEYS],
+        }
+    }
+
+    pub fn press(&mut self, chip8: &mut Chip8, key: u8) {
+        if let Some(left) = self.frames_left.get_mut(key as usize) {
+            *left = Some(self.release_after.unwrap_or(0));
+            chip8.key_down(key);
+        }
+    }
+
+    pub fn release(&mut self, chip8: &mut Chip8, key: u8) {
+        if let Some(left) = self.frames_left.get_mut(key as usize) {
+            *left = None;
+            chip8.key_up(key);
+        }
+    }
+
+    pub fn is_pressed(&self, key: u8) -> bool {
+        matches!(self.frames_left.get(key as usize), Some(Some(_)))
+    }
+
+    // Call once per frame; releases any key whose synthetic hold has run out.
+    pub fn tick(&mut self, chip8: &mut Chip8) {
+        if self.release_after.is_none() {
+            return;
+        }
+
+        for key in 0..KEYS {
+            match self.frames_left[key] {
+                Some(0) | Some(1) => self.release(chip8, key as u8),
+                Some(left) => self.frames_left[key] = Some(left - 1),
+                None => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Chip8, Keypad};
+
+    #[test]
+    fn test_press_and_release() {
+        let mut chip8 = Chip8::default();
+        let mut keypad = Keypad::new(None);
+
+        keypad.press(&mut chip8, 0xA);
+
+        assert!(keypad.is_pressed(0xA));
+        assert!(chip8.is_key_down(0xA));
+
+        // without synthetic releases, ticking never lets go of a key
+        keypad.tick(&mut chip8);
+
+        assert!(chip8.is_key_down(0xA));
+
+        keypad.release(&mut chip8, 0xA);
+
+        assert!(!keypad.is_pressed(0xA));
+        assert!(!chip8.is_key_down(0xA));
+    }
+
+    #[test]
+    fn test_synthetic_release() {
+        let mut chip8 = Chip8::default();
+        let mut keypad = Keypad::new(Some(3));
+
+        keypad.press(&mut chip8, 5);
+
+        keypad.tick(&mut chip8);
+        keypad.tick(&mut chip8);
+
+        assert!(chip8.is_key_down(5));
+
+        keypad.tick(&mut chip8);
+
+        assert!(!chip8.is_key_down(5));
+    }
+
+    #[test]
+    fn test_repeated_press_extends_hold() {
+        let mut chip8 = Chip8::default();
+        let mut keypad = Keypad::new(Some(2));
+
+        keypad.press(&mut chip8, 1);
+        keypad.tick(&mut chip8);
+        keypad.press(&mut chip8, 1);
+        keypad.tick(&mut chip8);
+
+        assert!(chip8.is_key_down(1));
+
+        keypad.tick(&mut chip8);
+
+        assert!(!chip8.is_key_down(1));
+    }
+
+    #[test]
+    fn test_out_of_range_keys_are_ignored() {
+        let mut chip8 = Chip8::default();
+        let mut keypad = Keypad::new(Some(2));
+
+        keypad.press(&mut chip8, 0x10);
+
+        assert!(!keypad.is_pressed(0x10));
+    }
+}
@@ -1,61 +1,190 @@
 use std::error::Error;
-
-use errors::ProgramTooLargeError;
-
+use std::fmt;
+
+pub use asm::assemble;
+pub use dap::{serve_dap, DapSession};
+pub use debugger::{Debugger, Location, Register, Stop, WatchKind, WatchTarget, Watchpoint};
+pub use disasm::{disassemble, mnemonic, Syntax};
+pub use errors::{
+    AsmError, Chip8Fault, CommandError, DecodeError, DesyncError, ProgramTooLargeError,
+    ScriptError, StateError,
+};
+pub use frontend::{Framebuffer, Frontend, Hotkey, InputEvent, Io, NullFrontend, Region};
+pub use gdb::GdbStub;
+pub use headless::{Headless, KeyEvent, KeyScript};
+pub use image::{save_screenshot, write_image, ImageFormat, Palette, MAX_SCALE};
+pub use instruction::Instruction;
+pub use keypad::Keypad;
+pub use movie::{rom_hash, screen_hash, Movie, MovieRecorder};
+pub use persistence::{
+    fading, is_lit, Persistence, Phosphor, MAX_FADE_FRAMES, SHADED_COLORS, SHADES,
+};
+pub use quirks::Quirks;
+pub use random::{RandomSource, ScriptedRandom, SeededRandom};
+pub use record::GifRecorder;
+pub use renderer::{Cell, CellGrid, Renderer};
+pub use rewind::{RewindBuffer, DEFAULT_REWIND_FRAMES};
+pub use trace::{TraceFormat, Tracer};
+
+mod asm;
+mod condition;
+mod dap;
+mod debugger;
+mod disasm;
 mod errors;
+mod frontend;
+mod gdb;
+mod headless;
+mod image;
+mod instruction;
+mod keypad;
+mod movie;
+mod persistence;
+mod quirks;
+mod random;
+mod record;
+mod renderer;
+mod rewind;
+mod state;
+mod trace;
 
 const MEMORY_SIZE: usize = 4096;
+const XO_CHIP_MEMORY_SIZE: usize = 65536;
 const LOWER_MEMORY_BOUNDARY: usize = 512;
 const GRAPHICS_COLUMNS: usize = 64;
 const GRAPHICS_ROWS: usize = 32;
-const GRAPHICS_ARRAY_SIZE: usize = GRAPHICS_COLUMNS * GRAPHICS_ROWS;
+const HIRES_GRAPHICS_COLUMNS: usize = 128;
+const HIRES_GRAPHICS_ROWS: usize = 64;
+const GRAPHICS_ARRAY_SIZE: usize = HIRES_GRAPHICS_COLUMNS * HIRES_GRAPHICS_ROWS;
 const STACK_SIZE: usize = 16;
 const KEYBOARD_ARRAY_SIZE: usize = 16;
 const REGISTERS: usize = 16;
+const RPL_FLAGS: usize = 16;
+const BIG_FONT_ADDRESS: usize = 0x100;
+const SCROLL_PIXELS: usize = 4;
+const AUDIO_PATTERN_SIZE: usize = 16;
+const DEFAULT_PITCH: u8 = 64;
+const ALL_PLANES: u8 = 0b11;
 
 // 0x000-0x1FF - Chip 8 interpreter (contains font set in emu)
-// 0x050-0x0A0 - Used for the built in 4x5 pixel font set (0-F)
-// 0x200-0xFFF - Program ROM and work RAM
+// 0x000-0x04F - Used for the built in 4x5 pixel font set (0-F)
+// 0x100-0x19F - Used for the SUPER-CHIP 8x10 pixel font set (0-F)
+// 0x200-0xFFF - Program ROM and work RAM (up to 0xFFFF in XO-CHIP mode)
+//
+// `gfx` is always big enough for the 128x64 SUPER-CHIP hi-res mode; in
+// lo-res mode only the first 64x32 pixels are used, 64 to a row. Each
+// pixel holds one bit per XO-CHIP display plane, so it ranges from 0 to 3.
 pub struct Chip8 {
-    memory: [u8; MEMORY_SIZE],          // program memory
-    v: [u8; REGISTERS],                 // registers
-    i: u16,                             // index register
-    pc: u16,                            // program counter
-    pub gfx: [u8; GRAPHICS_ARRAY_SIZE], // graphics display
-    delay_timer: u8,                    // delay timer
-    pub sound_timer: u8,                // sound timer
-    stack: [u16; STACK_SIZE],           // program stack
-    sp: u8,                             // stack pointer
-    pub key: [u8; KEYBOARD_ARRAY_SIZE], // keyboard
-    pub draw_flag: bool,                // drawing flag
+    memory: Vec<u8>,                         // program memory
+    v: [u8; REGISTERS],                      // registers
+    i: u16,                                  // index register
+    pc: u16,                                 // program counter
+    pub gfx: [u8; GRAPHICS_ARRAY_SIZE],      // graphics display
+    delay_timer: u8,                         // delay timer
+    pub sound_timer: u8,                     // sound timer
+    stack: [u16; STACK_SIZE],                // program stack
+    sp: u8,                                  // stack pointer
+    key: [u8; KEYBOARD_ARRAY_SIZE],          // keyboard, 1 while a key is held down
+    pub draw_flag: bool,                     // drawing flag
+    dirty: Option<Region>,                   // pixels changed since the last `take_dirty_region`
+    quirks: Quirks,                          // interpreter quirks
+    hires: bool,                             // SUPER-CHIP 128x64 mode
+    rpl: [u8; RPL_FLAGS],                    // SUPER-CHIP RPL user flags
+    xo_chip: bool,                           // XO-CHIP extensions enabled
+    plane: u8,                               // XO-CHIP selected display planes
+    audio_pattern: [u8; AUDIO_PATTERN_SIZE], // XO-CHIP audio pattern buffer
+    pitch: u8,                               // XO-CHIP audio pitch register
+    awaited_key: Option<u8>,                 // key pressed during FX0A, waiting for release
+    rng: Box<dyn RandomSource>,              // random numbers for CXNN
+    track_accesses: bool,                    // record memory accesses for watchpoints
+    accesses: Vec<MemoryAccess>,             // memory accessed by the last instruction
+    cycles: u64,                             // instructions executed so far
+    tracer: Option<Tracer>,                  // execution trace
+}
+
+// What happened when `execute_cycle` ran a single instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    // The instruction ran normally.
+    Executed,
+    // FX0A is still waiting for a key; the same instruction runs again next cycle.
+    WaitingForKey,
+    // 00FD asked the interpreter to exit.
+    Exited,
+}
+
+// Whether an instruction read or wrote a memory location or register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+// A byte of memory an instruction accessed as data. Fetching the
+// instruction itself doesn't count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryAccess {
+    pub address: usize,
+    pub access: Access,
+}
+
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self::new(Quirks::default())
+    }
 }
 
 impl Chip8 {
-    pub fn new() -> Self {
+    pub fn new(quirks: Quirks) -> Self {
+        Self::with_memory(quirks, MEMORY_SIZE, false)
+    }
+
+    // Opts in to the XO-CHIP extensions: 64 KiB of memory, two display
+    // planes and the audio pattern buffer. The pattern and pitch are only
+    // stored; it's up to a frontend to play them.
+    pub fn new_xo_chip(quirks: Quirks) -> Self {
+        Self::with_memory(quirks, XO_CHIP_MEMORY_SIZE, true)
+    }
+
+    fn with_memory(quirks: Quirks, memory_size: usize, xo_chip: bool) -> Self {
         let mut chip8 = Chip8 {
-            memory: [0; MEMORY_SIZE],
+            memory: vec![0; memory_size],
             v: [0; REGISTERS],
             i: 0,
             pc: 0x200,
-            gfx: [0; GRAPHICS_COLUMNS * GRAPHICS_ROWS],
+            gfx: [0; GRAPHICS_ARRAY_SIZE],
             delay_timer: 0,
             sound_timer: 0,
             stack: [0; STACK_SIZE],
             sp: 0,
             key: [0; KEYBOARD_ARRAY_SIZE],
             draw_flag: false,
+            dirty: None,
+            quirks,
+            hires: false,
+            rpl: [0; RPL_FLAGS],
+            xo_chip,
+            plane: 1,
+            audio_pattern: [0; AUDIO_PATTERN_SIZE],
+            pitch: DEFAULT_PITCH,
+            awaited_key: None,
+            rng: Box::new(SeededRandom::from_entropy()),
+            track_accesses: false,
+            accesses: vec![],
+            cycles: 0,
+            tracer: None,
         };
 
-        // Load fontset
-        for i in 0..79 {
-            chip8.memory[i] = CHIP8_FONTSET[i];
-        }
+        // Load fontsets
+        chip8.memory[..CHIP8_FONTSET.len()].copy_from_slice(&CHIP8_FONTSET);
+        chip8.memory[BIG_FONT_ADDRESS..BIG_FONT_ADDRESS + SCHIP_BIG_FONTSET.len()]
+            .copy_from_slice(&SCHIP_BIG_FONTSET);
 
         chip8
     }
 
     pub fn load_program(&mut self, program: Vec<u8>) -> Result<(), Box<dyn Error>> {
-        if program.len() + LOWER_MEMORY_BOUNDARY > MEMORY_SIZE {
+        if program.len() + LOWER_MEMORY_BOUNDARY > self.memory.len() {
             return Err(Box::new(ProgramTooLargeError));
         }
 
@@ -66,1316 +195,2732 @@ impl Chip8 {
         Ok(())
     }
 
-    pub fn execute_cycle(&mut self) {
-        let opcode = read_word(self.memory, self.pc);
+    // Executes a single instruction. The delay and sound timers are left
+    // alone; they count down at 60 Hz through `tick_timers`, however fast
+    // instructions are being executed.
+    pub fn execute_cycle(&mut self) -> Result<StepOutcome, Chip8Fault> {
+        if let Some(mut tracer) = self.tracer.take() {
+            tracer.trace(self.cycles, self);
+            self.tracer = Some(tracer);
+        }
+
+        self.cycles += 1;
+
+        let instruction = self.fetch()?;
+
+        self.execute(instruction)
+    }
 
-        self.process_opcode(opcode);
+    // Decodes the instruction at the program counter without running it.
+    pub fn fetch(&self) -> Result<Instruction, Chip8Fault> {
+        let pc = self.pc;
+        let opcode = self.read_word(pc)?;
 
-        self.update_timers();
+        Instruction::decode(opcode).map_err(|_| Chip8Fault::UnknownOpcode { pc, opcode })
     }
 
-    fn update_timers(&mut self) {
+    // Decrements the delay and sound timers; call this 60 times a second.
+    pub fn tick_timers(&mut self) {
         if self.delay_timer > 0 {
             self.delay_timer -= 1;
         }
 
         if self.sound_timer > 0 {
-            if self.sound_timer == 1 {
-                // println!("BEEP!");
-            }
-
             self.sound_timer -= 1;
         }
     }
 
-    fn process_opcode(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let y = ((opcode & 0x00F0) >> 4) as usize;
-        let vx = self.v[x] as u16;
-        let vy = self.v[y] as u16;
-        let nnn = opcode & 0x0FFF;
-        let nn = (opcode & 0x00FF) as u8;
-        let n = (opcode & 0x000F) as u8;
-
-        match opcode & 0xF000 {
-            0x0000 => {
-                // two special opcodes that can't be determined by the
-                // top four bits
-                match opcode & 0x000F {
-                    0x0000 => {
-                        // 0x00E0; clear the screen
-                        for i in 0..GRAPHICS_ARRAY_SIZE {
-                            self.gfx[i as usize] = 0;
-                        }
-
-                        self.draw_flag = true;
-                        self.pc += 2;
-                    }
-                    0x000E => {
-                        // 0x00EE; returns from subroutine
-                        self.sp -= 1;
-                        self.pc = self.stack[self.sp as usize];
-                        self.pc += 2;
-                    }
-                    _ => {
-                        // 0x0NNN: Calls RCA 1802 program at address NNN. Not necessary for most ROMs.
-                        self.pc = nnn;
-                    }
-                }
-            }
-
-            0x1000 => {
-                // 0x1NNN: jumps to address NNN
-                self.pc = nnn;
-            }
+    // Runs one 60 Hz frame: up to `instructions_per_frame` instructions,
+    // followed by a timer tick. The frame ends early if the program exits or
+    // is waiting on FX0A, since nothing can change until new input arrives.
+    pub fn run_frame(&mut self, instructions_per_frame: u32) -> Result<StepOutcome, Chip8Fault> {
+        self.run_frame_within(instructions_per_frame, None)
+    }
 
-            0x2000 => {
-                // 0x2NNN: calls subroutine at NNN
-                self.stack[self.sp as usize] = self.pc;
-                self.sp += 1;
-                self.pc = nnn;
+    // Runs a frame like `run_frame`, but with no more than `budget`
+    // instructions, for runs capped at a total number of instructions. The
+    // timers still tick if the budget cuts the frame short.
+    pub fn run_frame_within(
+        &mut self,
+        instructions_per_frame: u32,
+        budget: Option<u64>,
+    ) -> Result<StepOutcome, Chip8Fault> {
+        let count = budget.map_or(instructions_per_frame as u64, |budget| {
+            budget.min(instructions_per_frame as u64)
+        });
+        let mut outcome = StepOutcome::Executed;
+
+        for _ in 0..count {
+            outcome = self.execute_cycle()?;
+
+            if outcome != StepOutcome::Executed {
+                break;
             }
+        }
 
-            0x3000 => {
-                // 0x3XNN: Skips the next instruction if VX equals NN. (Usually the next instruction is a jump to skip a code block)
-                if self.v[x] == nn {
-                    self.pc += 4;
-                } else {
-                    self.pc += 2;
-                }
-            }
+        if outcome != StepOutcome::Exited {
+            self.tick_timers();
+        }
 
-            0x4000 => {
-                // 0x4XNN: Skips the next instruction if VX doesn't equal NN. (Usually the next instruction is a jump to skip a code block)
-                if self.v[x] != nn {
-                    self.pc += 4;
-                } else {
-                    self.pc += 2;
-                }
-            }
+        Ok(outcome)
+    }
 
-            0x5000 => {
-                // 0x5XY0: Skips the next instruction if VX equals VY. (Usually the next instruction is a jump to skip a code block)
-                if self.v[x] == self.v[y] {
-                    self.pc += 4;
-                } else {
-                    self.pc += 2;
-                }
-            }
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
 
-            0x6000 => {
-                // 0x6XNN: Sets VX to NN.
-                self.v[x] = nn;
-                self.pc += 2;
-            }
+    pub fn i(&self) -> u16 {
+        self.i
+    }
 
-            0x7000 => {
-                // 0x7XNN: Adds NN to VX. (Carry flag is not changed)
-                self.v[x] = ((self.v[x] as u16 + nn as u16) & 0xff) as u8;
-                self.pc += 2;
-            }
+    pub fn sp(&self) -> u8 {
+        self.sp
+    }
 
-            0x8000 => {
-                match n {
-                    0x0 => {
-                        // 0x8XY0: Sets VX to the value of VY.
-                        self.v[x] = self.v[y];
-                        self.pc += 2;
-                    }
-                    0x1 => {
-                        // 0x8XY1: Sets VX to VX or VY. (Bitwise OR operation)
-                        self.v[x] |= self.v[y];
-                        self.pc += 2;
-                    }
-                    0x2 => {
-                        // 0x8XY2: Sets VX to VX and VY. (Bitwise AND operation)
-                        self.v[x] &= self.v[y];
-                        self.pc += 2;
-                    }
-                    0x3 => {
-                        // 0x8XY3: Sets VX to VX xor VY.
-                        self.v[x] ^= self.v[y];
-                        self.pc += 2;
-                    }
-                    0x4 => {
-                        // 0x8XY4: Adds VY to VX. VF is set to 1 when there's a carry, and to 0 when there isn't.
-                        if self.v[y] > (0xFF - self.v[x]) {
-                            self.v[0xF] = 1; // carry the 1
-                        } else {
-                            self.v[0xF] = 0;
-                        }
+    pub fn v(&self) -> &[u8; REGISTERS] {
+        &self.v
+    }
 
-                        self.v[x] = ((self.v[x] as u16 + self.v[y] as u16) & 0xff) as u8;
-                        self.pc += 2;
-                    }
-                    0x5 => {
-                        // 0x8XY5: VY is subtracted from VX. VF is set to 0 when there's a borrow, and 1 when there isn't.
-                        if self.v[y] > (self.v[x]) {
-                            self.v[0xF] = 0; // carry the 1
-                        } else {
-                            self.v[0xF] = 1;
-                        }
+    pub fn stack(&self) -> &[u16; STACK_SIZE] {
+        &self.stack
+    }
 
-                        let tx = self.v[x];
-                        let ty = self.v[y];
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
 
-                        let tz = if ty > tx {
-                            ((tx as i16 - ty as i16).abs() as u8) - 1
-                        } else {
-                            tx - ty
-                        };
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
 
-                        self.v[x] = tz;
-                        self.pc += 2;
-                    }
-                    0x6 => {
-                        // 0x8XY6: Stores the least significant bit of VX in VF and then shifts VX to the right by 1.
-                        self.v[0xF] = self.v[x] & 0x1;
-                        self.v[x] >>= 1;
-                        self.pc += 2;
-                    }
-                    0x7 => {
-                        // 0x8XY7: Sets VX to VY minus VX. VF is set to 0 when there's a borrow, and 1 when there isn't.
-                        if vx > vy {
-                            self.v[0xF] = 0;
-                        } else {
-                            self.v[0xF] = 1;
-                        }
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
 
-                        let tz = if vx > vy {
-                            ((vy as i16 - vx as i16).abs() as u8) - 1
-                        } else {
-                            (vy - vx) as u8
-                        };
+    // Setters for debuggers; the program itself only changes state through
+    // instructions.
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        &mut self.memory
+    }
 
-                        self.v[x] = tz;
-                        self.pc += 2;
-                    }
-                    0xE => {
-                        // 0x8XYE: Stores the most significant bit of VX in VF and then shifts VX to the left by 1.
-                        self.v[0xF] = self.v[x] >> 7;
-                        self.v[x] <<= 1;
-                        self.pc += 2;
-                    }
-                    _ => panic!("unknown 0x8000 opcode: {:#X?}", opcode),
-                }
-            }
+    pub fn set_v(&mut self, x: usize, value: u8) {
+        self.v[x] = value;
+    }
 
-            0x9000 => {
-                // 0x9XY0: Skips the next instruction if VX doesn't equal VY. (Usually the next instruction is a jump to skip a code block)
-                if self.v[x] != self.v[y] {
-                    self.pc += 4;
-                } else {
-                    self.pc += 2;
-                }
-            }
+    pub fn set_i(&mut self, i: u16) {
+        self.i = i;
+    }
 
-            0xA000 => {
-                // 0xANNN: sets I to the address NNN
-                self.i = nnn;
-                self.pc += 2;
-            }
+    pub fn set_pc(&mut self, pc: u16) {
+        self.pc = pc;
+    }
 
-            0xB000 => {
-                // 0xBNNN: Jumps to the address NNN plus V0.
-                self.pc = nnn + self.v[0] as u16;
-            }
+    pub fn set_sp(&mut self, sp: u8) {
+        self.sp = sp;
+    }
 
-            0xC000 => {
-                // 0xCXNN: Sets VX to the result of a bitwise and operation on a random number (Typically: 0 to 255) and NN.
-                let r: u8 = rand::random();
-                self.v[x] = r | nn;
-                self.pc += 2;
-            }
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.delay_timer = value;
+    }
 
-            0xD000 => {
-                // 0xDXYN: Draws a sprite at coordinate (VX, VY) that has a width of 8 pixels
-                // and a height of N pixels.
-                let height = n;
+    // Memory watchpoints need to know which bytes each instruction reads and
+    // writes. Recording that costs a little, so it's off unless asked for.
+    pub fn set_access_tracking(&mut self, enabled: bool) {
+        self.track_accesses = enabled;
+        self.accesses.clear();
+    }
 
-                self.v[0xF] = 0;
+    // the memory the last instruction executed read or wrote, in order; always
+    // empty unless access tracking is on
+    pub fn memory_accesses(&self) -> &[MemoryAccess] {
+        &self.accesses
+    }
 
-                for yline in 0..height {
-                    let pixel = self.memory[(self.i + yline as u16) as usize];
+    // the number of cycles `execute_cycle` has run, including ones spent
+    // waiting on FX0A
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
 
-                    for xline in 0..8 {
-                        if (pixel & (0x80 >> xline)) != 0 {
-                            let x_coord = (vx + xline as u16) % GRAPHICS_COLUMNS as u16;
-                            let y_coord = (vy + yline as u16) % GRAPHICS_ROWS as u16;
-                            let pixel_index =
-                                ((y_coord * GRAPHICS_COLUMNS as u16) + x_coord) as usize;
+    // Traces every cycle from now on, or stops tracing; returns the tracer
+    // that was in use, so that it can be finished.
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) -> Option<Tracer> {
+        std::mem::replace(&mut self.tracer, tracer)
+    }
 
-                            if self.gfx[pixel_index] == 0x01 {
-                                self.v[0xF] = 1;
-                            }
+    pub fn set_random_source(&mut self, rng: Box<dyn RandomSource>) {
+        self.rng = rng;
+    }
 
-                            self.gfx[pixel_index] ^= 0x01;
-                        }
-                    }
-                }
+    // shorthand for using the default generator with a fixed seed
+    pub fn seed(&mut self, seed: u64) {
+        self.set_random_source(Box::new(SeededRandom::new(seed)));
+    }
 
-                self.draw_flag = true;
-                self.pc += 2;
-            }
+    pub fn hires(&self) -> bool {
+        self.hires
+    }
 
-            0xE000 => {
-                match opcode & 0x00FF {
-                    0x009E => {
-                        // 0xEX9E: Skips the next instruction if the key stored in VX is pressed. (Usually the next instruction is a jump to skip a code block)
-                        if self.key[vx as usize] != 0 {
-                            // since we can't get key released events, let's clear it out
-                            self.key[vx as usize] = 0;
-                            self.pc += 4;
-                        } else {
-                            self.pc += 2;
-                        }
-                    }
-                    0x00A1 => {
-                        // 0xEXA1: Skips the next instruction if the key stored in VX isn't pressed. (Usually the next instruction is a jump to skip a code block)
-                        if self.key[vx as usize] == 0 {
-                            self.pc += 4;
-                        } else {
-                            self.key[vx as usize] = 0;
-                            self.pc += 2;
-                        }
-                    }
-                    _ => panic!("unknown 0xE000 opcode: {:#X?}", opcode),
-                }
-            }
+    // number of columns in the active display mode
+    pub fn width(&self) -> usize {
+        if self.hires {
+            HIRES_GRAPHICS_COLUMNS
+        } else {
+            GRAPHICS_COLUMNS
+        }
+    }
 
-            0xF000 => {
-                match opcode & 0x00FF {
-                    0x0007 => {
-                        // 0xFX07: Sets VX to the value of the delay timer.
-                        self.v[x] = self.delay_timer;
-                        self.pc += 2;
-                    }
+    // number of rows in the active display mode
+    pub fn height(&self) -> usize {
+        if self.hires {
+            HIRES_GRAPHICS_ROWS
+        } else {
+            GRAPHICS_ROWS
+        }
+    }
 
-                    0x000A => {
-                        // 0xFX0A: A key press is awaited, and then stored in VX. (Blocking Operation. All instruction halted until next key event)
-                        let mut key_pressed = false;
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.gfx[y * self.width() + x] != 0
+    }
 
-                        for i in 0..16 {
-                            if self.key[i] != 0 {
-                                self.v[x] = i as u8;
-                                key_pressed = true;
-                            }
-                        }
+    // the combined XO-CHIP color (0-3) of a pixel; bit 0 is plane 1, bit 1 is plane 2
+    pub fn pixel_color(&self, x: usize, y: usize) -> u8 {
+        self.gfx[y * self.width() + x]
+    }
 
-                        if !key_pressed {
-                            // Since we didn't get a key press, we do not upate the
-                            // program counter, so the same instruciton will
-                            // get executed again, effectively waiting forever
-                            // for a keypress
-                            return;
-                        }
+    // the display in the active mode, for handing to a frontend
+    pub fn framebuffer(&self) -> Framebuffer<'_> {
+        let (width, height) = (self.width(), self.height());
 
-                        for i in 0..KEYBOARD_ARRAY_SIZE {
-                            self.key[i] = 0;
-                        }
+        Framebuffer {
+            width,
+            height,
+            pixels: &self.gfx[..width * height],
+            dirty: self.dirty.unwrap_or_default(),
+        }
+    }
 
-                        self.pc += 2;
-                    }
+    // The part of the display that instructions have changed since the last
+    // call, if any. Changes made straight to `gfx` aren't tracked.
+    pub fn take_dirty_region(&mut self) -> Option<Region> {
+        self.dirty.take()
+    }
 
-                    0x0015 => {
-                        // 0xFX15: Sets the delay timer to VX.
-                        self.delay_timer = self.v[x];
-                        self.pc += 2;
-                    }
+    // flags the display for drawing, and notes which part of it changed
+    fn mark_dirty(&mut self, region: Region) {
+        if !region.is_empty() {
+            self.dirty = Some(self.dirty.unwrap_or_default().union(region));
+        }
 
-                    0x0018 => {
-                        // 0xFX18: Sets the sound timer to VX.
-                        self.sound_timer = self.v[x];
-                        self.pc += 2;
-                    }
+        self.draw_flag = true;
+    }
 
-                    0x001E => {
-                        // 0xFX1E: Adds VX to I. VF is set to 1 when there is a range overflow
-                        // (I+VX>0xFFF), and to 0 when there isn't.
-                        if self.i + self.v[x] as u16 > 0xFFF {
-                            self.v[0xF] = 1;
-                        } else {
-                            self.v[0xF] = 0;
-                        }
+    fn mark_all_dirty(&mut self) {
+        self.mark_dirty(Region::new(0, 0, self.width(), self.height()));
+    }
 
-                        self.i += self.v[x] as u16;
-                        self.pc += 2;
-                    }
+    pub fn is_xo_chip(&self) -> bool {
+        self.xo_chip
+    }
 
-                    0x0029 => {
-                        // 0xFX29: Sets I to the location of the sprite for the character in VX. Characters 0-F (in hexadecimal) are represented by a 4x5 font.
-                        self.i = (self.v[x] * 0x5) as u16;
-                        self.pc += 2;
-                    }
+    pub fn audio_pattern(&self) -> &[u8; AUDIO_PATTERN_SIZE] {
+        &self.audio_pattern
+    }
 
-                    0x0033 => {
-                        // 0xFX33: Stores the binary-coded decimal representation of VX, with the most significant of three digits at the address in I, the middle digit at I plus 1, and the least significant digit at I plus 2.
-                        self.memory[self.i as usize] = self.v[x] / 100;
-                        self.memory[(self.i + 1) as usize] = self.v[x] / 10 % 10;
-                        self.memory[(self.i + 2) as usize] = self.v[x] % 10;
-                        self.pc += 2;
-                    }
+    pub fn pitch(&self) -> u8 {
+        self.pitch
+    }
 
-                    0x0055 => {
-                        // 0xFX55: Stores V0 to VX (including VX) in memory starting at address I.
-                        // The offset from I is increased by 1 for each value written, but I
-                        // itself is left unmodified.
-                        for i in 0..=x {
-                            self.memory[(self.i + i as u16) as usize] = self.v[i];
-                        }
-                        self.pc += 2;
-                    }
+    // the rate, in bits per second, the audio pattern is played back at
+    pub fn playback_rate(&self) -> f64 {
+        4000.0 * 2f64.powf((self.pitch as f64 - 64.0) / 48.0)
+    }
 
-                    0x0065 => {
-                        // 0xFX65: Fills V0 to VX (including VX) with values from memory
-                        // starting at address I. The offset from I is increased by 1 for
-                        // each value written, but I itself is left unmodified.
-                        for i in 0..=x {
-                            self.v[i] = self.memory[(self.i + i as u16) as usize];
-                        }
-                        self.pc += 2;
-                    }
-                    _ => panic!("unknown 0xF000 opcode: {:#X?}", opcode),
-                }
-            }
+    // only the selected XO-CHIP planes are cleared and scrolled; outside of
+    // XO-CHIP mode plane 1 is the only plane, so this is the whole screen
+    fn clear_screen(&mut self) {
+        let mask = self.plane;
 
-            _ => panic!("unknown opcode: {:#X?}", opcode),
+        for i in 0..GRAPHICS_ARRAY_SIZE {
+            self.gfx[i] &= !mask;
         }
-    }
 
-    pub fn clear_keys(&mut self) {
-        for i in 0..KEYBOARD_ARRAY_SIZE {
-            self.key[i] = 0;
-        }
+        self.mark_all_dirty();
     }
 
-    pub fn to_string(&self) -> String {
-        let mut rows: Vec<String> = vec![];
+    fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
 
-        for row in self.gfx.chunks(GRAPHICS_COLUMNS) {
-            let s: String = row
-                .iter()
-                .map(|c| if *c == 1 { '*' } else { ' ' })
-                .collect();
-            rows.push(s.clone());
+        for i in 0..GRAPHICS_ARRAY_SIZE {
+            self.gfx[i] = 0;
         }
 
-        rows.join("\n")
+        self.mark_all_dirty();
     }
-}
 
-static CHIP8_FONTSET: [u8; 80] = [
-    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-    0x20, 0x60, 0x20, 0x20, 0x70, // 1
-    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
-];
+    fn move_pixel(&mut self, to: usize, from: Option<usize>) {
+        let mask = self.plane;
+        let source = from.map_or(0, |f| self.gfx[f]);
 
-fn read_word(memory: [u8; 4096], index: u16) -> u16 {
-    (memory[index as usize] as u16) << 8 | memory[(index + 1) as usize] as u16
-}
+        self.gfx[to] = (self.gfx[to] & !mask) | (source & mask);
+    }
 
-#[cfg(test)]
-mod tests {
-    use std::error::Error;
+    fn scroll_down(&mut self, lines: usize) {
+        let (width, height) = (self.width(), self.height());
 
-    use crate::{
-        Chip8, GRAPHICS_ARRAY_SIZE, GRAPHICS_COLUMNS, GRAPHICS_ROWS, LOWER_MEMORY_BOUNDARY,
-    };
+        for y in (0..height).rev() {
+            for x in 0..width {
+                let from = if y >= lines {
+                    Some((y - lines) * width + x)
+                } else {
+                    None
+                };
 
-    #[test]
-    fn test_load_program() {
-        let program: Vec<u8> = [0; 512].to_vec();
+                self.move_pixel(y * width + x, from);
+            }
+        }
 
-        let chip8 = create_and_load(&program);
-        assert!(chip8.is_ok())
+        self.mark_all_dirty();
     }
 
-    #[test]
-    fn test_load_program_that_is_too_big() {
-        let program: Vec<u8> = [0; 8192].to_vec();
+    fn scroll_right(&mut self, pixels: usize) {
+        let (width, height) = (self.width(), self.height());
 
-        let chip8 = create_and_load(&program);
-        assert!(chip8.is_err())
-    }
+        for y in 0..height {
+            for x in (0..width).rev() {
+                let from = if x >= pixels {
+                    Some(y * width + x - pixels)
+                } else {
+                    None
+                };
 
-    #[test]
-    fn test_clear_screen() {
-        // 0x00E0; clear the screen
-        let program: Vec<u8> = vec![0x0, 0xE0];
+                self.move_pixel(y * width + x, from);
+            }
+        }
 
-        let mut chip8 = create_and_load(&program).unwrap();
+        self.mark_all_dirty();
+    }
 
-        for i in 0..GRAPHICS_ARRAY_SIZE {
-            chip8.gfx[i] = 1;
-        }
+    fn scroll_left(&mut self, pixels: usize) {
+        let (width, height) = (self.width(), self.height());
 
-        chip8.execute_cycle();
+        for y in 0..height {
+            for x in 0..width {
+                let from = if x + pixels < width {
+                    Some(y * width + x + pixels)
+                } else {
+                    None
+                };
 
-        let all_empty = chip8.gfx.iter().all(|b| *b == 0);
+                self.move_pixel(y * width + x, from);
+            }
+        }
 
-        assert!(all_empty);
-        assert!(chip8.draw_flag);
-        assert_eq!(chip8.pc, (LOWER_MEMORY_BOUNDARY + 2) as u16);
+        self.mark_all_dirty();
     }
 
-    #[test]
-    fn test_return_from_subroutine() {
-        // 0x00EE; returns from subroutine
-        let program: Vec<u8> = vec![
-            0x22, 0xA, 0x17, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0xEE,
-        ];
-
-        let mut chip8 = create_and_load(&program).unwrap();
+    // Moves I on after FX55/FX65 have stored or loaded V0 to VX, if the
+    // quirks say it should move.
+    fn increment_i_after_load_store(&mut self, x: usize) {
+        if self.quirks.load_store_increments_i {
+            let step = if self.quirks.load_store_increments_i_by_x {
+                x
+            } else {
+                x + 1
+            };
+
+            self.i = self.i.wrapping_add(step as u16);
+        }
+    }
 
-        chip8.i = LOWER_MEMORY_BOUNDARY as u16;
+    // Skips the next instruction. In XO-CHIP mode the next instruction may be
+    // the four byte F000 NNNN, which has to be skipped as a whole.
+    fn skip_next_instruction(&mut self) {
+        let next = self.pc as usize + 2;
+        let long = self.xo_chip
+            && self.memory.get(next) == Some(&0xF0)
+            && self.memory.get(next + 1) == Some(&0x00);
 
-        assert_eq!(chip8.sp, 0);
+        self.pc = self.pc.wrapping_add(if long { 6 } else { 4 });
+    }
 
-        let orig_pc = chip8.pc;
+    // The address `offset` bytes on from `i`. XO-CHIP memory is all 64 KiB,
+    // so data addresses wrap round it just as the program counter does; the
+    // smaller classic memory faults on anything past the end instead.
+    fn data_address(&self, i: u16, offset: usize) -> usize {
+        let address = i as usize + offset;
 
-        // the first time through will jump to the beginning
-        // of the subroutine
-        chip8.execute_cycle();
+        if self.xo_chip {
+            address & (self.memory.len() - 1)
+        } else {
+            address
+        }
+    }
 
-        assert_eq!(chip8.pc, 0x20A);
-        assert_eq!(chip8.sp, 1);
-        assert_eq!(chip8.stack[0], LOWER_MEMORY_BOUNDARY as u16);
+    fn read_word(&self, address: u16) -> Result<u16, Chip8Fault> {
+        let hi = self.read_byte(address as usize, 0)?;
+        let lo = self.read_byte(self.data_address(address, 1), 0)?;
 
-        // and the second time through should return from it
-        chip8.execute_cycle();
+        Ok((hi as u16) << 8 | lo as u16)
+    }
 
-        assert_eq!(chip8.pc, orig_pc + 2);
-        assert_eq!(chip8.sp, 0);
-        assert_eq!(chip8.memory[chip8.pc as usize], 0x17);
+    fn read_byte(&self, address: usize, opcode: u16) -> Result<u8, Chip8Fault> {
+        match self.memory.get(address) {
+            Some(b) => Ok(*b),
+            None => Err(Chip8Fault::MemoryOutOfRange {
+                pc: self.pc,
+                opcode,
+                address,
+            }),
+        }
     }
 
-    #[test]
-    fn test_jump_to_address() {
-        // 0x1NNN: jumps to address NNN
-        let program: Vec<u8> = vec![0x10, 0xDC];
+    // reads a byte of data for an instruction, as opposed to fetching one
+    fn load_byte(&mut self, address: usize, opcode: u16) -> Result<u8, Chip8Fault> {
+        let value = self.read_byte(address, opcode)?;
 
-        let mut chip8 = create_and_load(&program).unwrap();
+        self.record_access(address, Access::Read);
+        Ok(value)
+    }
 
-        assert_eq!(chip8.memory[0xDC as usize], 0);
-        chip8.memory[0xDC as usize] = 1;
-        assert_eq!(chip8.memory[0xDC as usize], 1);
+    fn write_byte(&mut self, address: usize, value: u8, opcode: u16) -> Result<(), Chip8Fault> {
+        let pc = self.pc;
 
-        chip8.execute_cycle();
+        match self.memory.get_mut(address) {
+            Some(b) => {
+                *b = value;
+                self.record_access(address, Access::Write);
+                Ok(())
+            }
+            None => Err(Chip8Fault::MemoryOutOfRange {
+                pc,
+                opcode,
+                address,
+            }),
+        }
+    }
+
+    fn record_access(&mut self, address: usize, access: Access) {
+        if self.track_accesses {
+            self.accesses.push(MemoryAccess { address, access });
+        }
+    }
+
+    fn key_pressed(&self, key: u16, opcode: u16) -> Result<bool, Chip8Fault> {
+        match self.key.get(key as usize) {
+            Some(k) => Ok(*k != 0),
+            None => Err(Chip8Fault::BadKey {
+                pc: self.pc,
+                opcode,
+                key: key as u8,
+            }),
+        }
+    }
+
+    // Executes a single decoded instruction, as if it had been fetched from
+    // the current program counter.
+    pub fn execute(&mut self, instruction: Instruction) -> Result<StepOutcome, Chip8Fault> {
+        let pc = self.pc;
+        let opcode = instruction.opcode();
+
+        if !instruction.is_valid() {
+            return Err(Chip8Fault::BadOperand { pc, opcode });
+        }
+
+        if instruction.is_xo_chip() && !self.xo_chip {
+            return Err(Chip8Fault::UnknownOpcode { pc, opcode });
+        }
+
+        self.accesses.clear();
+
+        match instruction {
+            Instruction::ClearScreen => {
+                // 0x00E0; clear the screen
+                self.clear_screen();
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::Return => {
+                // 0x00EE; returns from subroutine
+                if self.sp == 0 {
+                    return Err(Chip8Fault::StackUnderflow { pc, opcode });
+                }
+
+                self.sp -= 1;
+                self.pc = self.stack[self.sp as usize];
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::ScrollDown { n } => {
+                // 0x00CN: scroll the display down N lines (SUPER-CHIP)
+                self.scroll_down(n as usize);
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::ScrollRight => {
+                // 0x00FB: scroll the display right 4 pixels (SUPER-CHIP)
+                self.scroll_right(SCROLL_PIXELS);
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::ScrollLeft => {
+                // 0x00FC: scroll the display left 4 pixels (SUPER-CHIP)
+                self.scroll_left(SCROLL_PIXELS);
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::Exit => {
+                // 0x00FD: exit the interpreter (SUPER-CHIP)
+                return Ok(StepOutcome::Exited);
+            }
+
+            Instruction::LoRes => {
+                // 0x00FE: switch to 64x32 lo-res mode (SUPER-CHIP)
+                self.set_hires(false);
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::HiRes => {
+                // 0x00FF: switch to 128x64 hi-res mode (SUPER-CHIP)
+                self.set_hires(true);
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::Sys { nnn } => {
+                // 0x0NNN: Calls RCA 1802 program at address NNN. Not necessary for most ROMs.
+                self.pc = nnn;
+            }
+
+            Instruction::Jump { nnn } => {
+                // 0x1NNN: jumps to address NNN
+                self.pc = nnn;
+            }
+
+            Instruction::Call { nnn } => {
+                // 0x2NNN: calls subroutine at NNN
+                if self.sp as usize >= STACK_SIZE {
+                    return Err(Chip8Fault::StackOverflow { pc, opcode });
+                }
+
+                self.stack[self.sp as usize] = self.pc;
+                self.sp += 1;
+                self.pc = nnn;
+            }
+
+            Instruction::SkipEqImm { x, nn } => {
+                // 0x3XNN: Skips the next instruction if VX equals NN. (Usually the next instruction is a jump to skip a code block)
+                self.skip_if(self.v[x as usize] == nn);
+            }
+
+            Instruction::SkipNeImm { x, nn } => {
+                // 0x4XNN: Skips the next instruction if VX doesn't equal NN. (Usually the next instruction is a jump to skip a code block)
+                self.skip_if(self.v[x as usize] != nn);
+            }
+
+            Instruction::SkipEqReg { x, y } => {
+                // 0x5XY0: Skips the next instruction if VX equals VY. (Usually the next instruction is a jump to skip a code block)
+                self.skip_if(self.v[x as usize] == self.v[y as usize]);
+            }
+
+            Instruction::SaveRange { x, y } => {
+                // 0x5XY2: Stores VX to VY (in either order) in memory starting at address I,
+                // leaving I unmodified (XO-CHIP)
+                for (offset, r) in register_range(x as usize, y as usize).enumerate() {
+                    let address = self.data_address(self.i, offset);
+                    self.write_byte(address, self.v[r], opcode)?;
+                }
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::LoadRange { x, y } => {
+                // 0x5XY3: Fills VX to VY (in either order) with values from memory starting
+                // at address I, leaving I unmodified (XO-CHIP)
+                for (offset, r) in register_range(x as usize, y as usize).enumerate() {
+                    let address = self.data_address(self.i, offset);
+                    self.v[r] = self.load_byte(address, opcode)?;
+                }
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::SetImm { x, nn } => {
+                // 0x6XNN: Sets VX to NN.
+                self.v[x as usize] = nn;
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::AddImm { x, nn } => {
+                // 0x7XNN: Adds NN to VX. (Carry flag is not changed)
+                let x = x as usize;
+                self.v[x] = ((self.v[x] as u16 + nn as u16) & 0xff) as u8;
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::Move { x, y } => {
+                // 0x8XY0: Sets VX to the value of VY.
+                self.v[x as usize] = self.v[y as usize];
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::Or { x, y } => {
+                // 0x8XY1: Sets VX to VX or VY. (Bitwise OR operation)
+                self.v[x as usize] |= self.v[y as usize];
+
+                if self.quirks.logic_resets_vf {
+                    self.v[0xF] = 0;
+                }
+
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::And { x, y } => {
+                // 0x8XY2: Sets VX to VX and VY. (Bitwise AND operation)
+                self.v[x as usize] &= self.v[y as usize];
+
+                if self.quirks.logic_resets_vf {
+                    self.v[0xF] = 0;
+                }
+
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::Xor { x, y } => {
+                // 0x8XY3: Sets VX to VX xor VY.
+                self.v[x as usize] ^= self.v[y as usize];
+
+                if self.quirks.logic_resets_vf {
+                    self.v[0xF] = 0;
+                }
+
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::Add { x, y } => {
+                // 0x8XY4: Adds VY to VX. VF is set to 1 when there's a carry, and to 0 when there isn't.
+                let (x, y) = (x as usize, y as usize);
+
+                if self.v[y] > (0xFF - self.v[x]) {
+                    self.v[0xF] = 1; // carry the 1
+                } else {
+                    self.v[0xF] = 0;
+                }
+
+                self.v[x] = ((self.v[x] as u16 + self.v[y] as u16) & 0xff) as u8;
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::Sub { x, y } => {
+                // 0x8XY5: VY is subtracted from VX. VF is set to 0 when there's a borrow, and 1 when there isn't.
+                let (x, y) = (x as usize, y as usize);
+
+                if self.v[y] > (self.v[x]) {
+                    self.v[0xF] = 0; // carry the 1
+                } else {
+                    self.v[0xF] = 1;
+                }
+
+                let tx = self.v[x];
+                let ty = self.v[y];
+
+                let tz = if ty > tx {
+                    ((tx as i16 - ty as i16).unsigned_abs() as u8) - 1
+                } else {
+                    tx - ty
+                };
+
+                self.v[x] = tz;
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::ShiftRight { x, y } => {
+                // 0x8XY6: Stores the least significant bit of VX in VF and then shifts VX to the right by 1.
+                // With the `shift_uses_vy` quirk, VY is shifted into VX instead.
+                let source = if self.quirks.shift_uses_vy {
+                    self.v[y as usize]
+                } else {
+                    self.v[x as usize]
+                };
+
+                self.v[x as usize] = source >> 1;
+                self.v[0xF] = source & 0x1;
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::SubReverse { x, y } => {
+                // 0x8XY7: Sets VX to VY minus VX. VF is set to 0 when there's a borrow, and 1 when there isn't.
+                let vx = self.v[x as usize] as u16;
+                let vy = self.v[y as usize] as u16;
+
+                if vx > vy {
+                    self.v[0xF] = 0;
+                } else {
+                    self.v[0xF] = 1;
+                }
+
+                let tz = if vx > vy {
+                    ((vy as i16 - vx as i16).unsigned_abs() as u8) - 1
+                } else {
+                    (vy - vx) as u8
+                };
+
+                self.v[x as usize] = tz;
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::ShiftLeft { x, y } => {
+                // 0x8XYE: Stores the most significant bit of VX in VF and then shifts VX to the left by 1.
+                // With the `shift_uses_vy` quirk, VY is shifted into VX instead.
+                let source = if self.quirks.shift_uses_vy {
+                    self.v[y as usize]
+                } else {
+                    self.v[x as usize]
+                };
+
+                self.v[x as usize] = source << 1;
+                self.v[0xF] = source >> 7;
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::SkipNeReg { x, y } => {
+                // 0x9XY0: Skips the next instruction if VX doesn't equal VY. (Usually the next instruction is a jump to skip a code block)
+                self.skip_if(self.v[x as usize] != self.v[y as usize]);
+            }
+
+            Instruction::SetI { nnn } => {
+                // 0xANNN: sets I to the address NNN
+                self.i = nnn;
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::JumpOffset { nnn } => {
+                // 0xBNNN: Jumps to the address NNN plus V0.
+                // With the `jump_uses_vx` quirk this is 0xBXNN, which jumps to XNN plus VX.
+                let offset = if self.quirks.jump_uses_vx {
+                    self.v[(nnn >> 8) as usize]
+                } else {
+                    self.v[0]
+                };
+
+                self.pc = nnn + offset as u16;
+            }
+
+            Instruction::Random { x, nn } => {
+                // 0xCXNN: Sets VX to the result of a bitwise and operation on a random number (Typically: 0 to 255) and NN.
+                let r = self.rng.next_byte();
+                self.v[x as usize] = r & nn;
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::Draw { x, y, n } => {
+                // 0xDXYN: Draws a sprite at coordinate (VX, VY) that has a width of 8 pixels
+                // and a height of N pixels.
+                // 0xDXY0: Draws a 16x16 sprite, two bytes per row (SUPER-CHIP)
+                self.draw_sprite(self.v[x as usize], self.v[y as usize], n, opcode)?;
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::SkipKeyDown { x } => {
+                // 0xEX9E: Skips the next instruction if the key stored in VX is pressed. (Usually the next instruction is a jump to skip a code block)
+                let pressed = self.key_pressed(self.v[x as usize] as u16, opcode)?;
+                self.skip_if(pressed);
+            }
+
+            Instruction::SkipKeyUp { x } => {
+                // 0xEXA1: Skips the next instruction if the key stored in VX isn't pressed. (Usually the next instruction is a jump to skip a code block)
+                let pressed = self.key_pressed(self.v[x as usize] as u16, opcode)?;
+                self.skip_if(!pressed);
+            }
+
+            Instruction::SetLongI => {
+                // 0xF000 NNNN: Sets I to the 16-bit address NNNN in the next word (XO-CHIP)
+                self.i = self.read_word(self.pc.wrapping_add(2))?;
+                self.pc = self.pc.wrapping_add(4);
+            }
+
+            Instruction::SelectPlanes { n } => {
+                // 0xFN01: Selects the display planes in the bitmask N for drawing,
+                // clearing and scrolling (XO-CHIP)
+                self.plane = n & ALL_PLANES;
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::LoadAudio => {
+                // 0xF002: Loads the 16-byte audio pattern buffer from memory starting
+                // at address I (XO-CHIP)
+                for offset in 0..AUDIO_PATTERN_SIZE {
+                    let address = self.data_address(self.i, offset);
+                    self.audio_pattern[offset] = self.load_byte(address, opcode)?;
+                }
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::SetPitch { x } => {
+                // 0xFX3A: Sets the audio pitch register to VX (XO-CHIP)
+                self.pitch = self.v[x as usize];
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::GetDelay { x } => {
+                // 0xFX07: Sets VX to the value of the delay timer.
+                self.v[x as usize] = self.delay_timer;
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::WaitKey { x } => {
+                // 0xFX0A: A key press is awaited, and then stored in VX. (Blocking Operation. All instruction halted until next key event)
+                // Like the original hardware, the key has to be pressed and then
+                // released before execution carries on.
+                match self.awaited_key {
+                    Some(k) if self.key[k as usize] == 0 => {
+                        self.v[x as usize] = k;
+                        self.awaited_key = None;
+                    }
+                    Some(_) => return Ok(StepOutcome::WaitingForKey),
+                    None => {
+                        self.awaited_key = self.key.iter().position(|k| *k != 0).map(|k| k as u8);
+
+                        // Since we don't have a complete key press yet, we do
+                        // not update the program counter, so the same
+                        // instruction will get executed again, effectively
+                        // waiting forever for a keypress
+                        return Ok(StepOutcome::WaitingForKey);
+                    }
+                }
+
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::SetDelay { x } => {
+                // 0xFX15: Sets the delay timer to VX.
+                self.delay_timer = self.v[x as usize];
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::SetSound { x } => {
+                // 0xFX18: Sets the sound timer to VX.
+                self.sound_timer = self.v[x as usize];
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::AddI { x } => {
+                // 0xFX1E: Adds VX to I. VF is set to 1 when there is a range overflow
+                // (I+VX>0xFFF), and to 0 when there isn't.
+                let vx = self.v[x as usize] as u16;
+
+                if self.i as u32 + vx as u32 > 0xFFF {
+                    self.v[0xF] = 1;
+                } else {
+                    self.v[0xF] = 0;
+                }
+
+                self.i = self.i.wrapping_add(vx);
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::Font { x } => {
+                // 0xFX29: Sets I to the location of the sprite for the character in VX. Characters 0-F (in hexadecimal) are represented by a 4x5 font.
+                self.i = (self.v[x as usize] & 0xF) as u16 * 0x5;
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::BigFont { x } => {
+                // 0xFX30: Sets I to the location of the 8x10 sprite for the digit in VX (SUPER-CHIP)
+                self.i = (BIG_FONT_ADDRESS + (self.v[x as usize] & 0xF) as usize * 10) as u16;
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::Bcd { x } => {
+                // 0xFX33: Stores the binary-coded decimal representation of VX, with the most significant of three digits at the address in I, the middle digit at I plus 1, and the least significant digit at I plus 2.
+                let vx = self.v[x as usize];
+
+                for (offset, digit) in [vx / 100, vx / 10 % 10, vx % 10].iter().enumerate() {
+                    let address = self.data_address(self.i, offset);
+                    self.write_byte(address, *digit, opcode)?;
+                }
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::Store { x } => {
+                // 0xFX55: Stores V0 to VX (including VX) in memory starting at address I.
+                // The offset from I is increased by 1 for each value written, but I
+                // itself is left unmodified.
+                let x = x as usize;
+
+                for i in 0..=x {
+                    let address = self.data_address(self.i, i);
+                    self.write_byte(address, self.v[i], opcode)?;
+                }
+
+                self.increment_i_after_load_store(x);
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::Load { x } => {
+                // 0xFX65: Fills V0 to VX (including VX) with values from memory
+                // starting at address I. The offset from I is increased by 1 for
+                // each value written, but I itself is left unmodified.
+                let x = x as usize;
+
+                for i in 0..=x {
+                    let address = self.data_address(self.i, i);
+                    self.v[i] = self.load_byte(address, opcode)?;
+                }
+
+                self.increment_i_after_load_store(x);
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::SaveFlags { x } => {
+                // 0xFX75: Stores V0 to VX (including VX) in the RPL user flags (SUPER-CHIP)
+                let x = x as usize;
+                self.rpl[..=x].copy_from_slice(&self.v[..=x]);
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            Instruction::LoadFlags { x } => {
+                // 0xFX85: Fills V0 to VX (including VX) from the RPL user flags (SUPER-CHIP)
+                let x = x as usize;
+                self.v[..=x].copy_from_slice(&self.rpl[..=x]);
+                self.pc = self.pc.wrapping_add(2);
+            }
+        }
+
+        Ok(StepOutcome::Executed)
+    }
+
+    fn skip_if(&mut self, condition: bool) {
+        if condition {
+            self.skip_next_instruction();
+        } else {
+            self.pc = self.pc.wrapping_add(2);
+        }
+    }
+
+    // Draws an 8xN sprite (or a 16x16 one when N is 0) from memory at I to
+    // every selected plane, setting VF on collision.
+    fn draw_sprite(&mut self, vx: u8, vy: u8, n: u8, opcode: u16) -> Result<(), Chip8Fault> {
+        let (sprite_width, height) = if n == 0 { (16, 16) } else { (8, n as usize) };
+        let bytes_per_row = sprite_width / 8;
+        let columns = self.width() as u16;
+        let rows = self.height() as u16;
+
+        self.v[0xF] = 0;
+
+        // the starting position always wraps; the `sprites_wrap` quirk
+        // decides whether the rest of the sprite wraps or is clipped
+        let start_x = vx as u16 % columns;
+        let start_y = vy as u16 % rows;
+
+        // with both XO-CHIP planes selected, the plane 2 sprite data
+        // follows straight on from the plane 1 data
+        let mut sprite_offset = 0;
+        let mut drawn: Option<Region> = None;
+
+        for plane in [1u8, 2u8].iter().copied() {
+            if self.plane & plane == 0 {
+                continue;
+            }
+
+            for yline in 0..height {
+                let mut pixel: u16 = 0;
+
+                for b in 0..bytes_per_row {
+                    let address =
+                        self.data_address(self.i, sprite_offset + yline * bytes_per_row + b);
+                    pixel = pixel << 8 | self.load_byte(address, opcode)? as u16;
+                }
+
+                for xline in 0..sprite_width {
+                    if (pixel & (1 << (sprite_width - 1 - xline))) != 0 {
+                        let x_coord = start_x + xline as u16;
+                        let y_coord = start_y + yline as u16;
+
+                        if !self.quirks.sprites_wrap && (x_coord >= columns || y_coord >= rows) {
+                            continue;
+                        }
+
+                        let x_coord = x_coord % columns;
+                        let y_coord = y_coord % rows;
+                        let pixel_index = ((y_coord * columns) + x_coord) as usize;
+
+                        if self.gfx[pixel_index] & plane != 0 {
+                            self.v[0xF] = 1;
+                        }
+
+                        self.gfx[pixel_index] ^= plane;
+
+                        let pixel = Region::new(x_coord as usize, y_coord as usize, 1, 1);
+
+                        drawn = Some(drawn.map_or(pixel, |drawn| drawn.union(pixel)));
+                    }
+                }
+            }
+
+            sprite_offset += height * bytes_per_row;
+        }
+
+        // a wrapped sprite marks everything between its two halves
+        self.mark_dirty(drawn.unwrap_or_default());
+        Ok(())
+    }
+
+    // Marks a key on the hex keypad (0-F) as held down. Out of range keys are ignored.
+    pub fn key_down(&mut self, key: u8) {
+        if let Some(k) = self.key.get_mut(key as usize) {
+            *k = 1;
+        }
+    }
+
+    // Marks a key on the hex keypad (0-F) as released. Out of range keys are ignored.
+    pub fn key_up(&mut self, key: u8) {
+        if let Some(k) = self.key.get_mut(key as usize) {
+            *k = 0;
+        }
+    }
+
+    pub fn is_key_down(&self, key: u8) -> bool {
+        self.key.get(key as usize).is_some_and(|k| *k != 0)
+    }
+
+    pub fn clear_keys(&mut self) {
+        for i in 0..KEYBOARD_ARRAY_SIZE {
+            self.key[i] = 0;
+        }
+    }
+}
+
+impl fmt::Display for Chip8 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut rows: Vec<String> = vec![];
+        let width = self.width();
+
+        for row in self.gfx[..width * self.height()].chunks(width) {
+            let s: String = row
+                .iter()
+                .map(|c| if *c != 0 { '*' } else { ' ' })
+                .collect();
+            rows.push(s);
+        }
+
+        write!(f, "{}", rows.join("\n"))
+    }
+}
+
+// the registers touched by 5XY2/5XY3, which run backwards when X > Y
+fn register_range(x: usize, y: usize) -> Box<dyn Iterator<Item = usize>> {
+    if x <= y {
+        Box::new(x..=y)
+    } else {
+        Box::new((y..=x).rev())
+    }
+}
+
+static SCHIP_BIG_FONTSET: [u8; 160] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+static CHIP8_FONTSET: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use crate::{
+        Access, Chip8, Chip8Fault, Instruction, MemoryAccess, Quirks, Region, ScriptedRandom,
+        StepOutcome, BIG_FONT_ADDRESS, GRAPHICS_ARRAY_SIZE, GRAPHICS_COLUMNS, GRAPHICS_ROWS,
+        HIRES_GRAPHICS_COLUMNS, HIRES_GRAPHICS_ROWS, LOWER_MEMORY_BOUNDARY, STACK_SIZE,
+        XO_CHIP_MEMORY_SIZE,
+    };
+
+    #[test]
+    fn test_load_program() {
+        let program: Vec<u8> = [0; 512].to_vec();
+
+        let chip8 = create_and_load(&program);
+        assert!(chip8.is_ok())
+    }
+
+    #[test]
+    fn test_load_program_that_is_too_big() {
+        let program: Vec<u8> = [0; 8192].to_vec();
+
+        let chip8 = create_and_load(&program);
+        assert!(chip8.is_err())
+    }
+
+    #[test]
+    fn test_clear_screen() {
+        // 0x00E0; clear the screen
+        let program: Vec<u8> = vec![0x0, 0xE0];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        for i in 0..GRAPHICS_ARRAY_SIZE {
+            chip8.gfx[i] = 1;
+        }
+
+        chip8.execute_cycle().unwrap();
+
+        let all_empty = chip8.gfx.iter().all(|b| *b == 0);
+
+        assert!(all_empty);
+        assert!(chip8.draw_flag);
+        assert_eq!(chip8.pc, (LOWER_MEMORY_BOUNDARY + 2) as u16);
+    }
+
+    #[test]
+    fn test_return_from_subroutine() {
+        // 0x00EE; returns from subroutine
+        let program: Vec<u8> = vec![
+            0x22, 0xA, 0x17, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0xEE,
+        ];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        chip8.i = LOWER_MEMORY_BOUNDARY as u16;
+
+        assert_eq!(chip8.sp, 0);
+
+        let orig_pc = chip8.pc;
+
+        // the first time through will jump to the beginning
+        // of the subroutine
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.pc, 0x20A);
+        assert_eq!(chip8.sp, 1);
+        assert_eq!(chip8.stack[0], LOWER_MEMORY_BOUNDARY as u16);
+
+        // and the second time through should return from it
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.pc, orig_pc + 2);
+        assert_eq!(chip8.sp, 0);
+        assert_eq!(chip8.memory[chip8.pc as usize], 0x17);
+    }
+
+    #[test]
+    fn test_jump_to_address() {
+        // 0x1NNN: jumps to address NNN
+        let program: Vec<u8> = vec![0x10, 0xDC];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        assert_eq!(chip8.memory[0xDC_usize], 0);
+        chip8.memory[0xDC_usize] = 1;
+        assert_eq!(chip8.memory[0xDC_usize], 1);
+
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.pc, 0xDC);
+        assert_eq!(chip8.memory[chip8.pc as usize], 1);
+    }
+
+    #[test]
+    fn test_call_subroutine_at_nnn() {
+        // 0x2NNN: calls subroutine at NNN
+        let program: Vec<u8> = vec![0x20, 0xDC];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        assert_eq!(chip8.sp, 0);
+
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.pc, 0xDC);
+        assert_eq!(chip8.sp, 1);
+        assert_eq!(chip8.stack[0], 512);
+    }
+
+    #[test]
+    fn test_skip_next_instruction_if_vx_equals_nn_positive() {
+        // 0x3XNN: Skips the next instruction if VX equals NN.
+        let program: Vec<u8> = vec![0x34, 0x17];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        chip8.v[4] = 0x17;
+
+        let orig_pc = chip8.pc;
+
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.pc, orig_pc + 4);
+    }
+
+    #[test]
+    fn test_skip_next_instruction_if_vx_equals_nn_negative() {
+        // 0x3XNN: Skips the next instruction if VX equals NN.
+        let program: Vec<u8> = vec![0x34, 0x17];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        chip8.v[4] = 0x23;
+
+        let orig_pc = chip8.pc;
+
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.pc, orig_pc + 2);
+    }
+
+    #[test]
+    fn test_skip_next_instruction_if_vx_does_not_equal_nn_positive() {
+        // 0x4XNN: Skips the next instruction if VX doesn't equal NN.
+        let program: Vec<u8> = vec![0x44, 0x17];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        chip8.v[4] = 0x23;
+
+        let orig_pc = chip8.pc;
+
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.pc, orig_pc + 4);
+    }
+
+    #[test]
+    fn test_skip_next_instruction_if_vx_does_not_equal_nn_negative() {
+        // 0x4XNN: Skips the next instruction if VX doesn't equal NN.
+        let program: Vec<u8> = vec![0x44, 0x17];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        chip8.v[4] = 0x17;
+
+        let orig_pc = chip8.pc;
+
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.pc, orig_pc + 2);
+    }
+
+    #[test]
+    fn test_skip_next_instruction_if_vx_equals_vy_positive() {
+        // 0x5XY0: Skips the next instruction if VX equals VY.
+        let program: Vec<u8> = vec![0x54, 0x60];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        chip8.v[4] = 0x17;
+        chip8.v[6] = 0x17;
+
+        let orig_pc = chip8.pc;
+
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.pc, orig_pc + 4);
+    }
+
+    #[test]
+    fn test_skip_next_instruction_if_vx_equals_vy_negative() {
+        // 0x5XY0: Skips the next instruction if VX equals VY.
+        let program: Vec<u8> = vec![0x54, 0x60];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        chip8.v[4] = 0x17;
+        chip8.v[6] = 0x23;
+
+        let orig_pc = chip8.pc;
+
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.pc, orig_pc + 2);
+    }
+
+    #[test]
+    fn test_set_vx_to_nn() {
+        // 0x6XNN: Sets VX to NN.
+        let program: Vec<u8> = vec![0x64, 0xAA];
+
+        let chip8 = create_and_load(&program).unwrap();
+
+        assert_eq!(chip8.v[4], 0);
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.v[4], 0xAA);
+    }
+
+    #[test]
+    fn test_add_nn_to_vx() {
+        // 0x7XNN: Adds NN to VX. (Carry flag is not changed)
+        let program: Vec<u8> = vec![0x74, 0xAA];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        chip8.v[4] = 0x10;
+
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.v[4], 0xBA);
+    }
+
+    #[test]
+    fn test_add_nn_to_vx_wrapping() {
+        // 0x7XNN: Adds NN to VX. (Carry flag is not changed)
+        let program: Vec<u8> = vec![0x74, 0xAA];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        chip8.v[4] = 0xBA;
+
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.v[4], 0x64);
+    }
+
+    #[test]
+    fn test_set_vx_to_value_of_vy() {
+        // 0x8XY0: Sets VX to the value of VY.
+        let program: Vec<u8> = vec![0x84, 0x50];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        chip8.v[4] = 0xBA;
+        chip8.v[5] = 0xDD;
+
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.v[4], 0xDD);
+        assert_eq!(chip8.v[5], 0xDD);
+    }
+
+    #[test]
+    fn test_set_vx_to_vx_or_vy() {
+        // 0x8XY1: Sets VX to VX or VY. (Bitwise OR operation)
+        let program: Vec<u8> = vec![0x84, 0x51];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        chip8.v[4] = 0xBA;
+        chip8.v[5] = 0xCC;
+
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.v[4], 0xFE);
+        assert_eq!(chip8.v[5], 0xCC);
+    }
+
+    #[test]
+    fn test_set_vx_to_vx_and_vy() {
+        // 0x8XY2: Sets VX to VX and VY. (Bitwise AND operation)
+        let program: Vec<u8> = vec![0x84, 0x52];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        chip8.v[4] = 0xBA;
+        chip8.v[5] = 0xCC;
+
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.v[4], 0x88);
+        assert_eq!(chip8.v[5], 0xCC);
+    }
+
+    #[test]
+    fn test_set_vx_to_vx_xor_vy() {
+        // 0x8XY3: Sets VX to VX xor VY.
+        let program: Vec<u8> = vec![0x84, 0x53];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        chip8.v[4] = 0xBA;
+        chip8.v[5] = 0xCC;
+
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.v[4], 0x76);
+        assert_eq!(chip8.v[5], 0xCC);
+    }
+
+    #[test]
+    fn test_add_vy_to_vx_with_carry() {
+        // 0x8XY4: Adds VY to VX. VF is set to 1 when there's a carry, and to 0 when there isn't.
+        let program: Vec<u8> = vec![0x84, 0x54];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        assert_eq!(chip8.v[0xF], 0);
+
+        chip8.v[4] = 0xBA;
+        chip8.v[5] = 0xCC;
+
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.v[4], 0x86);
+        assert_eq!(chip8.v[0xF], 1);
+    }
+
+    #[test]
+    fn test_add_vy_to_vx_without_carry() {
+        // 0x8XY4: Adds VY to VX. VF is set to 1 when there's a carry, and to 0 when there isn't.
+        let program: Vec<u8> = vec![0x84, 0x54];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        assert_eq!(chip8.v[0xF], 0);
+
+        chip8.v[4] = 0xBA;
+        chip8.v[5] = 0x10;
+
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.v[4], 0xCA);
+        assert_eq!(chip8.v[0xF], 0);
+    }
+
+    #[test]
+    fn test_subtract_vy_from_vx_with_borrow() {
+        // 0x8XY5: VY is subtracted from VX. VF is set to 0 when there's a borrow,
+        // and 1 when there isn't.
+        let program: Vec<u8> = vec![0x84, 0x55];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        chip8.v[4] = 0xBA;
+        chip8.v[5] = 0xCC;
+
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.v[4], 0x11);
+        assert_eq!(chip8.v[0xF], 0);
+    }
+
+    #[test]
+    fn test_store_least_significant_bit_of_vx_in_vf_and_shift_vx_right_by_1() {
+        // 0x8XY6: Stores the least significant bit of VX in VF and then shifts VX to
+        // the right by 1.
+        let program: Vec<u8> = vec![0x84, 0x56];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        chip8.v[4] = 0xBB;
+        chip8.v[0xF] = 0x0;
+
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.v[4], 0x5D);
+        assert_eq!(chip8.v[0xF], 1);
+    }
+
+    #[test]
+    fn test_set_vx_to_vy_minus_vx_with_borrow() {
+        // 0x8XY7: Sets VX to VY minus VX. VF is set to 0 when there's a borrow, and 1
+        // when there isn't.
+        let program: Vec<u8> = vec![0x84, 0x57];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        chip8.v[4] = 0xCC;
+        chip8.v[5] = 0xBA;
+
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.v[4], 0x11);
+        assert_eq!(chip8.v[0xF], 0);
+    }
+
+    #[test]
+    fn test_store_most_significant_bit_of_vx_in_vf_and_shift_vx_right_by_1() {
+        // 0x8XYE: Stores the most significant bit of VX in VF and then shifts VX to the left by 1.
+        let program: Vec<u8> = vec![0x84, 0x5E];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        chip8.v[4] = 0xF0;
+        chip8.v[0xF] = 0x0;
+
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.v[4], 0xE0);
+        assert_eq!(chip8.v[0xF], 1);
+    }
+
+    #[test]
+    fn test_skip_next_instruction_if_vx_does_not_equal_vy_positive() {
+        // 0x9XY0: Skips the next instruction if VX doesn't equal VY. (Usually the next
+        // instruction is a jump to skip a code block)
+        let program: Vec<u8> = vec![0x94, 0x60];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        chip8.v[4] = 0x23;
+        chip8.v[6] = 0x17;
+
+        let orig_pc = chip8.pc;
+
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.pc, orig_pc + 4);
+    }
+
+    #[test]
+    fn test_skip_next_instruction_if_vx_does_not_equal_vy_negative() {
+        // 0x9XY0: Skips the next instruction if VX doesn't equal VY. (Usually the next
+        // instruction is a jump to skip a code block)
+        let program: Vec<u8> = vec![0x94, 0x60];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        chip8.v[4] = 0x17;
+        chip8.v[6] = 0x17;
+
+        let orig_pc = chip8.pc;
+
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.pc, orig_pc + 2);
+    }
+
+    #[test]
+    fn test_set_i_to_address_nnn() {
+        // 0xANNN: sets I to the address NNN
+        let program: Vec<u8> = vec![0xA0, 0xDC];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        assert_eq!(chip8.i, 0);
+
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.i, 0xDC);
+    }
+
+    #[test]
+    fn test_jump_to_nnn_plus_v0() {
+        // 0xBNNN: Jumps to the address NNN plus V0.
+        let program: Vec<u8> = vec![0xB0, 0xDC];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        assert_eq!(chip8.i, 0);
+
+        chip8.v[0] = 0x17;
+
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.pc, 0xF3);
+    }
+
+    #[test]
+    fn test_set_vx_to_random_and_nn() {
+        // 0xCXNN: Sets VX to the result of a bitwise and operation on a random number and NN.
+        let program: Vec<u8> = vec![0xC4, 0x0F, 0xC5, 0xF0];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        chip8.set_random_source(Box::new(ScriptedRandom::new(vec![0xAB, 0xCD])));
+
+        chip8.execute_cycle().unwrap();
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.v[4], 0x0B);
+        assert_eq!(chip8.v[5], 0xC0);
+    }
+
+    #[test]
+    fn test_seeded_random_is_reproducible() {
+        let program: Vec<u8> = vec![0xC4, 0xFF, 0x12, 0x00];
+
+        let run = || {
+            let mut chip8 = create_and_load(&program).unwrap();
+
+            chip8.seed(42);
+
+            (0..10)
+                .map(|_| {
+                    chip8.execute_cycle().unwrap();
+                    chip8.execute_cycle().unwrap();
+                    chip8.v[4]
+                })
+                .collect::<Vec<u8>>()
+        };
+
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn test_draw_sprite_at_x_y_with_height_n_with_no_collision() {
+        // 0xDXYN: Draws a sprite at coordinate (VX, VY) that has a width of 8 pixels
+        // and a height of N pixels.
+        let height = 5;
+        let start_x = 10;
+        let start_y = 10;
+
+        // Draw the 0 pixel at (10, 10)
+        let program: Vec<u8> = vec![0xD4, 0x65];
 
-        assert_eq!(chip8.pc, 0xDC);
-        assert_eq!(chip8.memory[chip8.pc as usize], 1);
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        let how_many_ones = chip8.gfx.iter().filter(|b| **b == 1).count();
+
+        assert_eq!(how_many_ones, 0);
+        assert_eq!(chip8.v[0xF], 0);
+
+        // set i to the first sprite in the font set (the number 0)
+        chip8.i = 0;
+        chip8.v[4] = start_x;
+        chip8.v[6] = start_y;
+
+        chip8.execute_cycle().unwrap();
+
+        let x_coord = (start_x % GRAPHICS_ROWS as u8) as usize;
+        let y_coord = (start_y % GRAPHICS_COLUMNS as u8) as usize;
+
+        let start_pixel = (y_coord * GRAPHICS_COLUMNS) + x_coord;
+        let end_pixel = start_pixel + (GRAPHICS_COLUMNS * height);
+
+        let how_many_ones = chip8.gfx[start_pixel..end_pixel]
+            .iter()
+            .filter(|b| **b == 1)
+            .count();
+
+        assert_eq!(how_many_ones, 14);
+        assert_eq!(chip8.v[0xF], 0);
+        assert!(chip8.draw_flag);
     }
 
     #[test]
-    fn test_call_subroutine_at_nnn() {
-        // 0x2NNN: calls subroutine at NNN
-        let program: Vec<u8> = vec![0x20, 0xDC];
+    fn test_draw_sprite_at_x_y_with_height_n_with_collision() {
+        // 0xDXYN: Draws a sprite at coordinate (VX, VY) that has a width of 8 pixels
+        // and a height of N pixels.
+        let height = 5;
+        let start_x = 10;
+        let start_y = 10;
+
+        // Draw the 0 pixel at (10, 10), twice, which should result in
+        // 0 pixels being set to 1, and the `chip8.v[0xF]` should be set to 1,
+        // indicating a collistion
+        let program: Vec<u8> = vec![0xD4, 0x65, 0xD4, 0x65];
 
         let mut chip8 = create_and_load(&program).unwrap();
 
-        assert_eq!(chip8.sp, 0);
+        let how_many_ones = chip8.gfx.iter().filter(|b| **b == 1).count();
 
-        chip8.execute_cycle();
+        assert_eq!(how_many_ones, 0);
+        assert_eq!(chip8.v[0xF], 0);
 
-        assert_eq!(chip8.pc, 0xDC);
-        assert_eq!(chip8.sp, 1);
-        assert_eq!(chip8.stack[0], 512);
+        // set i to the first sprite in the font set (the number 0)
+        chip8.i = 0;
+        chip8.v[4] = start_x;
+        chip8.v[6] = start_y;
+
+        // This will draw the `0`
+        chip8.execute_cycle().unwrap();
+
+        let x_coord = (start_x % GRAPHICS_ROWS as u8) as usize;
+        let y_coord = (start_y % GRAPHICS_COLUMNS as u8) as usize;
+
+        let start_pixel = (y_coord * GRAPHICS_COLUMNS) + x_coord;
+        let end_pixel = start_pixel + (GRAPHICS_COLUMNS * height);
+
+        let how_many_ones = chip8.gfx[start_pixel..end_pixel]
+            .iter()
+            .filter(|b| **b == 1)
+            .count();
+
+        assert_eq!(how_many_ones, 14);
+        assert_eq!(chip8.v[0xF], 0);
+        assert!(chip8.draw_flag);
+
+        // This will redraw the `0`, which should erase the previous one
+        chip8.execute_cycle().unwrap();
+
+        let x_coord = (start_x % GRAPHICS_ROWS as u8) as usize;
+        let y_coord = (start_y % GRAPHICS_COLUMNS as u8) as usize;
+
+        let start_pixel = (y_coord * GRAPHICS_COLUMNS) + x_coord;
+        let end_pixel = start_pixel + (GRAPHICS_COLUMNS * height);
+
+        let how_many_ones = chip8.gfx[start_pixel..end_pixel]
+            .iter()
+            .filter(|b| **b == 1)
+            .count();
+
+        assert_eq!(how_many_ones, 0);
+        assert_eq!(chip8.v[0xF], 1);
+        assert!(chip8.draw_flag);
     }
 
     #[test]
-    fn test_skip_next_instruction_if_vx_equals_nn_positive() {
-        // 0x3XNN: Skips the next instruction if VX equals NN.
-        let program: Vec<u8> = vec![0x34, 0x17];
+    fn test_skip_next_instruction_if_key_in_vx_is_pressed_positive() {
+        // 0xEX9E: Skips the next instruction if the key stored in VX is pressed.
+        let key_index: u8 = 0x4;
+        let program: Vec<u8> = vec![0xE4, 0x9E];
 
         let mut chip8 = create_and_load(&program).unwrap();
+        let keys_pressed = chip8.key.iter().filter(|k| **k == 1).count();
 
-        chip8.v[4] = 0x17;
+        assert_eq!(keys_pressed, 0);
 
         let orig_pc = chip8.pc;
 
-        chip8.execute_cycle();
+        chip8.v[4] = key_index;
+        chip8.key_down(key_index);
+
+        chip8.execute_cycle().unwrap();
+
+        // the key stays down until it is released
+        let keys_pressed = chip8.key.iter().filter(|k| **k == 1).count();
 
+        assert_eq!(keys_pressed, 1);
         assert_eq!(chip8.pc, orig_pc + 4);
     }
 
     #[test]
-    fn test_skip_next_instruction_if_vx_equals_nn_negative() {
-        // 0x3XNN: Skips the next instruction if VX equals NN.
-        let program: Vec<u8> = vec![0x34, 0x17];
+    fn test_skip_next_instruction_if_key_in_vx_is_pressed_negative() {
+        // 0xEX9E: Skips the next instruction if the key stored in VX is pressed.
+        let key_index: u8 = 0x4;
+        let program: Vec<u8> = vec![0xE4, 0x9E];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+        let keys_pressed = chip8.key.iter().filter(|k| **k == 1).count();
+
+        assert_eq!(keys_pressed, 0);
+
+        let orig_pc = chip8.pc;
+
+        chip8.v[4] = key_index;
+
+        chip8.execute_cycle().unwrap();
+
+        let keys_pressed = chip8.key.iter().filter(|k| **k == 1).count();
+
+        assert_eq!(keys_pressed, 0);
+        assert_eq!(chip8.pc, orig_pc + 2);
+    }
+
+    #[test]
+    fn test_skip_next_instruction_if_key_in_vx_is_not_pressed_positive() {
+        // 0xEXA1: Skips the next instruction if the key stored in VX isn't pressed.
+        let key_index: u8 = 0x4;
+        let program: Vec<u8> = vec![0xE4, 0xA1];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+        let keys_pressed = chip8.key.iter().filter(|k| **k == 1).count();
+
+        assert_eq!(keys_pressed, 0);
+
+        let orig_pc = chip8.pc;
+
+        chip8.v[4] = key_index;
+
+        chip8.execute_cycle().unwrap();
+
+        let keys_pressed = chip8.key.iter().filter(|k| **k == 1).count();
+
+        assert_eq!(keys_pressed, 0);
+        assert_eq!(chip8.pc, orig_pc + 4);
+    }
+
+    #[test]
+    fn test_skip_next_instruction_if_key_in_vx_is_not_pressed_negative() {
+        // 0xEXA1: Skips the next instruction if the key stored in VX isn't pressed.
+        let key_index: u8 = 0x4;
+        let program: Vec<u8> = vec![0xE4, 0xA1];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+        let keys_pressed = chip8.key.iter().filter(|k| **k == 1).count();
+
+        assert_eq!(keys_pressed, 0);
+
+        let orig_pc = chip8.pc;
+
+        chip8.v[4] = key_index;
+        chip8.key_down(key_index);
+
+        chip8.execute_cycle().unwrap();
+
+        // the key stays down until it is released
+        let keys_pressed = chip8.key.iter().filter(|k| **k == 1).count();
+
+        assert_eq!(keys_pressed, 1);
+        assert_eq!(chip8.pc, orig_pc + 2);
+    }
+
+    #[test]
+    fn test_set_vx_to_value_of_delay_timer() {
+        // 0xFX07: Sets VX to the value of the delay timer.
+        let test_value: u8 = 23;
+        let program: Vec<u8> = vec![0xF4, 0x07];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        assert_eq!(chip8.delay_timer, 0);
+
+        chip8.delay_timer = test_value;
+
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.v[4], test_value);
+    }
+
+    #[test]
+    fn test_wait_for_keypress() {
+        // 0xFX0A: A key press is awaited, and then stored in VX.
+        // (Blocking Operation. All instruction halted until next key event)
+        let key_index: u8 = 0x4;
+        let program: Vec<u8> = vec![0xF4, 0x0A];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+        let keys_pressed = chip8.key.iter().filter(|k| **k == 1).count();
+
+        assert_eq!(keys_pressed, 0);
+        assert_eq!(chip8.v[4], 0);
+        let orig_pc = chip8.pc;
+
+        // After this, everything should be just as it was, since no key has been pressed.
+        chip8.execute_cycle().unwrap();
+
+        let keys_pressed = chip8.key.iter().filter(|k| **k == 1).count();
+
+        assert_eq!(keys_pressed, 0);
+        assert_eq!(chip8.pc, orig_pc);
+
+        // Now press the key, and go again; nothing happens until it is released
+        chip8.key_down(key_index);
+
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.v[4], 0);
+        assert_eq!(chip8.pc, orig_pc);
+
+        // After releasing it, the key index should be in `chip8.v[4]`,
+        // and `self.pc` should have been advanced
+        chip8.key_up(key_index);
+
+        chip8.execute_cycle().unwrap();
+
+        let keys_pressed = chip8.key.iter().filter(|k| **k == 1).count();
+
+        assert_eq!(keys_pressed, 0);
+        assert_eq!(chip8.v[4], key_index);
+        assert_eq!(chip8.pc, orig_pc + 2);
+    }
+
+    #[test]
+    fn test_set_delay_timer_to_vx() {
+        // 0xFX15: Sets the delay timer to VX.
+        let program: Vec<u8> = vec![0xF4, 0x15];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        assert_eq!(chip8.delay_timer, 0);
+
+        chip8.v[4] = 0x17;
+
+        chip8.execute_cycle().unwrap();
+
+        // executing an instruction doesn't touch the timers
+        assert_eq!(chip8.delay_timer, 0x17);
+
+        chip8.tick_timers();
+
+        assert_eq!(chip8.delay_timer, 0x17 - 1);
+    }
+
+    #[test]
+    fn test_set_sound_timer_to_vx() {
+        // 0xFX18: Sets the delay timer to VX.
+        let program: Vec<u8> = vec![0xF4, 0x18];
+
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        assert_eq!(chip8.sound_timer, 0);
+
+        chip8.v[4] = 0x17;
+
+        chip8.execute_cycle().unwrap();
+
+        // executing an instruction doesn't touch the timers
+        assert_eq!(chip8.sound_timer, 0x17);
+
+        chip8.tick_timers();
+
+        assert_eq!(chip8.sound_timer, 0x17 - 1);
+    }
+
+    #[test]
+    fn test_add_vx_to_i_with_no_overflow() {
+        // 0xFX1E: Adds VX to I. VF is set to 1 when there is a range overflow (I+VX>0xFFF),
+        // and to 0 when there isn't.
+        let program: Vec<u8> = vec![0xF4, 0x1E];
 
         let mut chip8 = create_and_load(&program).unwrap();
 
-        chip8.v[4] = 0x23;
-
-        let orig_pc = chip8.pc;
+        chip8.i = 0xA;
+        chip8.v[4] = 0x17;
 
-        chip8.execute_cycle();
+        chip8.execute_cycle().unwrap();
 
-        assert_eq!(chip8.pc, orig_pc + 2);
+        assert_eq!(chip8.i, 0x21);
+        assert_eq!(chip8.v[0xF], 0);
     }
 
     #[test]
-    fn test_skip_next_instruction_if_vx_does_not_equal_nn_positive() {
-        // 0x4XNN: Skips the next instruction if VX doesn't equal NN.
-        let program: Vec<u8> = vec![0x44, 0x17];
+    fn test_add_vx_to_i_with_overflow() {
+        // 0xFX1E: Adds VX to I. VF is set to 1 when there is a range overflow (I+VX>0xFFF),
+        // and to 0 when there isn't.
+        let program: Vec<u8> = vec![0xF4, 0x1E];
 
         let mut chip8 = create_and_load(&program).unwrap();
 
-        chip8.v[4] = 0x23;
-
-        let orig_pc = chip8.pc;
+        chip8.i = 0xFFA;
+        chip8.v[4] = 0xA;
 
-        chip8.execute_cycle();
+        chip8.execute_cycle().unwrap();
 
-        assert_eq!(chip8.pc, orig_pc + 4);
+        assert_eq!(chip8.i, 0x1004);
+        assert_eq!(chip8.v[0xF], 1);
     }
 
     #[test]
-    fn test_skip_next_instruction_if_vx_does_not_equal_nn_negative() {
-        // 0x4XNN: Skips the next instruction if VX doesn't equal NN.
-        let program: Vec<u8> = vec![0x44, 0x17];
+    fn test_set_i_to_location_of_sprite_for_character_in_vx() {
+        // 0xFX29: Sets I to the location of the sprite for the character in VX.
+        // Characters 0-F (in hexadecimal) are represented by a 4x5 font.
+        let program: Vec<u8> = vec![0xF4, 0x29];
 
         let mut chip8 = create_and_load(&program).unwrap();
 
-        chip8.v[4] = 0x17;
-
-        let orig_pc = chip8.pc;
+        chip8.v[4] = 2;
 
-        chip8.execute_cycle();
+        chip8.execute_cycle().unwrap();
 
-        assert_eq!(chip8.pc, orig_pc + 2);
+        assert_eq!(chip8.i, 10);
     }
 
     #[test]
-    fn test_skip_next_instruction_if_vx_equals_vy_positive() {
-        // 0x5XY0: Skips the next instruction if VX equals VY.
-        let program: Vec<u8> = vec![0x54, 0x60];
+    fn test_store_binary_coded_decimal_representation_of_vx() {
+        // 0xFX33: Stores the binary-coded decimal representation of VX, with the most
+        // significant of three digits at the address in I, the middle digit at I plus 1,
+        // and the least significant digit at I plus 2.
+        let program: Vec<u8> = vec![0xF4, 0x33];
 
         let mut chip8 = create_and_load(&program).unwrap();
 
-        chip8.v[4] = 0x17;
-        chip8.v[6] = 0x17;
-
-        let orig_pc = chip8.pc;
+        let first_i = LOWER_MEMORY_BOUNDARY as u16;
+        chip8.i = first_i;
+        chip8.v[4] = 0xDC;
 
-        chip8.execute_cycle();
+        chip8.execute_cycle().unwrap();
 
-        assert_eq!(chip8.pc, orig_pc + 4);
+        assert_eq!(chip8.memory[first_i as usize], 2);
+        assert_eq!(chip8.memory[(first_i + 1) as usize], 2);
+        assert_eq!(chip8.memory[(first_i + 2) as usize], 0);
     }
 
     #[test]
-    fn test_skip_next_instruction_if_vx_equals_vy_negative() {
-        // 0x5XY0: Skips the next instruction if VX equals VY.
-        let program: Vec<u8> = vec![0x54, 0x60];
+    fn test_store_v0_to_vx_in_memory_starting_at_address_i() {
+        // 0xFX55: Stores V0 to VX (including VX) in memory starting at address I.
+        // The offset from I is increased by 1 for each value written, but I itself
+        // is left unmodified.
+
+        let program: Vec<u8> = vec![0xF4, 0x55];
 
         let mut chip8 = create_and_load(&program).unwrap();
 
-        chip8.v[4] = 0x17;
-        chip8.v[6] = 0x23;
+        let first_i = LOWER_MEMORY_BOUNDARY as u16;
+        chip8.i = first_i;
 
-        let orig_pc = chip8.pc;
+        for i in 0..5 {
+            chip8.v[i as usize] = i + 1;
+        }
 
-        chip8.execute_cycle();
+        chip8.execute_cycle().unwrap();
 
-        assert_eq!(chip8.pc, orig_pc + 2);
+        for i in 0..5 {
+            assert_eq!(chip8.memory[LOWER_MEMORY_BOUNDARY + i as usize], i + 1);
+        }
     }
 
     #[test]
-    fn test_set_vx_to_nn() {
-        // 0x6XNN: Sets VX to NN.
-        let program: Vec<u8> = vec![0x64, 0xAA];
+    fn test_fill_v0_to_vx_with_values_from_memory_starting_at_address_i() {
+        // 0xFX65: Fills V0 to VX (including VX) with values from memory
+        // starting at address I. The offset from I is increased by 1 for
+        // each value written, but I itself is left unmodified.
+        let program: Vec<u8> = vec![0xF4, 0x65];
 
-        let chip8 = create_and_load(&program).unwrap();
+        let mut chip8 = create_and_load(&program).unwrap();
 
-        assert_eq!(chip8.v[4], 0);
+        let first_i = (LOWER_MEMORY_BOUNDARY + 2) as u16;
+        chip8.i = first_i;
 
-        let mut chip8 = create_and_load(&program).unwrap();
+        for i in 0..5 {
+            chip8.memory[(first_i + i) as usize] = i as u8 + 1;
+        }
 
-        chip8.execute_cycle();
+        chip8.execute_cycle().unwrap();
 
-        assert_eq!(chip8.v[4], 0xAA);
+        for i in 0..5 {
+            assert_eq!(chip8.v[i as usize], i + 1);
+        }
     }
 
     #[test]
-    fn test_add_nn_to_vx() {
-        // 0x7XNN: Adds NN to VX. (Carry flag is not changed)
-        let program: Vec<u8> = vec![0x74, 0xAA];
+    fn test_unknown_opcode_faults() {
+        let program: Vec<u8> = vec![0x84, 0x5F];
 
         let mut chip8 = create_and_load(&program).unwrap();
 
-        chip8.v[4] = 0x10;
-
-        chip8.execute_cycle();
+        let fault = chip8.execute_cycle().unwrap_err();
 
-        assert_eq!(chip8.v[4], 0xBA);
+        assert_eq!(
+            fault,
+            Chip8Fault::UnknownOpcode {
+                pc: 0x200,
+                opcode: 0x845F
+            }
+        );
+        assert_eq!(chip8.pc, 0x200);
     }
 
     #[test]
-    fn test_add_nn_to_vx_wrapping() {
-        // 0x7XNN: Adds NN to VX. (Carry flag is not changed)
-        let program: Vec<u8> = vec![0x74, 0xAA];
+    fn test_xo_chip_instruction_faults_outside_xo_mode() {
+        let program: Vec<u8> = vec![0xF3, 0x01];
 
         let mut chip8 = create_and_load(&program).unwrap();
 
-        chip8.v[4] = 0xBA;
-
-        chip8.execute_cycle();
+        let fault = chip8.execute_cycle().unwrap_err();
 
-        assert_eq!(chip8.v[4], 0x64);
+        assert_eq!(
+            fault,
+            Chip8Fault::UnknownOpcode {
+                pc: 0x200,
+                opcode: 0xF301
+            }
+        );
     }
 
     #[test]
-    fn test_set_vx_to_value_of_vy() {
-        // 0x8XY0: Sets VX to the value of VY.
-        let program: Vec<u8> = vec![0x84, 0x50];
-
-        let mut chip8 = create_and_load(&program).unwrap();
+    fn test_execute_rejects_out_of_range_register() {
+        let mut chip8 = create_and_load(&[]).unwrap();
+
+        let fault = chip8
+            .execute(Instruction::SetImm { x: 20, nn: 1 })
+            .unwrap_err();
+
+        assert_eq!(
+            fault,
+            Chip8Fault::BadOperand {
+                pc: 0x200,
+                opcode: 0x6401
+            }
+        );
+        assert_eq!(chip8.pc, 0x200);
+    }
 
-        chip8.v[4] = 0xBA;
-        chip8.v[5] = 0xDD;
+    #[test]
+    fn test_execute_decoded_instruction() {
+        let mut chip8 = create_and_load(&[]).unwrap();
 
-        chip8.execute_cycle();
+        chip8
+            .execute(Instruction::SetImm { x: 0x3, nn: 0x42 })
+            .unwrap();
 
-        assert_eq!(chip8.v[4], 0xDD);
-        assert_eq!(chip8.v[5], 0xDD);
+        assert_eq!(chip8.v[3], 0x42);
+        assert_eq!(chip8.pc, 0x202);
     }
 
     #[test]
-    fn test_set_vx_to_vx_or_vy() {
-        // 0x8XY1: Sets VX to VX or VY. (Bitwise OR operation)
-        let program: Vec<u8> = vec![0x84, 0x51];
+    fn test_return_with_empty_stack_faults() {
+        let program: Vec<u8> = vec![0x00, 0xEE];
 
         let mut chip8 = create_and_load(&program).unwrap();
 
-        chip8.v[4] = 0xBA;
-        chip8.v[5] = 0xCC;
-
-        chip8.execute_cycle();
+        let fault = chip8.execute_cycle().unwrap_err();
 
-        assert_eq!(chip8.v[4], 0xFE);
-        assert_eq!(chip8.v[5], 0xCC);
+        assert_eq!(
+            fault,
+            Chip8Fault::StackUnderflow {
+                pc: 0x200,
+                opcode: 0x00EE
+            }
+        );
     }
 
     #[test]
-    fn test_set_vx_to_vx_and_vy() {
-        // 0x8XY2: Sets VX to VX and VY. (Bitwise AND operation)
-        let program: Vec<u8> = vec![0x84, 0x52];
+    fn test_call_with_full_stack_faults() {
+        // 0x2200: calls itself forever
+        let program: Vec<u8> = vec![0x22, 0x00];
 
         let mut chip8 = create_and_load(&program).unwrap();
 
-        chip8.v[4] = 0xBA;
-        chip8.v[5] = 0xCC;
+        for _ in 0..STACK_SIZE {
+            chip8.execute_cycle().unwrap();
+        }
 
-        chip8.execute_cycle();
+        let fault = chip8.execute_cycle().unwrap_err();
 
-        assert_eq!(chip8.v[4], 0x88);
-        assert_eq!(chip8.v[5], 0xCC);
+        assert_eq!(
+            fault,
+            Chip8Fault::StackOverflow {
+                pc: 0x200,
+                opcode: 0x2200
+            }
+        );
     }
 
     #[test]
-    fn test_set_vx_to_vx_xor_vy() {
-        // 0x8XY3: Sets VX to VX xor VY.
-        let program: Vec<u8> = vec![0x84, 0x53];
+    fn test_store_past_end_of_memory_faults() {
+        let program: Vec<u8> = vec![0xF4, 0x55];
 
         let mut chip8 = create_and_load(&program).unwrap();
 
-        chip8.v[4] = 0xBA;
-        chip8.v[5] = 0xCC;
+        chip8.i = 0xFFE;
 
-        chip8.execute_cycle();
+        let fault = chip8.execute_cycle().unwrap_err();
 
-        assert_eq!(chip8.v[4], 0x76);
-        assert_eq!(chip8.v[5], 0xCC);
+        assert_eq!(
+            fault,
+            Chip8Fault::MemoryOutOfRange {
+                pc: 0x200,
+                opcode: 0xF455,
+                address: 0x1000
+            }
+        );
     }
 
     #[test]
-    fn test_add_vy_to_vx_with_carry() {
-        // 0x8XY4: Adds VY to VX. VF is set to 1 when there's a carry, and to 0 when there isn't.
-        let program: Vec<u8> = vec![0x84, 0x54];
+    fn test_skip_on_bad_key_faults() {
+        let program: Vec<u8> = vec![0xE4, 0x9E];
 
         let mut chip8 = create_and_load(&program).unwrap();
 
-        assert_eq!(chip8.v[0xF], 0);
-
-        chip8.v[4] = 0xBA;
-        chip8.v[5] = 0xCC;
+        chip8.v[4] = 0x20;
 
-        chip8.execute_cycle();
+        let fault = chip8.execute_cycle().unwrap_err();
 
-        assert_eq!(chip8.v[4], 0x86);
-        assert_eq!(chip8.v[0xF], 1);
+        assert_eq!(
+            fault,
+            Chip8Fault::BadKey {
+                pc: 0x200,
+                opcode: 0xE49E,
+                key: 0x20
+            }
+        );
     }
 
     #[test]
-    fn test_add_vy_to_vx_without_carry() {
-        // 0x8XY4: Adds VY to VX. VF is set to 1 when there's a carry, and to 0 when there isn't.
-        let program: Vec<u8> = vec![0x84, 0x54];
+    fn test_wait_for_keypress_reports_waiting() {
+        let program: Vec<u8> = vec![0xF4, 0x0A];
 
         let mut chip8 = create_and_load(&program).unwrap();
 
-        assert_eq!(chip8.v[0xF], 0);
+        assert_eq!(chip8.execute_cycle(), Ok(StepOutcome::WaitingForKey));
 
-        chip8.v[4] = 0xBA;
-        chip8.v[5] = 0x10;
+        chip8.key_down(2);
 
-        chip8.execute_cycle();
+        assert_eq!(chip8.execute_cycle(), Ok(StepOutcome::WaitingForKey));
 
-        assert_eq!(chip8.v[4], 0xCA);
-        assert_eq!(chip8.v[0xF], 0);
+        chip8.key_up(2);
+
+        assert_eq!(chip8.execute_cycle(), Ok(StepOutcome::Executed));
     }
 
     #[test]
-    fn test_subtract_vy_from_vx_with_borrow() {
-        // 0x8XY5: VY is subtracted from VX. VF is set to 0 when there's a borrow,
-        // and 1 when there isn't.
-        let program: Vec<u8> = vec![0x84, 0x55];
+    fn test_shift_right_uses_vy_with_quirk() {
+        let program: Vec<u8> = vec![0x84, 0x56];
 
-        let mut chip8 = create_and_load(&program).unwrap();
+        let mut chip8 = create_and_load_with_quirks(&program, Quirks::COSMAC_VIP).unwrap();
 
-        chip8.v[4] = 0xBA;
-        chip8.v[5] = 0xCC;
+        chip8.v[4] = 0xFF;
+        chip8.v[5] = 0x02;
 
-        chip8.execute_cycle();
+        chip8.execute_cycle().unwrap();
 
-        assert_eq!(chip8.v[4], 0x11);
+        assert_eq!(chip8.v[4], 0x01);
         assert_eq!(chip8.v[0xF], 0);
     }
 
     #[test]
-    fn test_store_least_significant_bit_of_vx_in_vf_and_shift_vx_right_by_1() {
-        // 0x8XY6: Stores the least significant bit of VX in VF and then shifts VX to
-        // the right by 1.
-        let program: Vec<u8> = vec![0x84, 0x56];
+    fn test_shift_left_uses_vy_with_quirk() {
+        let program: Vec<u8> = vec![0x84, 0x5E];
 
-        let mut chip8 = create_and_load(&program).unwrap();
+        let mut chip8 = create_and_load_with_quirks(&program, Quirks::COSMAC_VIP).unwrap();
 
-        chip8.v[4] = 0xBB;
-        chip8.v[0xF] = 0x0;
+        chip8.v[4] = 0x01;
+        chip8.v[5] = 0x81;
 
-        chip8.execute_cycle();
+        chip8.execute_cycle().unwrap();
 
-        assert_eq!(chip8.v[4], 0x5D);
+        assert_eq!(chip8.v[4], 0x02);
         assert_eq!(chip8.v[0xF], 1);
     }
 
     #[test]
-    fn test_set_vx_to_vy_minus_vx_with_borrow() {
-        // 0x8XY7: Sets VX to VY minus VX. VF is set to 0 when there's a borrow, and 1
-        // when there isn't.
-        let program: Vec<u8> = vec![0x84, 0x57];
+    fn test_store_increments_i_with_quirk() {
+        let program: Vec<u8> = vec![0xF4, 0x55];
 
-        let mut chip8 = create_and_load(&program).unwrap();
+        let mut chip8 = create_and_load_with_quirks(&program, Quirks::COSMAC_VIP).unwrap();
 
-        chip8.v[4] = 0xCC;
-        chip8.v[5] = 0xBA;
+        chip8.i = 0x300;
 
-        chip8.execute_cycle();
+        chip8.execute_cycle().unwrap();
 
-        assert_eq!(chip8.v[4], 0x11);
-        assert_eq!(chip8.v[0xF], 0);
+        assert_eq!(chip8.i, 0x305);
     }
 
     #[test]
-    fn test_store_most_significant_bit_of_vx_in_vf_and_shift_vx_right_by_1() {
-        // 0x8XYE: Stores the most significant bit of VX in VF and then shifts VX to the left by 1.
-        let program: Vec<u8> = vec![0x84, 0x5E];
+    fn test_load_increments_i_by_x_on_chip_48() {
+        let program: Vec<u8> = vec![0xF4, 0x65];
 
-        let mut chip8 = create_and_load(&program).unwrap();
+        let mut chip8 = create_and_load_with_quirks(&program, Quirks::CHIP_48).unwrap();
 
-        chip8.v[4] = 0xF0;
-        chip8.v[0xF] = 0x0;
+        chip8.i = 0x300;
 
-        chip8.execute_cycle();
+        chip8.execute_cycle().unwrap();
 
-        assert_eq!(chip8.v[4], 0xE0);
-        assert_eq!(chip8.v[0xF], 1);
+        assert_eq!(chip8.i, 0x304);
     }
 
     #[test]
-    fn test_skip_next_instruction_if_vx_does_not_equal_vy_positive() {
-        // 0x9XY0: Skips the next instruction if VX doesn't equal VY. (Usually the next
-        // instruction is a jump to skip a code block)
-        let program: Vec<u8> = vec![0x94, 0x60];
-
-        let mut chip8 = create_and_load(&program).unwrap();
+    fn test_jump_uses_vx_with_quirk() {
+        let program: Vec<u8> = vec![0xB3, 0x00];
 
-        chip8.v[4] = 0x23;
-        chip8.v[6] = 0x17;
+        let mut chip8 = create_and_load_with_quirks(&program, Quirks::SUPER_CHIP).unwrap();
 
-        let orig_pc = chip8.pc;
+        chip8.v[0] = 0x10;
+        chip8.v[3] = 0x04;
 
-        chip8.execute_cycle();
+        chip8.execute_cycle().unwrap();
 
-        assert_eq!(chip8.pc, orig_pc + 4);
+        assert_eq!(chip8.pc, 0x304);
     }
 
     #[test]
-    fn test_skip_next_instruction_if_vx_does_not_equal_vy_negative() {
-        // 0x9XY0: Skips the next instruction if VX doesn't equal VY. (Usually the next
-        // instruction is a jump to skip a code block)
-        let program: Vec<u8> = vec![0x94, 0x60];
-
-        let mut chip8 = create_and_load(&program).unwrap();
+    fn test_logic_resets_vf_with_quirk() {
+        let program: Vec<u8> = vec![0x84, 0x51];
 
-        chip8.v[4] = 0x17;
-        chip8.v[6] = 0x17;
+        let mut chip8 = create_and_load_with_quirks(&program, Quirks::COSMAC_VIP).unwrap();
 
-        let orig_pc = chip8.pc;
+        chip8.v[0xF] = 1;
 
-        chip8.execute_cycle();
+        chip8.execute_cycle().unwrap();
 
-        assert_eq!(chip8.pc, orig_pc + 2);
+        assert_eq!(chip8.v[0xF], 0);
     }
 
     #[test]
-    fn test_set_i_to_address_nnn() {
-        // 0xANNN: sets I to the address NNN
-        let program: Vec<u8> = vec![0xA0, 0xDC];
+    fn test_sprites_clip_at_edge_with_quirk() {
+        // draw the `0` glyph so it hangs off the right edge of the screen
+        let program: Vec<u8> = vec![0xD4, 0x65];
 
-        let mut chip8 = create_and_load(&program).unwrap();
+        let mut chip8 = create_and_load_with_quirks(&program, Quirks::COSMAC_VIP).unwrap();
 
-        assert_eq!(chip8.i, 0);
+        chip8.i = 0;
+        chip8.v[4] = (GRAPHICS_COLUMNS - 2) as u8;
+        chip8.v[6] = 0;
 
-        chip8.execute_cycle();
+        chip8.execute_cycle().unwrap();
 
-        assert_eq!(chip8.i, 0xDC);
+        // nothing should have wrapped around to the left-hand column
+        let left_column = (0..GRAPHICS_ROWS).any(|r| chip8.gfx[r * GRAPHICS_COLUMNS] == 1);
+
+        assert!(!left_column);
     }
 
     #[test]
-    fn test_jump_to_nnn_plus_v0() {
-        // 0xBNNN: Jumps to the address NNN plus V0.
-        let program: Vec<u8> = vec![0xB0, 0xDC];
+    fn test_dirty_region() {
+        // draw a `0` at (10, 4), another one wrapping round the right edge,
+        // then clear the screen
+        let program: Vec<u8> = vec![0xD4, 0x65, 0xD5, 0x65, 0x00, 0xE0];
 
         let mut chip8 = create_and_load(&program).unwrap();
 
-        assert_eq!(chip8.i, 0);
-
-        chip8.v[0] = 0x17;
+        chip8.i = 0;
+        chip8.v[4] = 10;
+        chip8.v[5] = (GRAPHICS_COLUMNS - 2) as u8;
+        chip8.v[6] = 4;
 
-        chip8.execute_cycle();
+        assert_eq!(chip8.take_dirty_region(), None);
 
-        assert_eq!(chip8.pc, 0xF3);
-    }
+        chip8.execute_cycle().unwrap();
 
-    #[test]
-    fn test_draw_sprite_at_x_y_with_height_n_with_no_collision() {
-        // 0xDXYN: Draws a sprite at coordinate (VX, VY) that has a width of 8 pixels
-        // and a height of N pixels.
-        let height = 5;
-        let start_x = 10;
-        let start_y = 10;
+        assert_eq!(chip8.framebuffer().dirty, Region::new(10, 4, 4, 5));
+        assert_eq!(chip8.take_dirty_region(), Some(Region::new(10, 4, 4, 5)));
+        assert_eq!(chip8.take_dirty_region(), None);
 
-        // Draw the 0 pixel at (10, 10)
-        let program: Vec<u8> = vec![0xD4, 0x65];
+        chip8.execute_cycle().unwrap();
 
-        let mut chip8 = create_and_load(&program).unwrap();
+        assert_eq!(
+            chip8.take_dirty_region(),
+            Some(Region::new(0, 4, GRAPHICS_COLUMNS, 5))
+        );
 
-        let how_many_ones = chip8.gfx.iter().filter(|b| **b == 1).count();
+        chip8.execute_cycle().unwrap();
 
-        assert_eq!(how_many_ones, 0);
-        assert_eq!(chip8.v[0xF], 0);
+        assert_eq!(
+            chip8.take_dirty_region(),
+            Some(Region::new(0, 0, GRAPHICS_COLUMNS, GRAPHICS_ROWS))
+        );
+    }
 
-        // set i to the first sprite in the font set (the number 0)
-        chip8.i = 0;
-        chip8.v[4] = start_x;
-        chip8.v[6] = start_y;
+    #[test]
+    fn test_quirks_from_name() {
+        assert_eq!(Quirks::from_name("VIP"), Some(Quirks::COSMAC_VIP));
+        assert_eq!(Quirks::from_name("schip"), Some(Quirks::SUPER_CHIP));
+        assert_eq!(Quirks::from_name("nope"), None);
 
-        chip8.execute_cycle();
+        for name in Quirks::PROFILE_NAMES {
+            assert_eq!(Quirks::from_name(name).unwrap().name(), Some(name));
+        }
+    }
 
-        let x_coord = (start_x % GRAPHICS_ROWS as u8) as usize;
-        let y_coord = (start_y % GRAPHICS_COLUMNS as u8) as usize;
+    #[test]
+    fn test_switch_to_hires_and_back() {
+        // 0x00FF: hi-res, 0x00FE: lo-res
+        let program: Vec<u8> = vec![0x00, 0xFF, 0x00, 0xFE];
 
-        let start_pixel = ((y_coord * GRAPHICS_COLUMNS) + x_coord) as usize;
-        let end_pixel = start_pixel + (GRAPHICS_COLUMNS * height);
+        let mut chip8 = create_and_load(&program).unwrap();
 
-        let how_many_ones = chip8.gfx[start_pixel..end_pixel]
-            .iter()
-            .filter(|b| **b == 1)
-            .count();
+        chip8.gfx[0] = 1;
 
-        assert_eq!(how_many_ones, 14);
-        assert_eq!(chip8.v[0xF], 0);
+        chip8.execute_cycle().unwrap();
+
+        assert!(chip8.hires());
+        assert_eq!(chip8.width(), HIRES_GRAPHICS_COLUMNS);
+        assert_eq!(chip8.height(), HIRES_GRAPHICS_ROWS);
+        assert_eq!(chip8.gfx[0], 0);
         assert!(chip8.draw_flag);
+
+        chip8.execute_cycle().unwrap();
+
+        assert!(!chip8.hires());
+        assert_eq!(chip8.width(), GRAPHICS_COLUMNS);
+        assert_eq!(chip8.height(), GRAPHICS_ROWS);
     }
 
     #[test]
-    fn test_draw_sprite_at_x_y_with_height_n_with_collision() {
-        // 0xDXYN: Draws a sprite at coordinate (VX, VY) that has a width of 8 pixels
-        // and a height of N pixels.
-        let height = 5;
-        let start_x = 10;
-        let start_y = 10;
-
-        // Draw the 0 pixel at (10, 10), twice, which should result in
-        // 0 pixels being set to 1, and the `chip8.v[0xF]` should be set to 1,
-        // indicating a collistion
-        let program: Vec<u8> = vec![0xD4, 0x65, 0xD4, 0x65];
+    fn test_scroll_down_n_lines() {
+        // 0x00CN: scroll down N lines
+        let program: Vec<u8> = vec![0x00, 0xC3];
 
         let mut chip8 = create_and_load(&program).unwrap();
 
-        let how_many_ones = chip8.gfx.iter().filter(|b| **b == 1).count();
+        chip8.gfx[5] = 1;
 
-        assert_eq!(how_many_ones, 0);
-        assert_eq!(chip8.v[0xF], 0);
+        chip8.execute_cycle().unwrap();
 
-        // set i to the first sprite in the font set (the number 0)
-        chip8.i = 0;
-        chip8.v[4] = start_x;
-        chip8.v[6] = start_y;
+        assert_eq!(chip8.gfx[5], 0);
+        assert!(chip8.pixel(5, 3));
+    }
 
-        // This will draw the `0`
-        chip8.execute_cycle();
+    #[test]
+    fn test_scroll_right_and_left() {
+        // 0x00FB: scroll right 4 pixels, 0x00FC: scroll left 4 pixels
+        let program: Vec<u8> = vec![0x00, 0xFB, 0x00, 0xFC];
 
-        let x_coord = (start_x % GRAPHICS_ROWS as u8) as usize;
-        let y_coord = (start_y % GRAPHICS_COLUMNS as u8) as usize;
+        let mut chip8 = create_and_load(&program).unwrap();
 
-        let start_pixel = ((y_coord * GRAPHICS_COLUMNS) + x_coord) as usize;
-        let end_pixel = start_pixel + (GRAPHICS_COLUMNS * height);
+        chip8.gfx[GRAPHICS_COLUMNS - 1] = 1;
+        chip8.gfx[GRAPHICS_COLUMNS + 2] = 1;
 
-        let how_many_ones = chip8.gfx[start_pixel..end_pixel]
-            .iter()
-            .filter(|b| **b == 1)
-            .count();
+        chip8.execute_cycle().unwrap();
 
-        assert_eq!(how_many_ones, 14);
-        assert_eq!(chip8.v[0xF], 0);
-        assert!(chip8.draw_flag);
+        assert!(!chip8.pixel(GRAPHICS_COLUMNS - 1, 0));
+        assert!(chip8.pixel(6, 1));
 
-        // This will redraw the `0`, which should erase the previous one
-        chip8.execute_cycle();
+        chip8.execute_cycle().unwrap();
 
-        let x_coord = (start_x % GRAPHICS_ROWS as u8) as usize;
-        let y_coord = (start_y % GRAPHICS_COLUMNS as u8) as usize;
+        assert!(chip8.pixel(2, 1));
+        assert!(!chip8.pixel(6, 1));
+    }
 
-        let start_pixel = ((y_coord * GRAPHICS_COLUMNS) + x_coord) as usize;
-        let end_pixel = start_pixel + (GRAPHICS_COLUMNS * height);
+    #[test]
+    fn test_exit_interpreter() {
+        // 0x00FD: exit the interpreter
+        let program: Vec<u8> = vec![0x00, 0xFD];
 
-        let how_many_ones = chip8.gfx[start_pixel..end_pixel]
-            .iter()
-            .filter(|b| **b == 1)
-            .count();
+        let mut chip8 = create_and_load(&program).unwrap();
 
-        assert_eq!(how_many_ones, 0);
-        assert_eq!(chip8.v[0xF], 1);
-        assert!(chip8.draw_flag);
+        assert_eq!(chip8.execute_cycle(), Ok(StepOutcome::Exited));
     }
 
     #[test]
-    fn test_skip_next_instruction_if_key_in_vx_is_pressed_positive() {
-        // 0xEX9E: Skips the next instruction if the key stored in VX is pressed.
-        let key_index: u8 = 0x4;
-        let program: Vec<u8> = vec![0xE4, 0x9E];
+    fn test_draw_16x16_sprite_in_hires() {
+        // 0xDXY0: Draws a 16x16 sprite
+        let program: Vec<u8> = vec![0x00, 0xFF, 0xD0, 0x10];
 
         let mut chip8 = create_and_load(&program).unwrap();
-        let keys_pressed = chip8.key.iter().filter(|k| **k == 1).count();
 
-        assert_eq!(keys_pressed, 0);
+        chip8.i = 0x300;
 
-        let orig_pc = chip8.pc;
+        for b in 0..32 {
+            chip8.memory[0x300 + b] = 0xFF;
+        }
 
-        chip8.v[4] = key_index;
-        chip8.key[key_index as usize] = 1;
+        chip8.v[0] = 100;
+        chip8.v[1] = 40;
 
-        chip8.execute_cycle();
+        chip8.execute_cycle().unwrap();
+        chip8.execute_cycle().unwrap();
 
-        let keys_pressed = chip8.key.iter().filter(|k| **k == 1).count();
+        let how_many_ones = chip8.gfx.iter().filter(|b| **b == 1).count();
 
-        assert_eq!(keys_pressed, 0);
-        assert_eq!(chip8.pc, orig_pc + 4);
+        assert_eq!(how_many_ones, 256);
+        assert!(chip8.pixel(100, 40));
+        assert!(chip8.pixel(115, 55));
+        assert!(!chip8.pixel(116, 55));
     }
 
     #[test]
-    fn test_skip_next_instruction_if_key_in_vx_is_pressed_negative() {
-        // 0xEX9E: Skips the next instruction if the key stored in VX is pressed.
-        let key_index: u8 = 0x4;
-        let program: Vec<u8> = vec![0xE4, 0x9E];
+    fn test_set_i_to_big_font_sprite() {
+        // 0xFX30: Sets I to the location of the 8x10 sprite for the digit in VX
+        let program: Vec<u8> = vec![0xF4, 0x30];
 
         let mut chip8 = create_and_load(&program).unwrap();
-        let keys_pressed = chip8.key.iter().filter(|k| **k == 1).count();
-
-        assert_eq!(keys_pressed, 0);
-
-        let orig_pc = chip8.pc;
 
-        chip8.v[4] = key_index;
-
-        chip8.execute_cycle();
+        chip8.v[4] = 3;
 
-        let keys_pressed = chip8.key.iter().filter(|k| **k == 1).count();
+        chip8.execute_cycle().unwrap();
 
-        assert_eq!(keys_pressed, 0);
-        assert_eq!(chip8.pc, orig_pc + 2);
+        assert_eq!(chip8.i as usize, BIG_FONT_ADDRESS + 30);
     }
 
     #[test]
-    fn test_skip_next_instruction_if_key_in_vx_is_not_pressed_positive() {
-        // 0xEXA1: Skips the next instruction if the key stored in VX isn't pressed.
-        let key_index: u8 = 0x4;
-        let program: Vec<u8> = vec![0xE4, 0xA1];
+    fn test_save_and_restore_rpl_flags() {
+        // 0xFX75: save V0-VX to the RPL flags, 0xFX85: restore them
+        let program: Vec<u8> = vec![0xF2, 0x75, 0x60, 0x00, 0xF2, 0x85];
 
         let mut chip8 = create_and_load(&program).unwrap();
-        let keys_pressed = chip8.key.iter().filter(|k| **k == 1).count();
-
-        assert_eq!(keys_pressed, 0);
 
-        let orig_pc = chip8.pc;
+        chip8.v[0] = 7;
+        chip8.v[1] = 8;
+        chip8.v[2] = 9;
 
-        chip8.v[4] = key_index;
+        chip8.execute_cycle().unwrap();
+        chip8.execute_cycle().unwrap();
 
-        chip8.execute_cycle();
+        assert_eq!(chip8.v[0], 0);
 
-        let keys_pressed = chip8.key.iter().filter(|k| **k == 1).count();
+        chip8.execute_cycle().unwrap();
 
-        assert_eq!(keys_pressed, 0);
-        assert_eq!(chip8.pc, orig_pc + 4);
+        assert_eq!(&chip8.v[..3], &[7, 8, 9]);
     }
 
     #[test]
-    fn test_skip_next_instruction_if_key_in_vx_is_not_pressed_negative() {
-        // 0xEXA1: Skips the next instruction if the key stored in VX isn't pressed.
-        let key_index: u8 = 0x4;
-        let program: Vec<u8> = vec![0xE4, 0xA1];
-
-        let mut chip8 = create_and_load(&program).unwrap();
-        let keys_pressed = chip8.key.iter().filter(|k| **k == 1).count();
+    fn test_xo_chip_program_can_use_64k() {
+        let program: Vec<u8> = vec![0; 8192];
 
-        assert_eq!(keys_pressed, 0);
+        let mut chip8 = Chip8::new_xo_chip(Quirks::default());
 
-        let orig_pc = chip8.pc;
+        assert!(chip8.load_program(program).is_ok());
+        assert_eq!(chip8.memory.len(), XO_CHIP_MEMORY_SIZE);
+    }
 
-        chip8.v[4] = key_index;
-        chip8.key[key_index as usize] = 1;
+    #[test]
+    fn test_long_load_i() {
+        // 0xF000 NNNN: Sets I to NNNN
+        let program: Vec<u8> = vec![0xF0, 0x00, 0xBE, 0xEF];
 
-        chip8.execute_cycle();
+        let mut chip8 = create_and_load_xo_chip(&program);
 
-        let keys_pressed = chip8.key.iter().filter(|k| **k == 1).count();
+        chip8.execute_cycle().unwrap();
 
-        assert_eq!(keys_pressed, 0);
-        assert_eq!(chip8.pc, orig_pc + 2);
+        assert_eq!(chip8.i, 0xBEEF);
+        assert_eq!(chip8.pc, 0x204);
     }
 
     #[test]
-    fn test_set_vx_to_value_of_delay_timer() {
-        // 0xFX07: Sets VX to the value of the delay timer.
-        let test_value: u8 = 23;
-        let program: Vec<u8> = vec![0xF4, 0x07];
+    fn test_long_load_i_is_unknown_outside_xo_chip() {
+        let program: Vec<u8> = vec![0xF0, 0x00, 0xBE, 0xEF];
 
         let mut chip8 = create_and_load(&program).unwrap();
 
-        assert_eq!(chip8.delay_timer, 0);
+        assert!(chip8.execute_cycle().is_err());
+    }
 
-        chip8.delay_timer = test_value;
+    #[test]
+    fn test_skip_over_long_load_i() {
+        // 0x3XNN followed by 0xF000 NNNN should skip all four bytes
+        let program: Vec<u8> = vec![0x34, 0x00, 0xF0, 0x00, 0xBE, 0xEF];
 
-        chip8.execute_cycle();
+        let mut chip8 = create_and_load_xo_chip(&program);
 
-        assert_eq!(chip8.v[4], test_value);
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.pc, 0x206);
     }
 
     #[test]
-    fn test_wait_for_keypress() {
-        // 0xFX0A: A key press is awaited, and then stored in VX.
-        // (Blocking Operation. All instruction halted until next key event)
-        let key_index: u8 = 0x4;
-        let program: Vec<u8> = vec![0xF4, 0x0A];
-
-        let mut chip8 = create_and_load(&program).unwrap();
-        let keys_pressed = chip8.key.iter().filter(|k| **k == 1).count();
-
-        assert_eq!(keys_pressed, 0);
-        assert_eq!(chip8.v[4], 0);
-        let orig_pc = chip8.pc;
+    fn test_xo_chip_addresses_wrap_at_64k() {
+        // 0xF000 0xFFFF: I := 0xFFFF, then 0x6005: V0 := 5, 0xF01E: I += V0
+        let program: Vec<u8> = vec![0xF0, 0x00, 0xFF, 0xFF, 0x60, 0x05, 0xF0, 0x1E];
 
-        // After this, everything should be just as it was, since no key has been pressed.
-        chip8.execute_cycle();
+        let mut chip8 = create_and_load_xo_chip(&program);
 
-        let keys_pressed = chip8.key.iter().filter(|k| **k == 1).count();
+        for _ in 0..3 {
+            chip8.execute_cycle().unwrap();
+        }
 
-        assert_eq!(keys_pressed, 0);
-        assert_eq!(chip8.pc, orig_pc);
+        assert_eq!(chip8.i, 0x0004);
+        assert_eq!(chip8.v[0xF], 1);
 
-        // Now set the key, and go again
-        chip8.key[key_index as usize] = 1;
+        // the program counter runs off the end of memory back to the start
+        chip8.pc = 0xFFFE;
+        chip8.memory[0xFFFE] = 0x60;
+        chip8.execute_cycle().unwrap();
 
-        // After this time, the key index should be in `chip8.v[4]`,
-        // the `chip8.key` array should be all `0`, and `self.pc` should have been advanced
-        chip8.execute_cycle();
+        assert_eq!(chip8.pc, 0x0000);
 
-        let keys_pressed = chip8.key.iter().filter(|k| **k == 1).count();
+        // and so does a skip
+        chip8.pc = 0xFFFC;
+        chip8.memory[0xFFFC] = 0x30;
+        chip8.memory[0xFFFD] = 0x00;
+        chip8.execute_cycle().unwrap();
 
-        assert_eq!(keys_pressed, 0);
-        assert_eq!(chip8.v[4], key_index);
-        assert_eq!(chip8.pc, orig_pc + 2);
+        assert_eq!(chip8.pc, 0x0000);
     }
 
     #[test]
-    fn test_set_delay_timer_to_vx() {
-        // 0xFX15: Sets the delay timer to VX.
-        let program: Vec<u8> = vec![0xF4, 0x15];
+    fn test_xo_chip_data_wraps_at_64k() {
+        // 0xF233: BCD of V2 at I, then 0xF165: load it back into V0 and V1
+        let program: Vec<u8> = vec![0xF2, 0x33, 0xF1, 0x65];
 
-        let mut chip8 = create_and_load(&program).unwrap();
+        let mut chip8 = create_and_load_xo_chip(&program);
 
-        assert_eq!(chip8.delay_timer, 0);
+        chip8.i = 0xFFFF;
+        chip8.v[2] = 123;
 
-        chip8.v[4] = 0x17;
+        chip8.execute_cycle().unwrap();
 
-        chip8.execute_cycle();
+        assert_eq!(chip8.memory[0xFFFF], 1);
+        assert_eq!(&chip8.memory[..2], &[2, 3]);
 
-        // the value is 1 less than what was set, because
-        // the `process_timers` method has been called
-        assert_eq!(chip8.delay_timer, 0x17 - 1);
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(&chip8.v[..2], &[1, 2]);
+
+        // the classic 4 KiB memory still faults past its end
+        let mut chip8 = create_and_load(&program).unwrap();
+
+        chip8.i = 0xFFF;
+
+        assert!(matches!(
+            chip8.execute_cycle(),
+            Err(Chip8Fault::MemoryOutOfRange {
+                address: 0x1000,
+                ..
+            })
+        ));
     }
 
     #[test]
-    fn test_set_sound_timer_to_vx() {
-        // 0xFX18: Sets the delay timer to VX.
-        let program: Vec<u8> = vec![0xF4, 0x18];
+    fn test_save_and_load_register_range() {
+        // 0x5XY2: save V2-V4, 0x5XY3: load them back reversed into V4-V2
+        let program: Vec<u8> = vec![0x52, 0x42, 0x54, 0x23];
 
-        let mut chip8 = create_and_load(&program).unwrap();
+        let mut chip8 = create_and_load_xo_chip(&program);
 
-        assert_eq!(chip8.sound_timer, 0);
+        chip8.i = 0x300;
+        chip8.v[2] = 1;
+        chip8.v[3] = 2;
+        chip8.v[4] = 3;
 
-        chip8.v[4] = 0x17;
+        chip8.execute_cycle().unwrap();
 
-        chip8.execute_cycle();
+        assert_eq!(&chip8.memory[0x300..0x303], &[1, 2, 3]);
+        assert_eq!(chip8.i, 0x300);
 
-        // the value is 1 less than what was set, because
-        // the `process_timers` method has been called
-        assert_eq!(chip8.sound_timer, 0x17 - 1);
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(&chip8.v[2..5], &[3, 2, 1]);
     }
 
     #[test]
-    fn test_add_vx_to_i_with_no_overflow() {
-        // 0xFX1E: Adds VX to I. VF is set to 1 when there is a range overflow (I+VX>0xFFF),
-        // and to 0 when there isn't.
-        let program: Vec<u8> = vec![0xF4, 0x1E];
+    fn test_draw_on_both_planes() {
+        // 0xF301: select both planes, then draw a 1-line sprite
+        let program: Vec<u8> = vec![0xF3, 0x01, 0xD0, 0x01, 0xF2, 0x01, 0x00, 0xE0];
 
-        let mut chip8 = create_and_load(&program).unwrap();
+        let mut chip8 = create_and_load_xo_chip(&program);
 
-        chip8.i = 0xA;
-        chip8.v[4] = 0x17;
+        chip8.i = 0x300;
+        chip8.memory[0x300] = 0xC0;
+        chip8.memory[0x301] = 0x80;
 
-        chip8.execute_cycle();
+        chip8.execute_cycle().unwrap();
+        chip8.execute_cycle().unwrap();
 
-        assert_eq!(chip8.i, 0x21);
-        assert_eq!(chip8.v[0xF], 0);
+        assert_eq!(chip8.pixel_color(0, 0), 3);
+        assert_eq!(chip8.pixel_color(1, 0), 1);
+
+        // clearing with only plane 2 selected leaves plane 1 alone
+        chip8.execute_cycle().unwrap();
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.pixel_color(0, 0), 1);
+        assert_eq!(chip8.pixel_color(1, 0), 1);
     }
 
     #[test]
-    fn test_add_vx_to_i_with_overflow() {
-        // 0xFX1E: Adds VX to I. VF is set to 1 when there is a range overflow (I+VX>0xFFF),
-        // and to 0 when there isn't.
-        let program: Vec<u8> = vec![0xF4, 0x1E];
+    fn test_load_audio_pattern_and_pitch() {
+        // 0xF002: load the audio pattern, 0xFX3A: set the pitch
+        let program: Vec<u8> = vec![0xF0, 0x02, 0xF4, 0x3A];
 
-        let mut chip8 = create_and_load(&program).unwrap();
+        let mut chip8 = create_and_load_xo_chip(&program);
 
-        chip8.i = 0xFFA;
-        chip8.v[4] = 0xA;
+        chip8.i = 0x300;
 
-        chip8.execute_cycle();
+        for b in 0..16 {
+            chip8.memory[0x300 + b] = b as u8;
+        }
 
-        assert_eq!(chip8.i, 0x1004);
-        assert_eq!(chip8.v[0xF], 1);
+        chip8.v[4] = 112;
+
+        chip8.execute_cycle().unwrap();
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(chip8.audio_pattern()[15], 15);
+        assert_eq!(chip8.pitch(), 112);
+        assert!((chip8.playback_rate() - 8000.0).abs() < 0.001);
     }
 
     #[test]
-    fn test_set_i_to_location_of_sprite_for_character_in_vx() {
-        // 0xFX29: Sets I to the location of the sprite for the character in VX.
-        // Characters 0-F (in hexadecimal) are represented by a 4x5 font.
-        let program: Vec<u8> = vec![0xF4, 0x29];
+    fn test_run_frame_ticks_timers_once() {
+        // 0x1200: jump to self forever
+        let program: Vec<u8> = vec![0x12, 0x00];
 
         let mut chip8 = create_and_load(&program).unwrap();
 
-        chip8.v[4] = 2;
+        chip8.delay_timer = 10;
+        chip8.sound_timer = 10;
 
-        chip8.execute_cycle();
+        assert_eq!(chip8.run_frame(50), Ok(StepOutcome::Executed));
 
-        assert_eq!(chip8.i, 10);
+        assert_eq!(chip8.delay_timer, 9);
+        assert_eq!(chip8.sound_timer, 9);
     }
 
     #[test]
-    fn test_store_binary_coded_decimal_representation_of_vx() {
-        // 0xFX33: Stores the binary-coded decimal representation of VX, with the most
-        // significant of three digits at the address in I, the middle digit at I plus 1,
-        // and the least significant digit at I plus 2.
-        let program: Vec<u8> = vec![0xF4, 0x33];
+    fn test_run_frame_within_budget() {
+        // 0x1200: jump to self forever
+        let program: Vec<u8> = vec![0x12, 0x00];
 
         let mut chip8 = create_and_load(&program).unwrap();
 
-        let first_i = LOWER_MEMORY_BOUNDARY as u16;
-        chip8.i = first_i;
-        chip8.v[4] = 0xDC;
+        chip8.delay_timer = 10;
 
-        chip8.execute_cycle();
+        assert_eq!(
+            chip8.run_frame_within(50, Some(7)),
+            Ok(StepOutcome::Executed)
+        );
+        assert_eq!(chip8.cycles(), 7);
+        assert_eq!(chip8.delay_timer, 9);
 
-        assert_eq!(chip8.memory[first_i as usize], 2);
-        assert_eq!(chip8.memory[(first_i + 1) as usize], 2);
-        assert_eq!(chip8.memory[(first_i + 2) as usize], 0);
+        chip8.run_frame_within(50, Some(100)).unwrap();
+
+        assert_eq!(chip8.cycles(), 57);
     }
 
     #[test]
-    fn test_store_v0_to_vx_in_memory_starting_at_address_i() {
-        // 0xFX55: Stores V0 to VX (including VX) in memory starting at address I.
-        // The offset from I is increased by 1 for each value written, but I itself
-        // is left unmodified.
-
-        let program: Vec<u8> = vec![0xF4, 0x55];
+    fn test_run_frame_stops_on_exit() {
+        // 0x00FD: exit
+        let program: Vec<u8> = vec![0x60, 0x01, 0x00, 0xFD, 0x60, 0x02];
 
         let mut chip8 = create_and_load(&program).unwrap();
 
-        let first_i = LOWER_MEMORY_BOUNDARY as u16;
-        chip8.i = first_i;
-
-        for i in 0..5 {
-            chip8.v[i as usize] = i + 1;
-        }
-
-        chip8.execute_cycle();
-
-        for i in 0..5 {
-            assert_eq!(chip8.memory[LOWER_MEMORY_BOUNDARY + i as usize], i + 1);
-        }
+        assert_eq!(chip8.run_frame(10), Ok(StepOutcome::Exited));
+        assert_eq!(chip8.v[0], 1);
     }
 
     #[test]
-    fn test_fill_v0_to_vx_with_values_from_memory_starting_at_address_i() {
-        // 0xFX65: Fills V0 to VX (including VX) with values from memory
-        // starting at address I. The offset from I is increased by 1 for
-        // each value written, but I itself is left unmodified.
-        let program: Vec<u8> = vec![0xF4, 0x65];
+    fn test_memory_accesses() {
+        // 0xA300: I = 0x300; 0xF133: BCD of V1; 0xD012: draw 2 rows from I
+        let program: Vec<u8> = vec![0xA3, 0x00, 0xF1, 0x33, 0xD0, 0x12];
 
         let mut chip8 = create_and_load(&program).unwrap();
+        chip8.v[1] = 123;
+
+        // nothing is recorded until tracking is turned on
+        chip8.execute_cycle().unwrap();
+        chip8.execute_cycle().unwrap();
+        assert!(chip8.memory_accesses().is_empty());
+
+        chip8.set_access_tracking(true);
+        chip8.execute_cycle().unwrap();
+
+        assert_eq!(
+            chip8.memory_accesses(),
+            &[
+                MemoryAccess {
+                    address: 0x300,
+                    access: Access::Read
+                },
+                MemoryAccess {
+                    address: 0x301,
+                    access: Access::Read
+                },
+            ]
+        );
+
+        chip8.pc = 0x202;
+        chip8.execute_cycle().unwrap();
+
+        let writes: Vec<usize> = chip8
+            .memory_accesses()
+            .iter()
+            .filter(|a| a.access == Access::Write)
+            .map(|a| a.address)
+            .collect();
 
-        let first_i = (LOWER_MEMORY_BOUNDARY + 2) as u16;
-        chip8.i = first_i;
+        assert_eq!(writes, vec![0x300, 0x301, 0x302]);
+    }
 
-        for i in 0..5 {
-            chip8.memory[(first_i + i) as usize] = i as u8 + 1;
-        }
+    fn create_and_load_xo_chip(program: &[u8]) -> Chip8 {
+        let mut chip8 = Chip8::new_xo_chip(Quirks::default());
 
-        chip8.execute_cycle();
+        chip8.load_program(program.to_vec()).unwrap();
 
-        for i in 0..5 {
-            assert_eq!(chip8.v[i as usize], i + 1);
-        }
+        chip8
+    }
+
+    fn create_and_load(program: &[u8]) -> Result<Chip8, Box<dyn Error>> {
+        create_and_load_with_quirks(program, Quirks::default())
     }
 
-    fn create_and_load(program: &Vec<u8>) -> Result<Chip8, Box<dyn Error>> {
-        let mut chip8 = Chip8::new();
+    fn create_and_load_with_quirks(
+        program: &[u8],
+        quirks: Quirks,
+    ) -> Result<Chip8, Box<dyn Error>> {
+        let mut chip8 = Chip8::new(quirks);
 
-        chip8.load_program(program.clone())?;
+        chip8.load_program(program.to_vec())?;
 
         Ok(chip8)
     }
@@ -1,7 +1,7 @@
 use std::thread::sleep;
 use std::time::Duration;
 use std::time::SystemTime;
-use std::{env, io};
+use std::{env, io, process};
 
 use easycurses::constants::acs;
 use easycurses::Color::*;
@@ -9,13 +9,81 @@ use easycurses::ColorPair;
 use easycurses::*;
 use getopts::Options;
 
-use chip_8::Chip8;
+use chip_8::{
+    assemble, disassemble, fading, is_lit, mnemonic, rom_hash, save_screenshot, serve_dap,
+    CellGrid, Chip8, Chip8Fault, Debugger, Framebuffer, Frontend, GdbStub, GifRecorder, Headless,
+    Hotkey, ImageFormat, InputEvent, Instruction, Io, KeyScript, Keypad, Movie, MovieRecorder,
+    Palette, Persistence, Phosphor, Quirks, Renderer, RewindBuffer, StepOutcome, Stop, Syntax,
+    TraceFormat, Tracer, MAX_FADE_FRAMES, MAX_SCALE, SHADED_COLORS,
+};
+use std::fs;
 use std::fs::File;
-use std::io::Read;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
 
 const CYCLES_PER_SECOND: u32 = 500;
-const TICKS_PER_CYCLE: u32 = (1000.0 / CYCLES_PER_SECOND as f64) as u32;
+const FRAMES_PER_SECOND: u32 = 60;
+// curses never reports key releases, so a key counts as released once the
+// terminal's auto-repeat hasn't pressed it again for this many frames
+const KEY_HOLD_FRAMES: u32 = 8;
+// ten seconds, when a headless run isn't given a limit
+const HEADLESS_FRAMES: u64 = 600;
 const ESC: Input = Input::Character(27 as char);
+const SAVE_STATE_KEY: Input = Input::KeyF2;
+const LOAD_STATE_KEY: Input = Input::KeyF3;
+const NEXT_SLOT_KEY: Input = Input::KeyF4;
+const REWIND_KEYS: [Input; 2] = [Input::KeyBackspace, Input::Character('\u{7f}')];
+const STATE_SLOTS: u8 = 9;
+const SCREENSHOT_KEY: Input = Input::KeyF8;
+const RECORD_KEY: Input = Input::KeyF12;
+const IMAGE_SCALE: u32 = 8;
+const PAUSE_KEY: Input = Input::KeyF5;
+const STEP_KEY: Input = Input::KeyF6;
+const STEP_FRAME_KEY: Input = Input::KeyF7;
+const BREAKPOINT_KEY: Input = Input::KeyF9;
+const COMMAND_KEY: Input = Input::Character(':');
+const ENTER_KEYS: [Input; 3] = [
+    Input::KeyEnter,
+    Input::Character('\n'),
+    Input::Character('\r'),
+];
+// the debugger panel, to the right of the display
+const PANEL_WIDTH: usize = 52;
+const DISASSEMBLY_BEFORE: u16 = 4;
+const DISASSEMBLY_LINES: usize = 12;
+const MEMORY_ROWS: usize = 4;
+const MEMORY_ROW_BYTES: usize = 8;
+
+// the XO-CHIP plane combinations: background, plane 1, plane 2, both
+const PIXEL_COLORS: [Color; 4] = [Black, White, Cyan, Yellow];
+// fading pixels on 256-color terminals get a color pair for every shaded
+// color on every other, numbered after the ones easycurses sets up
+const SHADE_PAIRS: i16 = 65;
+
+// everything `run_loop` needs to know from the command line
+struct Settings {
+    rom: String,
+    cycles_per_second: u32,
+    key_hold_frames: u32,
+    debugger: bool,
+    image: ImageSettings,
+}
+
+// a GIF being recorded, and where it's going
+type Recording = (String, GifRecorder<BufWriter<File>>);
+
+// whatever `run_loop` is recording, which outlives it
+struct Recorders<'a> {
+    gif: &'a mut Option<Recording>,
+    movie: Option<&'a mut MovieRecorder>,
+}
+
+// how screenshots and recordings are drawn
+struct ImageSettings {
+    scale: u32,
+    palette: Palette,
+    persistence: Persistence,
+}
 
 const KEY_MAP: [Input; 16] = [
     Input::Character('x'),
@@ -39,14 +107,175 @@ const KEY_MAP: [Input; 16] = [
 fn main() {
     let args: Vec<String> = env::args().collect();
 
+    match args.get(1).map(String::as_str) {
+        Some("asm") => return asm(&args[2..]),
+        Some("disasm") => return disasm(&args[2..]),
+        Some("dap") => return dap(&args[2..]),
+        _ => {}
+    }
+
     let mut opts = Options::new();
 
     opts.optflag("d", "debug", "display debug info");
+    opts.optflag("g", "debugger", "start paused in the interactive debugger");
+    opts.optopt(
+        "",
+        "gdb",
+        "listen for gdb on 127.0.0.1:PORT (implies --debugger)",
+        "PORT",
+    );
+    opts.optopt(
+        "q",
+        "quirks",
+        &format!(
+            "interpreter quirks profile ({})",
+            Quirks::PROFILE_NAMES.join(", ")
+        ),
+        "PROFILE",
+    );
+    opts.optflag("x", "xo-chip", "enable the XO-CHIP extensions");
+    opts.optopt(
+        "c",
+        "cycles",
+        &format!(
+            "instructions executed per second (default {})",
+            CYCLES_PER_SECOND
+        ),
+        "N",
+    );
+    opts.optopt(
+        "k",
+        "key-hold",
+        &format!(
+            "frames a key stays down after each press (default {})",
+            KEY_HOLD_FRAMES
+        ),
+        "FRAMES",
+    );
+    opts.optopt(
+        "s",
+        "seed",
+        "seed the random number generator, for reproducible runs",
+        "SEED",
+    );
+    opts.optopt(
+        "",
+        "trace",
+        "write a line to FILE for every instruction executed",
+        "FILE",
+    );
+    opts.optopt(
+        "",
+        "trace-format",
+        &format!(
+            "trace format ({}; default text)",
+            TraceFormat::NAMES.join(", ")
+        ),
+        "FORMAT",
+    );
+    opts.optopt(
+        "",
+        "trace-range",
+        "only trace instructions with PC in this range, such as 0x200-0x2FF",
+        "START-END",
+    );
+    opts.optflag(
+        "",
+        "headless",
+        "run without a display, then print the screen and registers",
+    );
+    opts.optopt(
+        "",
+        "frames",
+        &format!(
+            "stop a headless run after N frames (default {})",
+            HEADLESS_FRAMES
+        ),
+        "N",
+    );
+    opts.optopt(
+        "",
+        "instructions",
+        "stop a headless run after N instructions",
+        "N",
+    );
+    opts.optopt(
+        "",
+        "keys",
+        "key presses for a headless run, from FILE",
+        "FILE",
+    );
+    opts.optopt(
+        "",
+        "screenshot",
+        "save the screen at the end of a headless run to FILE (.png, .pbm or .pgm)",
+        "FILE",
+    );
+    opts.optopt(
+        "",
+        "screenshot-at",
+        "take the headless screenshot after FRAME frames instead",
+        "FRAME",
+    );
+    opts.optopt(
+        "",
+        "record",
+        "record the screen to FILE as an animated GIF",
+        "FILE",
+    );
+    opts.optopt(
+        "",
+        "movie",
+        "record every key press to a movie FILE, for --replay",
+        "FILE",
+    );
+    opts.optopt(
+        "",
+        "replay",
+        "replay a movie FILE headlessly, checking every frame against it",
+        "FILE",
+    );
+    opts.optopt(
+        "",
+        "scale",
+        &format!(
+            "size in image pixels of each screenshot or recording pixel, up to {} (default {})",
+            MAX_SCALE, IMAGE_SCALE
+        ),
+        "N",
+    );
+    opts.optopt(
+        "",
+        "palette",
+        &format!(
+            "screenshot and recording colors ({}, or RRGGBB,RRGGBB[,...])",
+            Palette::NAMES.join(", ")
+        ),
+        "PALETTE",
+    );
+    opts.optopt(
+        "",
+        "renderer",
+        &format!(
+            "how the display is drawn in the terminal ({}; default ascii)",
+            Renderer::NAMES.join(", ")
+        ),
+        "NAME",
+    );
+    opts.optopt(
+        "",
+        "persistence",
+        &format!(
+            "keep turned off pixels on screen: off, blend with the last frame, or fade over up to {} FRAMES (default off)",
+            MAX_FADE_FRAMES
+        ),
+        "MODE",
+    );
     opts.optflag("h", "help", "display this help message");
 
     let matches = match opts.parse(&args[1..]) {
         Ok(m) => m,
-        Err(f) => panic!(f.to_string()),
+        Err(f) => panic!("{}", f),
     };
 
     if matches.opt_present("h") {
@@ -56,6 +285,217 @@ fn main() {
 
     let debug = matches.opt_present("d");
 
+    let cycles_per_second = match matches.opt_str("c") {
+        Some(c) => match c.parse::<u32>() {
+            Ok(c) if c > 0 => c,
+            _ => {
+                eprintln!("invalid number of cycles: {}", c);
+                print_usage(opts);
+                return;
+            }
+        },
+        None => CYCLES_PER_SECOND,
+    };
+
+    let key_hold_frames = match matches.opt_str("k") {
+        Some(k) => match k.parse::<u32>() {
+            Ok(k) if k > 0 => k,
+            _ => {
+                eprintln!("invalid number of frames: {}", k);
+                print_usage(opts);
+                return;
+            }
+        },
+        None => KEY_HOLD_FRAMES,
+    };
+
+    let seed = match matches.opt_str("s") {
+        Some(s) => match s.parse::<u64>() {
+            Ok(s) => Some(s),
+            Err(_) => {
+                eprintln!("invalid seed: {}", s);
+                print_usage(opts);
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let gdb_port = match matches.opt_str("gdb") {
+        Some(p) => match p.parse::<u16>() {
+            Ok(p) => Some(p),
+            Err(_) => {
+                eprintln!("invalid port: {}", p);
+                print_usage(opts);
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let trace_format = match matches.opt_str("trace-format") {
+        Some(name) => match TraceFormat::from_name(&name) {
+            Some(format) => format,
+            None => {
+                eprintln!("unknown trace format: {}", name);
+                print_usage(opts);
+                return;
+            }
+        },
+        None => TraceFormat::Text,
+    };
+
+    let trace_range = match matches.opt_str("trace-range") {
+        Some(r) => match parse_range(&r) {
+            Some(range) => Some(range),
+            None => {
+                eprintln!("invalid trace range: {}", r);
+                print_usage(opts);
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let mut limits = [None, None];
+
+    for (limit, name) in limits.iter_mut().zip(["frames", "instructions"].iter()) {
+        if let Some(n) = matches.opt_str(name) {
+            match n.parse::<u64>() {
+                Ok(n) if n > 0 => *limit = Some(n),
+                _ => {
+                    eprintln!("invalid number of {}: {}", name, n);
+                    print_usage(opts);
+                    return;
+                }
+            }
+        }
+    }
+
+    let replay = match matches.opt_str("replay").map(|path| load_movie(&path)) {
+        Some(Ok(movie)) => Some(movie),
+        Some(Err(e)) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+        None => None,
+    };
+
+    let headless = matches.opt_present("headless") || replay.is_some();
+    let movie_path = matches.opt_str("movie");
+
+    let scale = match matches.opt_str("scale") {
+        Some(n) => match n.parse::<u32>() {
+            Ok(n) if n > 0 && n <= MAX_SCALE => n,
+            _ => {
+                eprintln!("invalid scale: {} (must be 1 to {})", n, MAX_SCALE);
+                print_usage(opts);
+                return;
+            }
+        },
+        None => IMAGE_SCALE,
+    };
+
+    let palette = match matches.opt_str("palette") {
+        Some(p) => match Palette::parse(&p) {
+            Some(palette) => palette,
+            None => {
+                eprintln!("invalid palette: {}", p);
+                print_usage(opts);
+                return;
+            }
+        },
+        None => Palette::default(),
+    };
+
+    let renderer = match matches.opt_str("renderer") {
+        Some(r) => match Renderer::from_name(&r) {
+            Some(renderer) => renderer,
+            None => {
+                eprintln!("unknown renderer: {}", r);
+                print_usage(opts);
+                return;
+            }
+        },
+        None => Renderer::default(),
+    };
+
+    let persistence = match matches.opt_str("persistence") {
+        Some(p) => match Persistence::parse(&p) {
+            Some(persistence) => persistence,
+            None => {
+                eprintln!("invalid persistence: {}", p);
+                print_usage(opts);
+                return;
+            }
+        },
+        None => Persistence::Off,
+    };
+
+    let screenshot_at = match matches.opt_str("screenshot-at") {
+        Some(f) => match f.parse::<u64>() {
+            Ok(f) => Some(f),
+            Err(_) => {
+                eprintln!("invalid frame: {}", f);
+                print_usage(opts);
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let screenshot = matches.opt_str("screenshot");
+
+    if let Some(path) = &screenshot {
+        if ImageFormat::from_path(path).is_none() {
+            eprintln!("unknown screenshot format: {}", path);
+            print_usage(opts);
+            return;
+        }
+    }
+
+    if !headless && (screenshot.is_some() || screenshot_at.is_some()) {
+        eprintln!("--screenshot is only for headless runs; press F8 to take one");
+        print_usage(opts);
+        return;
+    }
+
+    let image = ImageSettings {
+        scale,
+        palette,
+        persistence,
+    };
+
+    if headless && (matches.opt_present("g") || gdb_port.is_some()) {
+        eprintln!("--headless can't be used with the debugger");
+        print_usage(opts);
+        return;
+    }
+
+    if replay.is_some() && matches.opt_present("keys") {
+        eprintln!("--replay plays the movie's own key presses, so it can't be used with --keys");
+        print_usage(opts);
+        return;
+    }
+
+    if movie_path.is_some() && (headless || matches.opt_present("g") || gdb_port.is_some()) {
+        eprintln!("--movie only records plain terminal play");
+        print_usage(opts);
+        return;
+    }
+
+    let quirks = match matches.opt_str("q") {
+        Some(name) => match Quirks::from_name(&name) {
+            Some(quirks) => quirks,
+            None => {
+                eprintln!("unknown quirks profile: {}", name);
+                print_usage(opts);
+                return;
+            }
+        },
+        None => Quirks::default(),
+    };
+
     let input = if !matches.free.is_empty() {
         matches.free[0].clone()
     } else {
@@ -64,20 +504,448 @@ fn main() {
         return;
     };
 
-    println!("Loading {}...", input);
+    if !headless {
+        println!("Loading {}...", input);
+    }
+
+    // a replay starts the machine the way the movie did, and a movie has to
+    // know the seed to be replayed
+    let quirks = replay.as_ref().map_or(quirks, |movie| movie.quirks);
+    let xo_chip = replay
+        .as_ref()
+        .map_or(matches.opt_present("x"), |movie| movie.xo_chip);
+    let seed = match &replay {
+        Some(movie) => Some(movie.seed),
+        None if movie_path.is_some() => Some(seed.unwrap_or_else(rand::random)),
+        None => seed,
+    };
+    let instructions_per_frame = match &replay {
+        Some(movie) => movie.instructions_per_frame,
+        None => (cycles_per_second / FRAMES_PER_SECOND).max(1),
+    };
+
+    let mut chip8 = if xo_chip {
+        Chip8::new_xo_chip(quirks)
+    } else {
+        Chip8::new(quirks)
+    };
 
-    let mut chip8 = Chip8::new();
-    let program = match load_file(&args[1]) {
+    if let Some(seed) = seed {
+        chip8.seed(seed);
+    }
+    let program = match load_file(&input) {
         Ok(program) => program,
-        Err(e) => panic!("error loading file: {}; {}", &args[1], e),
+        Err(e) => panic!("error loading file: {}; {}", &input, e),
     };
 
+    if replay
+        .as_ref()
+        .is_some_and(|movie| movie.rom_hash != rom_hash(&program))
+    {
+        eprintln!("{} isn't the ROM the movie was recorded with", input);
+        process::exit(1);
+    }
+
+    let mut movie = movie_path.map(|path| {
+        let recorder = MovieRecorder::new(
+            &program,
+            quirks,
+            xo_chip,
+            seed.unwrap_or_default(),
+            instructions_per_frame,
+        );
+
+        (path, recorder)
+    });
+
     chip8.load_program(program).unwrap();
 
-    let mut screen = setup_screen();
-    let (x_offset, y_offset) = get_offsets(&screen);
+    if let Some(path) = matches.opt_str("trace") {
+        match File::create(&path) {
+            Ok(file) => {
+                let tracer = Tracer::new(BufWriter::new(file), trace_format, trace_range);
+
+                chip8.set_tracer(Some(tracer));
+            }
+            Err(e) => {
+                eprintln!("can't write trace to {}: {}", path, e);
+                process::exit(1);
+            }
+        }
+    }
+
+    let mut recording = match matches.opt_str("record") {
+        Some(path) => match start_recording(&chip8, &path, &image) {
+            Ok(recorder) => Some((path, recorder)),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    if headless {
+        let keys = match matches.opt_str("keys").map(|path| load_key_script(&path)) {
+            Some(Ok(keys)) => keys,
+            Some(Err(e)) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+            None => replay
+                .as_ref()
+                .map_or_else(KeyScript::default, Movie::key_script),
+        };
+        let max_frames = match (limits, &replay) {
+            (_, Some(movie)) => Some(movie.frames),
+            ([None, None], None) => Some(HEADLESS_FRAMES),
+            ([frames, _], None) => frames,
+        };
+        let max_cycles = if replay.is_some() { None } else { limits[1] };
+        let mut runner = Headless::new(instructions_per_frame, max_frames, max_cycles, keys);
+        let screenshot = screenshot
+            .or_else(|| screenshot_at.map(|frame| format!("{}-{}.png", input, frame)))
+            .map(|path| (path, screenshot_at));
+
+        return run_headless(
+            &mut chip8,
+            &mut runner,
+            screenshot,
+            recording,
+            replay.as_ref(),
+            &image,
+        );
+    }
+
+    // bound before curses takes over the terminal, so a failure can be seen
+    let gdb = match gdb_port.map(|port| GdbStub::bind(("127.0.0.1", port))) {
+        Some(Ok(gdb)) => Some(gdb),
+        Some(Err(e)) => {
+            eprintln!("can't listen for gdb: {}", e);
+            process::exit(1);
+        }
+        None => None,
+    };
+
+    let result = {
+        let debugging = matches.opt_present("g") || gdb.is_some();
+        let mut frontend = CursesFrontend::new(renderer, persistence, debug, debugging);
+
+        let settings = Settings {
+            rom: input,
+            cycles_per_second,
+            key_hold_frames,
+            debugger: debugging,
+            image,
+        };
+
+        let mut recorders = Recorders {
+            gif: &mut recording,
+            movie: movie.as_mut().map(|(_, recorder)| recorder),
+        };
+
+        run_loop(&mut chip8, &mut frontend, &settings, gdb, &mut recorders)
+    };
+
+    if let Some((path, recorder)) = movie {
+        let movie = recorder.finish();
 
-    run_loop(&mut chip8, &mut screen, x_offset, y_offset, debug);
+        match fs::write(&path, movie.to_string()) {
+            Ok(_) => println!("saved {} ({} frames)", path, movie.frames),
+            Err(e) => eprintln!("error saving {}: {}", path, e),
+        }
+    }
+
+    if let Some(recording) = recording {
+        match stop_recording(recording) {
+            Ok(message) => println!("{}", message),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    if let Some(tracer) = chip8.set_tracer(None) {
+        if let Err(e) = tracer.finish() {
+            eprintln!("error writing trace: {}", e);
+        }
+    }
+
+    // the screen has been dropped by now, so the report lands on a normal terminal
+    if let Err(fault) = result {
+        print_crash_report(&chip8, &fault);
+        process::exit(1);
+    }
+}
+
+// `chip-8 asm [options] SOURCE` assembles Octo-style source into a ROM
+fn asm(args: &[String]) {
+    let mut opts = Options::new();
+
+    opts.optopt(
+        "o",
+        "output",
+        "where to write the ROM (default SOURCE with a .ch8 extension)",
+        "FILE",
+    );
+    opts.optflag("h", "help", "display this help message");
+
+    let matches = match opts.parse(args) {
+        Ok(m) => m,
+        Err(f) => panic!("{}", f),
+    };
+
+    if matches.opt_present("h") {
+        print_asm_usage(opts);
+        return;
+    }
+
+    let input = if !matches.free.is_empty() {
+        PathBuf::from(&matches.free[0])
+    } else {
+        eprintln!("no source file given");
+        print_asm_usage(opts);
+        return;
+    };
+
+    let output = match matches.opt_str("o") {
+        Some(o) => PathBuf::from(o),
+        None => input.with_extension("ch8"),
+    };
+
+    if output == input {
+        eprintln!("refusing to overwrite the source file; use --output");
+        process::exit(1);
+    }
+
+    let source = match fs::read_to_string(&input) {
+        Ok(source) => source,
+        Err(e) => panic!("error loading file: {}; {}", input.display(), e),
+    };
+
+    let rom = match assemble(&source, Some(&input)) {
+        Ok(rom) => rom,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        }
+    };
+
+    if let Err(e) = fs::write(&output, &rom) {
+        eprintln!("error writing {}: {}", output.display(), e);
+        process::exit(1);
+    }
+
+    println!("wrote {} bytes to {}", rom.len(), output.display());
+}
+
+// `chip-8 disasm [options] ROM` prints an annotated listing of the ROM
+fn disasm(args: &[String]) {
+    let mut opts = Options::new();
+
+    opts.optopt(
+        "s",
+        "syntax",
+        &format!(
+            "listing syntax ({}; default octo)",
+            Syntax::NAMES.join(", ")
+        ),
+        "SYNTAX",
+    );
+    opts.optflag("x", "xo-chip", "decode the XO-CHIP extensions");
+    opts.optflag("h", "help", "display this help message");
+
+    let matches = match opts.parse(args) {
+        Ok(m) => m,
+        Err(f) => panic!("{}", f),
+    };
+
+    if matches.opt_present("h") {
+        print_disasm_usage(opts);
+        return;
+    }
+
+    let syntax = match matches.opt_str("s") {
+        Some(name) => match Syntax::from_name(&name) {
+            Some(syntax) => syntax,
+            None => {
+                eprintln!("unknown syntax: {}", name);
+                print_disasm_usage(opts);
+                return;
+            }
+        },
+        None => Syntax::Octo,
+    };
+
+    let input = if !matches.free.is_empty() {
+        matches.free[0].clone()
+    } else {
+        eprintln!("no ROM file given");
+        print_disasm_usage(opts);
+        return;
+    };
+
+    let program = match load_file(&input) {
+        Ok(program) => program,
+        Err(e) => panic!("error loading file: {}; {}", &input, e),
+    };
+
+    print!(
+        "{}",
+        disassemble(&program, matches.opt_present("x"), syntax)
+    );
+}
+
+// `chip-8 dap` speaks the Debug Adapter Protocol over stdin and stdout, for
+// editors to launch and debug ROMs with
+fn dap(args: &[String]) {
+    let mut opts = Options::new();
+
+    opts.optflag("h", "help", "display this help message");
+
+    let matches = match opts.parse(args) {
+        Ok(m) => m,
+        Err(f) => panic!("{}", f),
+    };
+
+    if matches.opt_present("h") {
+        print_dap_usage(opts);
+        return;
+    }
+
+    // stdout belongs to the protocol, so errors can only go to stderr
+    if let Err(e) = serve_dap(io::stdin(), io::stdout()) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+// An inclusive range of addresses, written START-END in decimal or hex.
+fn parse_range(text: &str) -> Option<(u16, u16)> {
+    let parse = |n: &str| match n.strip_prefix("0x").or_else(|| n.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => n.parse::<u16>().ok(),
+    };
+    let (start, end) = text.split_once('-')?;
+    let (start, end) = (parse(start)?, parse(end)?);
+
+    if start <= end {
+        Some((start, end))
+    } else {
+        None
+    }
+}
+
+fn load_movie(path: &str) -> Result<Movie, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+
+    Movie::parse(&text).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn load_key_script(path: &str) -> Result<KeyScript, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+
+    KeyScript::parse(&text).map_err(|e| format!("{}: {}", path, e))
+}
+
+// Runs the ROM to the end of a headless run, then prints the screen, the
+// registers and how far it got, for regression checks to compare against.
+//
+// The screenshot, if there is one, is saved once the given number of frames
+// have run, or at the end when no frame is given.
+//
+// A replay checks the screen against the movie at the end of every frame.
+fn run_headless(
+    chip8: &mut Chip8,
+    runner: &mut Headless,
+    mut screenshot: Option<(String, Option<u64>)>,
+    mut recording: Option<Recording>,
+    replay: Option<&Movie>,
+    image: &ImageSettings,
+) {
+    let save = |phosphor: &Phosphor, path: &str| {
+        if let Err(e) = save_screenshot(&phosphor.framebuffer(), path, image.scale, &image.palette)
+        {
+            eprintln!("can't save screenshot to {}: {}", path, e);
+            process::exit(1);
+        }
+    };
+    // what the screenshot shows, with persistence
+    let mut phosphor = Phosphor::new(image.persistence);
+
+    phosphor.update(&chip8.framebuffer());
+
+    let result = loop {
+        if let Some((path, at)) = &screenshot {
+            if *at == Some(runner.frame()) {
+                save(&phosphor, path);
+                screenshot = None;
+            }
+        }
+
+        if runner.is_finished() {
+            break Ok(());
+        }
+
+        let frame = runner.frame();
+
+        if let Err(fault) = runner.run_frame(chip8) {
+            break Err(format!("CPU fault: {}", fault));
+        }
+
+        phosphor.update(&chip8.framebuffer());
+
+        if let Some(Err(desync)) = replay.map(|movie| movie.check_frame(frame, chip8)) {
+            break Err(desync.to_string());
+        }
+
+        if let Some((path, recorder)) = recording.as_mut() {
+            if let Err(e) = recorder.capture(chip8) {
+                eprintln!("error recording {}: {}", path, e);
+                process::exit(1);
+            }
+        }
+    };
+
+    if let Some(recording) = recording {
+        if let Err(e) = stop_recording(recording) {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+
+    match screenshot {
+        Some((path, None)) => save(&phosphor, &path),
+        Some((_, Some(at))) => {
+            eprintln!(
+                "the run ended at frame {}, before the screenshot at frame {}",
+                runner.frame(),
+                at
+            );
+            process::exit(1);
+        }
+        None => {}
+    }
+    let border = format!("+{}+", "-".repeat(chip8.width()));
+
+    if let Some(tracer) = chip8.set_tracer(None) {
+        if let Err(e) = tracer.finish() {
+            eprintln!("error writing trace: {}", e);
+        }
+    }
+
+    println!("{}", border);
+
+    for row in chip8.to_string().lines() {
+        println!("|{}|", row);
+    }
+
+    println!("{}", border);
+    print!("{}", register_dump(chip8));
+    println!("frames: {}  cycles: {}", runner.frame(), chip8.cycles());
+
+    if let Err(message) = result {
+        eprintln!("{}", message);
+        process::exit(1);
+    }
 }
 
 fn load_file(file_name: &str) -> io::Result<Vec<u8>> {
@@ -90,120 +958,655 @@ fn load_file(file_name: &str) -> io::Result<Vec<u8>> {
     Ok(buffer)
 }
 
+// Screenshots and recordings go next to the ROM, numbered from 1, without
+// overwriting any that are already there.
+fn next_path(rom: &str, extension: &str) -> String {
+    (1..)
+        .map(|n| format!("{}-{}.{}", rom, n, extension))
+        .find(|path| !Path::new(path).exists())
+        .unwrap()
+}
+
+fn take_screenshot(framebuffer: &Framebuffer, rom: &str, image: &ImageSettings) -> String {
+    let path = next_path(rom, "png");
+
+    match save_screenshot(framebuffer, &path, image.scale, &image.palette) {
+        Ok(_) => format!("saved {}", path),
+        Err(e) => format!("error saving screenshot: {}", e),
+    }
+}
+
+fn start_recording(
+    chip8: &Chip8,
+    path: &str,
+    image: &ImageSettings,
+) -> Result<GifRecorder<BufWriter<File>>, String> {
+    File::create(path)
+        .and_then(|file| {
+            GifRecorder::new(
+                BufWriter::new(file),
+                chip8,
+                image.scale,
+                &image.palette,
+                image.persistence,
+            )
+        })
+        .map_err(|e| format!("can't record to {}: {}", path, e))
+}
+
+fn stop_recording((path, recorder): Recording) -> Result<String, String> {
+    let frames = recorder.frames();
+
+    match recorder.finish().and_then(|mut file| file.flush()) {
+        Ok(_) => Ok(format!("saved {} ({} frames)", path, frames)),
+        Err(e) => Err(format!("error recording {}: {}", path, e)),
+    }
+}
+
+// save states live next to the ROM, one file per slot
+fn state_path(rom: &str, slot: u8) -> String {
+    format!("{}.state{}", rom, slot)
+}
+
+fn save_state(chip8: &Chip8, rom: &str, slot: u8) -> String {
+    match fs::write(state_path(rom, slot), chip8.save_state()) {
+        Ok(_) => format!("saved slot {}", slot),
+        Err(e) => format!("error saving slot {}: {}", slot, e),
+    }
+}
+
+fn load_state(chip8: &mut Chip8, rom: &str, slot: u8) -> String {
+    let result = load_file(&state_path(rom, slot))
+        .map_err(|e| e.to_string())
+        .and_then(|state| chip8.load_state(&state).map_err(|e| e.to_string()));
+
+    match result {
+        Ok(_) => format!("loaded slot {}", slot),
+        Err(e) => format!("error loading slot {}: {}", slot, e),
+    }
+}
+
 fn print_usage(opts: Options) {
-    let brief = format!("Usage: chip-8 [options] ROM");
+    let brief = "Usage: chip-8 [options] ROM\n       chip-8 asm [options] SOURCE\n       chip-8 disasm [options] ROM\n       chip-8 dap";
+
+    println!("{}", opts.usage(brief));
+}
+
+fn print_asm_usage(opts: Options) {
+    let brief = "Usage: chip-8 asm [options] SOURCE";
 
-    println!("{}", opts.usage(&brief));
+    println!("{}", opts.usage(brief));
 }
 
-fn run_loop(chip8: &mut Chip8, screen: &mut EasyCurses, x_offset: i32, y_offset: i32, debug: bool) {
+fn print_disasm_usage(opts: Options) {
+    let brief = "Usage: chip-8 disasm [options] ROM";
+
+    println!("{}", opts.usage(brief));
+}
+
+fn print_dap_usage(opts: Options) {
+    let brief = "Usage: chip-8 dap";
+
+    println!("{}", opts.usage(brief));
+}
+
+fn print_crash_report(chip8: &Chip8, fault: &Chip8Fault) {
+    eprintln!("CPU fault: {}", fault);
+    eprintln!();
+    eprint!("{}", register_dump(chip8));
+}
+
+// V0-VF four to a line, then I, PC, SP, the timers and the stack
+fn register_dump(chip8: &Chip8) -> String {
+    let mut dump = String::new();
+
+    for (i, v) in chip8.v().iter().enumerate() {
+        dump += &format!("V{:X}: {:#04X}", i, v);
+        dump += if i % 4 == 3 { "\n" } else { "  " };
+    }
+
+    dump += &format!(
+        "I: {:#05X}  PC: {:#05X}  SP: {}  DT: {}  ST: {}\n",
+        chip8.i(),
+        chip8.pc(),
+        chip8.sp(),
+        chip8.delay_timer(),
+        chip8.sound_timer
+    );
+
+    let depth = chip8.sp() as usize;
+    let stack: Vec<String> = chip8.stack()[..depth.min(chip8.stack().len())]
+        .iter()
+        .map(|a| format!("{:#05X}", a))
+        .collect();
+
+    dump += &format!("stack: [{}]\n", stack.join(", "));
+    dump
+}
+
+// Each pass through the loop is one 60 Hz frame, so the timers tick at the
+// right rate no matter how many instructions run in between.
+//
+// Holding the rewind key plays the game backwards, one recorded frame at a
+// time; like the keypad, "holding" means the terminal keeps repeating it.
+//
+// With the debugger on, frames go through `Debugger` instead, and a CPU fault
+// pauses the program rather than ending it. A gdb client, if there is one,
+// gets to drive the debugger at the start of every frame.
+//
+// A GIF recording captures every frame, and a movie every key press; the
+// caller finishes whatever is still being recorded when the loop ends. While
+// a movie is being recorded, rewinding and loading a state are off, since a
+// replay couldn't follow them.
+fn run_loop<F: Frontend>(
+    chip8: &mut Chip8,
+    frontend: &mut F,
+    settings: &Settings,
+    mut gdb: Option<GdbStub>,
+    recorders: &mut Recorders,
+) -> Result<(), Chip8Fault> {
+    let instructions_per_frame = (settings.cycles_per_second / FRAMES_PER_SECOND).max(1);
+    let recording = &mut *recorders.gif;
+    let frame_duration = Duration::from_secs(1) / FRAMES_PER_SECOND;
+    let mut io = Io::new(Keypad::new(Some(settings.key_hold_frames)));
+    let mut rewind = RewindBuffer::default();
+    let mut rewind_frames_left: u32 = 0;
     let mut iteration: u32 = 0;
+    let mut slot: u8 = 1;
+    let mut debugger = Debugger::new(instructions_per_frame);
+
+    if let Some(Ok(address)) = gdb.as_ref().map(GdbStub::local_addr) {
+        frontend.show_status(format!("waiting for gdb on {}", address));
+    }
+
+    if let Some((path, _)) = recording {
+        frontend.show_status(format!("recording {}", path));
+    }
 
     loop {
         let start = SystemTime::now();
 
-        chip8.execute_cycle();
+        io.input(frontend, chip8);
 
-        if !process_input(chip8, screen) {
+        if frontend.quit_requested() {
             break;
         }
 
-        if chip8.draw_flag {
-            draw_graphics(chip8, screen, x_offset, y_offset, iteration, debug);
+        for hotkey in frontend.take_hotkeys() {
+            let status = match hotkey {
+                Hotkey::SaveState => save_state(chip8, &settings.rom, slot),
+                Hotkey::LoadState | Hotkey::Rewind if recorders.movie.is_some() => {
+                    "can't go back while recording a movie".to_string()
+                }
+                Hotkey::LoadState => load_state(chip8, &settings.rom, slot),
+                Hotkey::NextSlot => {
+                    slot = slot % STATE_SLOTS + 1;
+                    format!("slot {}", slot)
+                }
+                Hotkey::Rewind => {
+                    rewind_frames_left = settings.key_hold_frames;
+                    format!("rewinding ({} frames left)", rewind.len())
+                }
+                Hotkey::TogglePause => {
+                    if debugger.is_paused() {
+                        debugger.resume();
+                        "running".to_string()
+                    } else {
+                        debugger.pause();
+                        "paused".to_string()
+                    }
+                }
+                Hotkey::Step => debugger_command(&mut debugger, chip8, "step"),
+                Hotkey::StepFrame => debugger_command(&mut debugger, chip8, "frame"),
+                Hotkey::ToggleBreakpoint => {
+                    let pc = chip8.pc();
+
+                    if debugger.toggle_breakpoint(pc) {
+                        format!("breakpoint set at {:#05X}", pc)
+                    } else {
+                        format!("deleted breakpoint at {:#05X}", pc)
+                    }
+                }
+                Hotkey::Screenshot => {
+                    take_screenshot(&frontend.shown(chip8), &settings.rom, &settings.image)
+                }
+                Hotkey::ToggleRecording => match recording.take() {
+                    Some(recording) => stop_recording(recording).unwrap_or_else(|e| e),
+                    None => {
+                        let path = next_path(&settings.rom, "gif");
+
+                        match start_recording(chip8, &path, &settings.image) {
+                            Ok(recorder) => {
+                                *recording = Some((path.clone(), recorder));
+                                format!("recording {}", path)
+                            }
+                            Err(e) => e,
+                        }
+                    }
+                },
+                Hotkey::Command(line) => debugger_command(&mut debugger, chip8, &line),
+            };
+
+            frontend.show_status(status);
+
+            chip8.draw_flag = true;
         }
 
-        let elapsed = match start.elapsed() {
-            Ok(e) => e.as_millis(),
-            Err(e) => panic!("time error: {}", e),
-        };
+        if let Some(gdb) = gdb.as_mut() {
+            gdb.poll(chip8, &mut debugger);
+        }
 
-        if elapsed < TICKS_PER_CYCLE as u128 {
-            let time_left = TICKS_PER_CYCLE as u128 - elapsed;
+        if rewind_frames_left > 0 {
+            rewind_frames_left -= 1;
+            rewind.rewind(chip8);
+        } else if settings.debugger {
+            if !debugger.is_paused() {
+                let result = match gdb.as_mut() {
+                    Some(gdb) => gdb.run_frame(chip8, &mut debugger),
+                    None => debugger.run_frame(chip8),
+                };
 
-            sleep(Duration::from_millis(time_left as u64));
+                match result {
+                    Ok(Some(Stop::Exited)) => break,
+                    Ok(Some(stop)) => frontend.show_status(stop.to_string()),
+                    Ok(None) => {
+                        io.end_frame(chip8);
+                        rewind.push(chip8);
+                    }
+                    Err(fault) => {
+                        debugger.pause();
+                        frontend.show_status(format!("CPU fault: {}", fault));
+                    }
+                }
+            }
+
+            // the panel changes with every instruction
+            chip8.draw_flag = true;
+        } else {
+            if let Some(movie) = recorders.movie.as_mut() {
+                movie.start_frame(chip8);
+            }
+
+            let outcome = chip8.run_frame(instructions_per_frame);
+
+            // recorded even if it faults, so the replay faults there too
+            if let Some(movie) = recorders.movie.as_mut() {
+                movie.end_frame(chip8);
+            }
+
+            if outcome? == StepOutcome::Exited {
+                break;
+            }
+
+            io.end_frame(chip8);
+            rewind.push(chip8);
         }
 
-        if chip8.sound_timer > 0 {
-            screen.beep();
+        if chip8.draw_flag && settings.debugger {
+            frontend.draw_debugger(chip8, &debugger);
+        }
+
+        if frontend.is_fading() {
+            chip8.draw_flag = true;
+        }
+
+        frontend.set_frame(iteration);
+        io.output(frontend, chip8, settings.debugger && debugger.is_paused());
+
+        if let Some((path, recorder)) = recording.as_mut() {
+            if let Err(e) = recorder.capture(chip8) {
+                frontend.show_status(format!("error recording {}: {}", path, e));
+                *recording = None;
+            }
+        }
+
+        let elapsed = match start.elapsed() {
+            Ok(e) => e,
+            Err(e) => panic!("time error: {}", e),
+        };
+
+        if elapsed < frame_duration {
+            sleep(frame_duration - elapsed);
         }
 
         iteration += 1;
     }
+
+    Ok(())
 }
 
-fn process_input(chip8: &mut Chip8, screen: &mut EasyCurses) -> bool {
-    if let Some(key) = screen.get_input() {
-        return if key == ESC {
-            false // exit on `Esc`
-        } else {
-            for i in 0..16 as usize {
-                if key == KEY_MAP[i] {
-                    chip8.key[i] = 1;
+fn debugger_command(debugger: &mut Debugger, chip8: &mut Chip8, line: &str) -> String {
+    match debugger.command(chip8, line) {
+        Ok(message) => message,
+        Err(e) => e.to_string(),
+    }
+}
+
+// The terminal frontend. The display is drawn with curses and the tone is
+// the terminal bell, which can't be held, so it rings once as the tone
+// starts. Terminals only report key presses, so the keypad releases keys;
+// anything that isn't on the keypad is kept as a hotkey for `run_loop`.
+struct CursesFrontend {
+    screen: EasyCurses,
+    // what's on the screen, so only cells that change are drawn again
+    grid: CellGrid,
+    phosphor: Phosphor,
+    // whether there are color pairs for shaded colors; without them,
+    // fading pixels are dim
+    shaded: bool,
+    debug: bool,
+    debugging: bool,
+    hotkeys: Vec<Hotkey>,
+    // the debugger command being typed, if any
+    command: Option<String>,
+    quit: bool,
+    // shown under the display
+    status: String,
+    shown_status: Option<String>,
+    // shown above the display with `--debug`
+    frame: u32,
+    // the display's resolution and the terminal's size; the border moves
+    // when either changes, so the screen is cleared and drawn from scratch
+    layout: (usize, usize, i32, i32),
+    cleared: bool,
+}
+
+impl CursesFrontend {
+    fn new(renderer: Renderer, persistence: Persistence, debug: bool, debugging: bool) -> Self {
+        let mut screen = EasyCurses::initialize_system().unwrap();
+
+        screen.set_cursor_visibility(CursorVisibility::Invisible);
+        screen.set_echo(false);
+        screen.set_keypad_enabled(true);
+        screen.set_color_pair(colorpair!(White on Black));
+        screen.set_input_mode(InputMode::Character);
+        screen.set_input_timeout(TimeoutMode::Immediate);
+
+        let shaded = persistence != Persistence::Off
+            && pancurses::COLORS() >= 256
+            && pancurses::COLOR_PAIRS() >= SHADE_PAIRS as i32 + 256;
+
+        if shaded {
+            let colors = Palette::default().shaded();
+
+            for (fg, foreground) in colors.iter().enumerate() {
+                for (bg, background) in colors.iter().enumerate() {
+                    pancurses::init_pair(
+                        shade_pair(fg as u8, bg as u8),
+                        xterm_color(*foreground),
+                        xterm_color(*background),
+                    );
                 }
             }
+        }
 
-            true
+        CursesFrontend {
+            screen,
+            grid: CellGrid::new(renderer),
+            phosphor: Phosphor::new(persistence),
+            shaded,
+            debug,
+            debugging,
+            hotkeys: vec![],
+            command: None,
+            quit: false,
+            status: String::new(),
+            shown_status: None,
+            frame: 0,
+            layout: (0, 0, 0, 0),
+            cleared: true,
+        }
+    }
+
+    fn set_colors(&mut self, foreground: u8, background: u8) {
+        if self.shaded {
+            self.screen
+                .win
+                .color_set(shade_pair(foreground, background));
+            return;
+        }
+
+        // a fading background is either there or not
+        let (foreground, dim) = match fading(foreground) {
+            Some((color, _)) => (color, true),
+            None => (foreground, false),
+        };
+        let background = match fading(background) {
+            Some((color, _)) if is_lit(background) => color,
+            Some(_) => 0,
+            None => background,
         };
+
+        self.screen.set_color_pair(ColorPair::new(
+            PIXEL_COLORS[foreground as usize],
+            PIXEL_COLORS[background as usize],
+        ));
+
+        if dim {
+            self.screen.win.attron(pancurses::Attribute::Dim);
+        } else {
+            self.screen.win.attroff(pancurses::Attribute::Dim);
+        }
     }
 
-    return true;
-}
+    fn resize(&mut self, width: usize, height: usize) {
+        let (rows, cols) = self.screen.get_row_col_count();
+        let layout = (width, height, rows, cols);
 
-fn get_offsets(screen: &EasyCurses) -> (i32, i32) {
-    let (rows, cols) = screen.get_row_col_count();
+        if self.layout != layout {
+            self.layout = layout;
+            self.screen.clear();
+            self.grid.invalidate();
+            self.shown_status = None;
+            self.cleared = true;
+        }
+    }
 
-    (rows / 2 - 16, cols / 2 - 32)
+    // The display is centered, except in the debugger, where it moves to the
+    // top left to make room for the panel.
+    fn offsets(&self) -> (i32, i32) {
+        let (width, height, rows, cols) = self.layout;
+        let (columns, lines) = self.grid.renderer().cells(width, height);
+
+        if self.debugging {
+            return (1, 0);
+        }
+
+        (rows / 2 - lines as i32 / 2, cols / 2 - columns as i32 / 2)
+    }
 }
 
-fn draw_graphics(
-    chip8: &mut Chip8,
-    screen: &mut EasyCurses,
-    x_offset: i32,
-    y_offset: i32,
-    iteration: u32,
-    debug: bool,
-) {
-    let rows = 32;
-    let cols = 64;
+impl Frontend for CursesFrontend {
+    fn present(&mut self, framebuffer: &Framebuffer<'_>) {
+        self.resize(framebuffer.width, framebuffer.height);
+
+        let (cols, rows) = self
+            .grid
+            .renderer()
+            .cells(framebuffer.width, framebuffer.height);
+        let (cols, rows) = (cols as i32, rows as i32);
+        let (x_offset, y_offset) = self.offsets();
+        let mut glyph = [0; 4];
+
+        if self.debug {
+            self.screen.move_rc(x_offset - 1, y_offset);
+            self.screen.print(format!("Frame: {}", self.frame));
+        }
+
+        if self.cleared {
+            self.cleared = false;
+            draw_border(&mut self.screen, x_offset, y_offset, rows, cols);
+        }
+
+        self.phosphor.update(framebuffer);
+
+        for (c, r, cell) in self.grid.update(&self.phosphor.framebuffer()) {
+            self.set_colors(cell.foreground, cell.background);
+            self.screen
+                .move_rc(r as i32 + 1 + x_offset, c as i32 + 1 + y_offset);
+            self.screen.print(cell.glyph.encode_utf8(&mut glyph));
+        }
+
+        let screen = &mut self.screen;
+
+        screen.win.attroff(pancurses::Attribute::Dim);
+        screen.set_color_pair(colorpair!(White on Black));
+
+        if self.shown_status.as_ref() != Some(&self.status) {
+            screen.move_rc(rows + 2 + x_offset, y_offset);
+            screen.print(format!(
+                "{:width$}",
+                self.status,
+                width = (cols + 2) as usize
+            ));
+            self.shown_status = Some(self.status.clone());
+        }
+
+        screen.refresh();
+    }
+
+    fn poll_input(&mut self) -> Vec<InputEvent> {
+        let mut events = vec![];
 
-    chip8.draw_flag = false;
+        // drain everything typed since the last frame
+        while let Some(key) = self.screen.get_input() {
+            // while a debugger command is being typed, every key goes to it
+            if let Some(line) = &mut self.command {
+                match key {
+                    ESC => self.command = None,
+                    _ if ENTER_KEYS.contains(&key) => {
+                        self.hotkeys.push(Hotkey::Command(line.clone()));
+                        self.command = None;
+                    }
+                    _ if REWIND_KEYS.contains(&key) => {
+                        line.pop();
+                    }
+                    Input::Character(c) if !c.is_control() => line.push(c),
+                    _ => {}
+                }
+
+                continue;
+            }
+
+            let debugging = self.debugging;
+            let hotkey = match key {
+                ESC => {
+                    // exit on `Esc`
+                    self.quit = true;
+                    continue;
+                }
+                SAVE_STATE_KEY => Hotkey::SaveState,
+                LOAD_STATE_KEY => Hotkey::LoadState,
+                NEXT_SLOT_KEY => Hotkey::NextSlot,
+                SCREENSHOT_KEY => Hotkey::Screenshot,
+                RECORD_KEY => Hotkey::ToggleRecording,
+                _ if REWIND_KEYS.contains(&key) => Hotkey::Rewind,
+                PAUSE_KEY if debugging => Hotkey::TogglePause,
+                STEP_KEY if debugging => Hotkey::Step,
+                STEP_FRAME_KEY if debugging => Hotkey::StepFrame,
+                BREAKPOINT_KEY if debugging => Hotkey::ToggleBreakpoint,
+                COMMAND_KEY if debugging => {
+                    self.command = Some(String::new());
+                    continue;
+                }
+                _ => {
+                    if let Some(i) = KEY_MAP.iter().position(|mapped| key == *mapped) {
+                        events.push(InputEvent::KeyDown(i as u8));
+                    }
+
+                    continue;
+                }
+            };
+
+            self.hotkeys.push(hotkey);
+        }
+
+        events
+    }
+
+    fn start_tone(&mut self) {
+        self.screen.beep();
+    }
 
-    if debug {
-        screen.move_rc(0 + x_offset - 1, 0 + y_offset);
-        screen.print(format!("Iteration: {}", iteration));
+    fn stop_tone(&mut self) {}
+
+    fn quit_requested(&self) -> bool {
+        self.quit
+    }
+
+    fn take_hotkeys(&mut self) -> Vec<Hotkey> {
+        std::mem::take(&mut self.hotkeys)
+    }
+
+    fn show_status(&mut self, status: String) {
+        self.status = status;
+    }
+
+    fn draw_debugger(&mut self, chip8: &Chip8, debugger: &Debugger) {
+        let (columns, _) = self.grid.renderer().cells(chip8.width(), chip8.height());
+
+        self.resize(chip8.width(), chip8.height());
+        draw_debugger(
+            chip8,
+            &mut self.screen,
+            columns as i32 + 4,
+            debugger,
+            &self.command,
+        );
+    }
+
+    // pixels fade out over several frames after they're turned off
+    fn is_fading(&self) -> bool {
+        self.phosphor.persistence() != Persistence::Off
+    }
+
+    fn shown<'a>(&'a self, chip8: &'a Chip8) -> Framebuffer<'a> {
+        if self.phosphor.persistence() == Persistence::Off || self.phosphor.is_empty() {
+            chip8.framebuffer()
+        } else {
+            self.phosphor.framebuffer()
+        }
     }
 
-    screen.move_rc(0 + x_offset, 0 + y_offset);
+    fn set_frame(&mut self, frame: u32) {
+        self.frame = frame;
+    }
+}
+
+// the color pair for one shaded color on another
+fn shade_pair(foreground: u8, background: u8) -> i16 {
+    SHADE_PAIRS + foreground as i16 * SHADED_COLORS as i16 + background as i16
+}
+
+// the nearest color in a 256-color terminal's 6x6x6 color cube
+fn xterm_color(rgb: [u8; 3]) -> i16 {
+    let [r, g, b] = rgb.map(|c| ((c as u32 * 5 + 127) / 255) as i16);
+
+    16 + 36 * r + 6 * g + b
+}
+
+// a box around a display `rows` by `cols` cells, with its top left corner at
+// `x_offset`, `y_offset`
+fn draw_border(screen: &mut EasyCurses, x_offset: i32, y_offset: i32, rows: i32, cols: i32) {
+    screen.move_rc(x_offset, y_offset);
     screen.print_char(acs::ulcorner());
 
     for i in 0..=cols {
-        screen.move_rc(0 + x_offset, i + 1 + y_offset);
+        screen.move_rc(x_offset, i + 1 + y_offset);
         screen.print_char(acs::hline());
     }
 
-    screen.move_rc(0 + x_offset, cols + 1 + y_offset);
+    screen.move_rc(x_offset, cols + 1 + y_offset);
     screen.print_char(acs::urcorner());
 
     for r in 0..rows {
-        screen.move_rc(r + 1 + x_offset, 0 + y_offset);
+        screen.move_rc(r + 1 + x_offset, y_offset);
         screen.print_char(acs::vline());
-
-        for c in 0..cols {
-            let pixel = if chip8.gfx[(c + r * cols) as usize] == 1 {
-                '*'
-            } else {
-                ' '
-            };
-
-            screen.move_rc(r + 1 + x_offset, c + 1 + y_offset);
-            screen.print_char(pixel);
-        }
-
         screen.move_rc(r + 1 + x_offset, cols + 1 + y_offset);
         screen.print_char(acs::vline());
     }
 
-    screen.move_rc(rows + 1 + x_offset, 0 + y_offset);
+    screen.move_rc(rows + 1 + x_offset, y_offset);
     screen.print_char(acs::llcorner());
 
     for i in 0..=cols {
@@ -213,17 +1616,154 @@ fn draw_graphics(
 
     screen.move_rc(rows + 1 + x_offset, cols + 1 + y_offset);
     screen.print_char(acs::lrcorner());
+}
+
+// Registers, stack, breakpoints, the code around PC and a window onto
+// memory, starting at column `left`, to the right of the display.
+fn draw_debugger(
+    chip8: &Chip8,
+    screen: &mut EasyCurses,
+    left: i32,
+    debugger: &Debugger,
+    command: &Option<String>,
+) {
+    let breakpoints: Vec<u16> = debugger.breakpoints().collect();
+    let mut lines = vec![];
+
+    lines.push(
+        if debugger.is_paused() {
+            "PAUSED"
+        } else {
+            "RUNNING"
+        }
+        .to_string(),
+    );
+    lines.push("F5 run/pause  F6 step  F7 frame  F9 break  : command".to_string());
+    lines.push(String::new());
+    lines.push(format!(
+        "PC {:#05X}  I {:#05X}  SP {:X}  DT {:02X}  ST {:02X}",
+        chip8.pc(),
+        chip8.i(),
+        chip8.sp(),
+        chip8.delay_timer(),
+        chip8.sound_timer
+    ));
+
+    for (row, values) in chip8.v().chunks(4).enumerate() {
+        let registers: Vec<String> = values
+            .iter()
+            .enumerate()
+            .map(|(c, value)| format!("V{:X} {:02X}", row * 4 + c, value))
+            .collect();
+
+        lines.push(registers.join("  "));
+    }
+
+    let depth = (chip8.sp() as usize).min(chip8.stack().len());
+
+    lines.push(format!("stack: {}", address_list(&chip8.stack()[..depth])));
+    lines.push(format!("breakpoints: {}", address_list(&breakpoints)));
+
+    for address in &breakpoints {
+        if let Some(condition) = debugger.condition(*address) {
+            lines.push(format!("  {:#05X} if {}", address, condition));
+        }
+    }
+
+    let watchpoints: Vec<String> = debugger
+        .watchpoints()
+        .iter()
+        .map(|w| w.to_string())
+        .collect();
 
-    screen.refresh();
+    lines.push(format!(
+        "watchpoints: {}",
+        if watchpoints.is_empty() {
+            "-".to_string()
+        } else {
+            watchpoints.join(", ")
+        }
+    ));
+    lines.push(String::new());
+
+    // decoding starts a few words before PC, which is usually but not
+    // always an instruction boundary
+    let memory = chip8.memory();
+    let word = |address: usize| -> Option<u16> {
+        Some((*memory.get(address)? as u16) << 8 | *memory.get(address + 1)? as u16)
+    };
+    let mut address = chip8.pc().saturating_sub(DISASSEMBLY_BEFORE * 2) as usize;
+
+    for _ in 0..DISASSEMBLY_LINES {
+        let opcode = match word(address) {
+            Some(opcode) => opcode,
+            None => break,
+        };
+        let (text, size) = match Instruction::decode(opcode) {
+            Ok(instruction) if !instruction.is_xo_chip() || chip8.is_xo_chip() => {
+                let operand = word(address + 2).unwrap_or_default();
+
+                (
+                    mnemonic(instruction, operand, Syntax::Octo),
+                    instruction.size() as usize,
+                )
+            }
+            _ => ("???".to_string(), 2),
+        };
+        let marker = if address == chip8.pc() as usize {
+            '>'
+        } else {
+            ' '
+        };
+        let breakpoint = if breakpoints.contains(&(address as u16)) {
+            '*'
+        } else {
+            ' '
+        };
+
+        lines.push(format!(
+            "{}{} {:#05X}  {:04X}  {}",
+            marker, breakpoint, address, opcode, text
+        ));
+        address += size;
+    }
+
+    lines.push(String::new());
+
+    let view = debugger.memory_view(chip8) as usize;
+
+    for row in 0..MEMORY_ROWS {
+        let start = view + row * MEMORY_ROW_BYTES;
+        let bytes: Vec<String> = (start..start + MEMORY_ROW_BYTES)
+            .filter_map(|a| memory.get(a))
+            .map(|b| format!("{:02X}", b))
+            .collect();
+
+        if bytes.is_empty() {
+            break;
+        }
+
+        lines.push(format!("{:#05X}  {}", start, bytes.join(" ")));
+    }
+
+    lines.push(String::new());
+    lines.push(match command {
+        Some(line) => format!(":{}", line),
+        None => String::new(),
+    });
+
+    for (row, line) in lines.iter().enumerate() {
+        screen.move_rc(row as i32, left);
+        screen.print(format!("{:width$}", line, width = PANEL_WIDTH));
+    }
 }
 
-fn setup_screen() -> EasyCurses {
-    let mut screen = EasyCurses::initialize_system().unwrap();
+fn address_list(addresses: &[u16]) -> String {
+    if addresses.is_empty() {
+        return "-".to_string();
+    }
+
+    let formatted: Vec<String> = addresses.iter().map(|a| format!("{:#05X}", a)).collect();
 
-    screen.set_cursor_visibility(CursorVisibility::Invisible);
-    screen.set_echo(false);
-    screen.set_color_pair(colorpair!(White on Black));
-    screen.set_input_mode(InputMode::Character);
-    screen.set_input_timeout(TimeoutMode::Immediate);
-    screen
+    formatted.join(" ")
 }
@@ -0,0 +1,424 @@
+use std::fmt;
+
+use crate::errors::{DesyncError, ScriptError};
+use crate::headless::parse_event;
+use crate::state::crc32;
+use crate::{Chip8, KeyEvent, KeyScript, Quirks};
+
+const KEYS: u8 = 16;
+const MAGIC: &str = "chip-8 movie 1";
+
+// A recording of everything that went into a run, so it can be replayed
+// exactly: the ROM, the settings the machine was started with, and every key
+// press and release with the frame it happened on. The screen is hashed at
+// the end of every frame so a replay can tell as soon as it goes astray.
+//
+// Movies are text:
+//
+//     chip-8 movie 1
+//     rom 5f3a09c2
+//     quirks vip
+//     xo-chip no
+//     seed 1234
+//     cycles 8
+//     frames 600
+//     0 hash 4d01f1a2
+//     30 press 5
+//     30 hash 0b8e71d3
+//     38 release 5
+//
+// `rom` is the CRC-32 of the ROM and `cycles` the instructions run each
+// frame. Key events happen at the start of their frame, as in a headless key
+// script, and `hash` is the screen at the end of its frame; it's only
+// written when the screen changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    pub rom_hash: u32,
+    pub quirks: Quirks,
+    pub xo_chip: bool,
+    pub seed: u64,
+    pub instructions_per_frame: u32,
+    pub frames: u64,
+    pub events: Vec<KeyEvent>,
+    // (frame, hash) for every frame that changed the screen
+    pub hashes: Vec<(u64, u32)>,
+}
+
+impl Movie {
+    pub fn parse(text: &str) -> Result<Movie, ScriptError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.split('#').next().unwrap_or_default()))
+            .filter(|(_, line)| !line.trim().is_empty());
+
+        match lines.next() {
+            Some((_, line)) if line.trim() == MAGIC => {}
+            other => {
+                return Err(ScriptError {
+                    line: other.map_or(1, |(line, _)| line),
+                    message: "not a movie".to_string(),
+                })
+            }
+        }
+
+        let mut movie = Movie {
+            rom_hash: 0,
+            quirks: Quirks::default(),
+            xo_chip: false,
+            seed: 0,
+            instructions_per_frame: 0,
+            frames: 0,
+            events: vec![],
+            hashes: vec![],
+        };
+        let mut missing = vec!["rom", "quirks", "xo-chip", "seed", "cycles", "frames"];
+        let mut last_frame = 0;
+
+        for (line, text) in lines {
+            let error = |message: String| ScriptError { line, message };
+            let words: Vec<&str> = text.split_whitespace().collect();
+            let invalid = || error(format!("invalid {}: {}", words[0], words[1..].join(" ")));
+
+            if words[0].starts_with(|c: char| c.is_ascii_digit()) {
+                let frame = if words.get(1) == Some(&"hash") && words.len() == 3 {
+                    let frame = words[0].parse().map_err(|_| invalid())?;
+                    let hash = u32::from_str_radix(words[2], 16).map_err(|_| invalid())?;
+
+                    movie.hashes.push((frame, hash));
+                    frame
+                } else {
+                    let event = parse_event(&words).map_err(error)?;
+
+                    movie.events.push(event);
+                    event.frame
+                };
+
+                if frame < last_frame {
+                    return Err(error(format!("frame {} is out of order", frame)));
+                }
+
+                last_frame = frame;
+                continue;
+            }
+
+            if words.len() != 2 {
+                return Err(error(format!("expected {} VALUE", words[0])));
+            }
+
+            let value = words[1];
+
+            match words[0] {
+                "rom" => movie.rom_hash = u32::from_str_radix(value, 16).map_err(|_| invalid())?,
+                "quirks" => {
+                    movie.quirks = match value.strip_prefix("0x") {
+                        Some(bits) => {
+                            Quirks::from_bits(u8::from_str_radix(bits, 16).map_err(|_| invalid())?)
+                        }
+                        None => Quirks::from_name(value).ok_or_else(invalid)?,
+                    }
+                }
+                "xo-chip" => {
+                    movie.xo_chip = match value {
+                        "yes" => true,
+                        "no" => false,
+                        _ => return Err(invalid()),
+                    }
+                }
+                "seed" => movie.seed = value.parse().map_err(|_| invalid())?,
+                "cycles" => {
+                    movie.instructions_per_frame = match value.parse() {
+                        Ok(n) if n > 0 => n,
+                        _ => return Err(invalid()),
+                    }
+                }
+                "frames" => movie.frames = value.parse().map_err(|_| invalid())?,
+                name => return Err(error(format!("unknown setting: {}", name))),
+            }
+
+            missing.retain(|name| *name != words[0]);
+        }
+
+        if let Some(name) = missing.first() {
+            // blamed on the header, which is where it belongs
+            return Err(ScriptError {
+                line: 1,
+                message: format!("movie has no {} line", name),
+            });
+        }
+
+        Ok(movie)
+    }
+
+    // The key presses and releases, to feed to a headless run.
+    pub fn key_script(&self) -> KeyScript {
+        KeyScript::from_events(self.events.clone())
+    }
+
+    // Checks the screen at the end of `frame` against the hash recorded for
+    // it.
+    pub fn check_frame(&self, frame: u64, chip8: &Chip8) -> Result<(), DesyncError> {
+        // the last hash written at or before this frame
+        let index = self.hashes.partition_point(|(f, _)| *f <= frame);
+        let expected = match index.checked_sub(1) {
+            Some(index) => self.hashes[index].1,
+            None => return Ok(()),
+        };
+        let actual = screen_hash(chip8);
+
+        if actual == expected {
+            Ok(())
+        } else {
+            Err(DesyncError {
+                frame,
+                expected,
+                actual,
+            })
+        }
+    }
+}
+
+impl fmt::Display for Movie {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", MAGIC)?;
+        writeln!(f, "rom {:08x}", self.rom_hash)?;
+
+        match self.quirks.name() {
+            Some(name) => writeln!(f, "quirks {}", name)?,
+            None => writeln!(f, "quirks {:#04x}", self.quirks.to_bits())?,
+        }
+
+        writeln!(f, "xo-chip {}", if self.xo_chip { "yes" } else { "no" })?;
+        writeln!(f, "seed {}", self.seed)?;
+        writeln!(f, "cycles {}", self.instructions_per_frame)?;
+        writeln!(f, "frames {}", self.frames)?;
+
+        // events come before the hash for the same frame, since they happen
+        // at its start
+        let mut events = self.events.iter().peekable();
+
+        for (frame, hash) in &self.hashes {
+            while let Some(event) = events.next_if(|event| event.frame <= *frame) {
+                write_event(f, event)?;
+            }
+
+            writeln!(f, "{} hash {:08x}", frame, hash)?;
+        }
+
+        for event in events {
+            write_event(f, event)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn write_event(f: &mut fmt::Formatter<'_>, event: &KeyEvent) -> fmt::Result {
+    let action = if event.pressed { "press" } else { "release" };
+
+    writeln!(f, "{} {} {:X}", event.frame, action, event.key)
+}
+
+// The CRC-32 of the pixels on screen and the resolution.
+pub fn screen_hash(chip8: &Chip8) -> u32 {
+    let mut data = chip8.gfx[..chip8.width() * chip8.height()].to_vec();
+
+    data.push(chip8.hires() as u8);
+    crc32(&data)
+}
+
+// The CRC-32 a movie records for a ROM.
+pub fn rom_hash(rom: &[u8]) -> u32 {
+    crc32(rom)
+}
+
+// Records a movie from a running machine. Call `start_frame` once input for a
+// frame has been handled, just before its instructions run, and `end_frame`
+// once the frame is over.
+pub struct MovieRecorder {
+    movie: Movie,
+    keys: [bool; KEYS as usize],
+    last_hash: Option<u32>,
+}
+
+impl MovieRecorder {
+    pub fn new(
+        rom: &[u8],
+        quirks: Quirks,
+        xo_chip: bool,
+        seed: u64,
+        instructions_per_frame: u32,
+    ) -> Self {
+        MovieRecorder {
+            movie: Movie {
+                rom_hash: rom_hash(rom),
+                quirks,
+                xo_chip,
+                seed,
+                instructions_per_frame,
+                frames: 0,
+                events: vec![],
+                hashes: vec![],
+            },
+            keys: [false; KEYS as usize],
+            last_hash: None,
+        }
+    }
+
+    // The number of frames recorded so far.
+    pub fn frames(&self) -> u64 {
+        self.movie.frames
+    }
+
+    // Records whichever keys went down or up since the last frame.
+    pub fn start_frame(&mut self, chip8: &Chip8) {
+        for key in 0..KEYS {
+            let pressed = chip8.is_key_down(key);
+
+            if pressed != self.keys[key as usize] {
+                self.keys[key as usize] = pressed;
+                self.movie.events.push(KeyEvent {
+                    frame: self.movie.frames,
+                    key,
+                    pressed,
+                });
+            }
+        }
+    }
+
+    pub fn end_frame(&mut self, chip8: &Chip8) {
+        let hash = screen_hash(chip8);
+
+        if self.last_hash != Some(hash) {
+            self.last_hash = Some(hash);
+            self.movie.hashes.push((self.movie.frames, hash));
+        }
+
+        self.movie.frames += 1;
+    }
+
+    pub fn finish(self) -> Movie {
+        self.movie
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rom_hash, Movie, MovieRecorder};
+    use crate::{Chip8, Headless, Quirks};
+
+    // waits for a key, draws its digit, clears the screen, and starts again
+    //
+    //     v0 := key; i := hex v0; clear; sprite v1 v1 5; jump 0x200
+    const PROGRAM: [u8; 10] = [0xF0, 0x0A, 0xF0, 0x29, 0x00, 0xE0, 0xD1, 0x15, 0x12, 0x00];
+
+    fn machine(movie: &Movie) -> Chip8 {
+        let mut chip8 = Chip8::new(movie.quirks);
+
+        chip8.seed(movie.seed);
+        chip8.load_program(PROGRAM.to_vec()).unwrap();
+        chip8
+    }
+
+    // presses 3 on frame 2 and 7 on frame 5, releasing each a frame later
+    fn record() -> Movie {
+        let mut recorder = MovieRecorder::new(&PROGRAM, Quirks::COSMAC_VIP, false, 42, 10);
+        let mut chip8 = machine(&recorder.movie);
+
+        for frame in 0..8 {
+            match frame {
+                2 => chip8.key_down(3),
+                3 => chip8.key_up(3),
+                5 => chip8.key_down(7),
+                6 => chip8.key_up(7),
+                _ => {}
+            }
+
+            recorder.start_frame(&chip8);
+            chip8.run_frame(10).unwrap();
+            recorder.end_frame(&chip8);
+        }
+
+        recorder.finish()
+    }
+
+    #[test]
+    fn test_record_and_parse() {
+        let movie = record();
+        let text = movie.to_string();
+
+        assert_eq!(movie.rom_hash, rom_hash(&PROGRAM));
+        assert_eq!(movie.frames, 8);
+        assert_eq!(movie.events.len(), 4);
+        // blank, then 3 once it's released, then 7
+        assert_eq!(movie.hashes.len(), 3);
+        assert!(text.starts_with("chip-8 movie 1\nrom "));
+        assert!(text.contains("\nquirks vip\nxo-chip no\nseed 42\ncycles 10\nframes 8\n"));
+        assert!(text.contains("\n2 press 3\n3 release 3\n3 hash "));
+        assert_eq!(Movie::parse(&text).unwrap(), movie);
+    }
+
+    #[test]
+    fn test_replay() {
+        let movie = record();
+        let mut chip8 = machine(&movie);
+        let mut headless = Headless::new(
+            movie.instructions_per_frame,
+            Some(movie.frames),
+            None,
+            movie.key_script(),
+        );
+
+        while !headless.is_finished() {
+            let frame = headless.frame();
+
+            headless.run_frame(&mut chip8).unwrap();
+            movie.check_frame(frame, &chip8).unwrap();
+        }
+
+        // pressing a different key draws a different digit
+        let mut altered = movie.clone();
+
+        altered.events[2].key = 8;
+        altered.events[3].key = 8;
+
+        let mut chip8 = machine(&altered);
+        let mut headless = Headless::new(10, Some(8), None, altered.key_script());
+        let mut desync = None;
+
+        while desync.is_none() && !headless.is_finished() {
+            let frame = headless.frame();
+
+            headless.run_frame(&mut chip8).unwrap();
+            desync = movie.check_frame(frame, &chip8).err();
+        }
+
+        assert_eq!(desync.map(|d| d.frame), Some(6));
+    }
+
+    #[test]
+    fn test_bad_movies() {
+        let text = record().to_string();
+
+        for (bad, message) in [
+            ("", "line 1: not a movie"),
+            ("chip-8 movie 2\n", "line 1: not a movie"),
+            (
+                &text.replace("quirks vip", "quirks odd")[..],
+                "line 3: invalid quirks: odd",
+            ),
+            (
+                &text.replace("seed 42\n", "")[..],
+                "line 1: movie has no seed line",
+            ),
+            (
+                &text.replace("5 press 7", "1 press 7")[..],
+                "line 12: frame 1 is out of order",
+            ),
+        ]
+        .iter()
+        {
+            assert_eq!(Movie::parse(bad).unwrap_err().to_string(), *message);
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use crate::{Framebuffer, Region};
+
+// The number of steps a pixel fades through between lit and off.
+pub const SHADES: u8 = 4;
+
+// The number of colors a shaded display uses: the four XO-CHIP colors, then
+// `SHADES` fading steps of each of the three lit ones.
+pub const SHADED_COLORS: usize = 4 + 3 * SHADES as usize;
+
+// The longest fade `Persistence::parse` accepts, ten seconds' worth of frames.
+pub const MAX_FADE_FRAMES: u32 = 600;
+
+// The shaded color for XO-CHIP color `color` (1-3) fading at `level`, from 1
+// (almost gone) to `SHADES` (almost lit).
+pub fn shade(color: u8, level: u8) -> u8 {
+    4 + (color - 1) * SHADES + (level - 1)
+}
+
+// The XO-CHIP color and fading level of a shaded color, or `None` if it's
+// one of the plain XO-CHIP colors.
+pub fn fading(shaded: u8) -> Option<(u8, u8)> {
+    if shaded < 4 {
+        return None;
+    }
+
+    let step = shaded - 4;
+
+    Some((step / SHADES + 1, step % SHADES + 1))
+}
+
+// Whether a shaded color should count as lit when there's only lit and off,
+// as in a PBM image.
+pub fn is_lit(shaded: u8) -> bool {
+    match fading(shaded) {
+        Some((_, level)) => level > SHADES / 2,
+        None => shaded != 0,
+    }
+}
+
+// How long pixels stay visible after they're turned off. CHIP-8 programs
+// move sprites by erasing and redrawing them with XOR, so they flicker
+// unless the display holds on to what was there a moment ago, as a CRT's
+// phosphor did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Persistence {
+    #[default]
+    Off,
+    // pixels fade out over this many frames
+    Fade(u32),
+    // each frame is shown together with the one before
+    Blend,
+}
+
+impl Persistence {
+    // Parses `off`, `blend`, or the number of frames to fade over, up to
+    // `MAX_FADE_FRAMES`.
+    pub fn parse(text: &str) -> Option<Persistence> {
+        match text.to_lowercase().as_str() {
+            "off" | "0" => Some(Persistence::Off),
+            "blend" => Some(Persistence::Blend),
+            frames => match frames.parse() {
+                Ok(frames) if frames <= MAX_FADE_FRAMES => Some(Persistence::Fade(frames)),
+                _ => None,
+            },
+        }
+    }
+}
+
+// Turns the frames a machine draws into what a display with persistence
+// shows. `update` takes each 60 Hz frame in turn, and `framebuffer` is then
+// the picture to show, in shaded colors; its dirty region covers every pixel
+// that changed since the last update. Without persistence the frames are
+// passed through as they are.
+pub struct Phosphor {
+    persistence: Persistence,
+    width: usize,
+    height: usize,
+    // the last frame's pixels, for blending
+    previous: Vec<u8>,
+    // the color each pixel was last lit in, and how many more frames it
+    // glows for
+    glow: Vec<(u8, u32)>,
+    shown: Vec<u8>,
+    dirty: Region,
+}
+
+impl Phosphor {
+    pub fn new(persistence: Persistence) -> Self {
+        Phosphor {
+            persistence,
+            width: 0,
+            height: 0,
+            previous: vec![],
+            glow: vec![],
+            shown: vec![],
+            dirty: Region::default(),
+        }
+    }
+
+    pub fn persistence(&self) -> Persistence {
+        self.persistence
+    }
+
+    // Whether there's a frame to show yet.
+    pub fn is_empty(&self) -> bool {
+        self.shown.is_empty()
+    }
+
+    pub fn update(&mut self, framebuffer: &Framebuffer<'_>) {
+        let (width, height) = (framebuffer.width, framebuffer.height);
+
+        // nothing carries over a change of resolution
+        let reset = (width, height) != (self.width, self.height) || self.shown.is_empty();
+
+        if reset {
+            self.width = width;
+            self.height = height;
+            self.previous = vec![0; width * height];
+            self.glow = vec![(0, 0); width * height];
+            self.shown = vec![0; width * height];
+        }
+
+        let mut dirty = Region::default();
+
+        if self.persistence == Persistence::Off {
+            self.shown.copy_from_slice(framebuffer.pixels);
+            dirty = framebuffer.dirty;
+        } else {
+            for (i, pixel) in framebuffer.pixels.iter().copied().enumerate() {
+                let shown = self.show(i, pixel);
+
+                if shown != self.shown[i] {
+                    self.shown[i] = shown;
+                    dirty = dirty.union(Region::new(i % width, i / width, 1, 1));
+                }
+
+                self.previous[i] = pixel;
+            }
+        }
+
+        self.dirty = if reset {
+            Region::new(0, 0, width, height)
+        } else {
+            dirty
+        };
+    }
+
+    // what pixel `i` shows, now that it's `pixel`
+    fn show(&mut self, i: usize, pixel: u8) -> u8 {
+        match self.persistence {
+            Persistence::Off => pixel,
+            Persistence::Blend => pixel | self.previous[i],
+            Persistence::Fade(frames) => {
+                let (color, left) = &mut self.glow[i];
+
+                if pixel != 0 {
+                    *color = pixel;
+                    *left = frames;
+                    pixel
+                } else if *left > 0 && *color != 0 {
+                    let level = (*left as u64 * SHADES as u64).div_ceil(frames as u64);
+
+                    *left -= 1;
+                    shade(*color, level as u8)
+                } else {
+                    0
+                }
+            }
+        }
+    }
+
+    pub fn framebuffer(&self) -> Framebuffer<'_> {
+        Framebuffer {
+            width: self.width,
+            height: self.height,
+            pixels: &self.shown,
+            dirty: self.dirty,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fading, is_lit, shade, Persistence, Phosphor, SHADES};
+    use crate::{Framebuffer, Region};
+
+    // runs 1x2 frames through a phosphor, returning what's shown after each
+    fn show(persistence: Persistence, frames: &[[u8; 2]]) -> Vec<Vec<u8>> {
+        let mut phosphor = Phosphor::new(persistence);
+
+        frames
+            .iter()
+            .map(|pixels| {
+                phosphor.update(&Framebuffer {
+                    width: 2,
+                    height: 1,
+                    pixels,
+                    dirty: Region::new(0, 0, 2, 1),
+                });
+                phosphor.framebuffer().pixels.to_vec()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_shades() {
+        assert_eq!(fading(2), None);
+        assert_eq!(fading(shade(1, 1)), Some((1, 1)));
+        assert_eq!(fading(shade(3, SHADES)), Some((3, SHADES)));
+        assert!(is_lit(shade(2, SHADES)));
+        assert!(!is_lit(shade(2, 1)));
+        assert_eq!(Persistence::parse("Blend"), Some(Persistence::Blend));
+        assert_eq!(Persistence::parse("6"), Some(Persistence::Fade(6)));
+        assert_eq!(Persistence::parse("0"), Some(Persistence::Off));
+        assert_eq!(Persistence::parse("slow"), None);
+        assert_eq!(Persistence::parse("600"), Some(Persistence::Fade(600)));
+        assert_eq!(Persistence::parse("2000000000"), None);
+    }
+
+    #[test]
+    fn test_long_fade() {
+        let frames = show(Persistence::Fade(u32::MAX), &[[1, 0], [0, 0]]);
+
+        assert_eq!(frames[1], vec![shade(1, SHADES), 0]);
+    }
+
+    #[test]
+    fn test_persistence() {
+        let frames = [[1, 0], [0, 1], [0, 0], [0, 0], [0, 0]];
+
+        assert_eq!(show(Persistence::Off, &frames), frames.map(|f| f.to_vec()));
+        assert_eq!(
+            show(Persistence::Blend, &frames),
+            vec![vec![1, 0], vec![1, 1], vec![0, 1], vec![0, 0], vec![0, 0]]
+        );
+        // fading over two frames goes through every other shade
+        assert_eq!(
+            show(Persistence::Fade(2), &frames),
+            vec![
+                vec![1, 0],
+                vec![shade(1, SHADES), 1],
+                vec![shade(1, SHADES / 2), shade(1, SHADES)],
+                vec![0, shade(1, SHADES / 2)],
+                vec![0, 0]
+            ]
+        );
+    }
+}
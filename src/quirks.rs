@@ -0,0 +1,108 @@
+// Several CHIP-8 instructions were implemented differently by the original
+// COSMAC VIP interpreter and by the later CHIP-48 and SUPER-CHIP ones. ROMs
+// written for one interpreter often misbehave on another, so each ambiguous
+// behavior can be toggled independently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    // 8XY6/8XYE: shift VY into VX instead of shifting VX in place
+    pub shift_uses_vy: bool,
+    // FX55/FX65: leave I pointing just past the last register stored/loaded
+    pub load_store_increments_i: bool,
+    // FX55/FX65: when I is left moved, leave it on the last register instead,
+    // as CHIP-48 did
+    pub load_store_increments_i_by_x: bool,
+    // BNNN: jump to NNN plus VX (BXNN) instead of NNN plus V0
+    pub jump_uses_vx: bool,
+    // DXYN: wrap sprite pixels around the screen edge instead of clipping them
+    pub sprites_wrap: bool,
+    // 8XY1/8XY2/8XY3: reset VF to 0 after the logic operation
+    pub logic_resets_vf: bool,
+}
+
+impl Quirks {
+    pub const COSMAC_VIP: Quirks = Quirks {
+        shift_uses_vy: true,
+        load_store_increments_i: true,
+        load_store_increments_i_by_x: false,
+        jump_uses_vx: false,
+        sprites_wrap: false,
+        logic_resets_vf: true,
+    };
+
+    pub const CHIP_48: Quirks = Quirks {
+        shift_uses_vy: false,
+        load_store_increments_i: true,
+        load_store_increments_i_by_x: true,
+        jump_uses_vx: true,
+        sprites_wrap: false,
+        logic_resets_vf: false,
+    };
+
+    pub const SUPER_CHIP: Quirks = Quirks {
+        shift_uses_vy: false,
+        load_store_increments_i: false,
+        load_store_increments_i_by_x: false,
+        jump_uses_vx: true,
+        sprites_wrap: false,
+        logic_resets_vf: false,
+    };
+
+    // The behavior this emulator has always had
+    pub const LEGACY: Quirks = Quirks {
+        shift_uses_vy: false,
+        load_store_increments_i: false,
+        load_store_increments_i_by_x: false,
+        jump_uses_vx: false,
+        sprites_wrap: true,
+        logic_resets_vf: false,
+    };
+
+    pub const PROFILE_NAMES: [&'static str; 4] = ["legacy", "vip", "chip48", "schip"];
+
+    pub fn from_name(name: &str) -> Option<Quirks> {
+        match name.to_lowercase().as_str() {
+            "legacy" => Some(Quirks::LEGACY),
+            "vip" | "cosmac-vip" => Some(Quirks::COSMAC_VIP),
+            "chip48" | "chip-48" => Some(Quirks::CHIP_48),
+            "schip" | "super-chip" => Some(Quirks::SUPER_CHIP),
+            _ => None,
+        }
+    }
+
+    // The name of the profile these quirks match, if any.
+    pub fn name(self) -> Option<&'static str> {
+        Quirks::PROFILE_NAMES
+            .iter()
+            .copied()
+            .find(|name| Quirks::from_name(name) == Some(self))
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks::LEGACY
+    }
+}
+
+impl Quirks {
+    // packs the toggles into a byte for save states
+    pub(crate) fn to_bits(self) -> u8 {
+        (self.shift_uses_vy as u8)
+            | (self.load_store_increments_i as u8) << 1
+            | (self.jump_uses_vx as u8) << 2
+            | (self.sprites_wrap as u8) << 3
+            | (self.logic_resets_vf as u8) << 4
+            | (self.load_store_increments_i_by_x as u8) << 5
+    }
+
+    pub(crate) fn from_bits(bits: u8) -> Quirks {
+        Quirks {
+            shift_uses_vy: bits & 1 != 0,
+            load_store_increments_i: bits & 1 << 1 != 0,
+            jump_uses_vx: bits & 1 << 2 != 0,
+            sprites_wrap: bits & 1 << 3 != 0,
+            logic_resets_vf: bits & 1 << 4 != 0,
+            load_store_increments_i_by_x: bits & 1 << 5 != 0,
+        }
+    }
+}
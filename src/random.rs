@@ -0,0 +1,121 @@
+// Where CXNN gets its random numbers from. Swapping the source out makes
+// runs reproducible and lets tests script exactly what a ROM sees.
+pub trait RandomSource {
+    fn next_byte(&mut self) -> u8;
+
+    // The source's position in its sequence, so save states can capture it.
+    // Sources that can't be captured return `None`.
+    fn state(&self) -> Option<u64> {
+        None
+    }
+
+    fn set_state(&mut self, _state: u64) {}
+}
+
+// The default source: a small SplitMix64 generator, so the same seed always
+// produces the same sequence no matter which platform or `rand` version is in use.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeededRandom {
+    state: u64,
+}
+
+impl SeededRandom {
+    pub fn new(seed: u64) -> Self {
+        SeededRandom { state: seed }
+    }
+
+    pub fn from_entropy() -> Self {
+        SeededRandom::new(rand::random())
+    }
+}
+
+impl RandomSource for SeededRandom {
+    fn state(&self) -> Option<u64> {
+        Some(self.state)
+    }
+
+    fn set_state(&mut self, state: u64) {
+        self.state = state;
+    }
+
+    fn next_byte(&mut self) -> u8 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+
+        (z >> 56) as u8
+    }
+}
+
+// Hands out a fixed list of values, starting over once it runs out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptedRandom {
+    values: Vec<u8>,
+    index: usize,
+}
+
+impl ScriptedRandom {
+    pub fn new(values: Vec<u8>) -> Self {
+        ScriptedRandom { values, index: 0 }
+    }
+}
+
+impl RandomSource for ScriptedRandom {
+    fn state(&self) -> Option<u64> {
+        Some(self.index as u64)
+    }
+
+    fn set_state(&mut self, state: u64) {
+        self.index = state as usize % self.values.len().max(1);
+    }
+
+    fn next_byte(&mut self) -> u8 {
+        if self.values.is_empty() {
+            return 0;
+        }
+
+        let value = self.values[self.index];
+        self.index = (self.index + 1) % self.values.len();
+
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{RandomSource, ScriptedRandom, SeededRandom};
+
+    #[test]
+    fn test_same_seed_same_sequence() {
+        let mut a = SeededRandom::new(1234);
+        let mut b = SeededRandom::new(1234);
+
+        let a: Vec<u8> = (0..32).map(|_| a.next_byte()).collect();
+        let b: Vec<u8> = (0..32).map(|_| b.next_byte()).collect();
+
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_different_seeds_differ() {
+        let mut a = SeededRandom::new(1);
+        let mut b = SeededRandom::new(2);
+
+        let a: Vec<u8> = (0..32).map(|_| a.next_byte()).collect();
+        let b: Vec<u8> = (0..32).map(|_| b.next_byte()).collect();
+
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_scripted_values_repeat() {
+        let mut r = ScriptedRandom::new(vec![1, 2, 3]);
+
+        let values: Vec<u8> = (0..5).map(|_| r.next_byte()).collect();
+
+        assert_eq!(values, vec![1, 2, 3, 1, 2]);
+    }
+}
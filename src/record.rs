@@ -0,0 +1,204 @@
+use std::io::{self, Write};
+
+use crate::image::{image_colors, render, render_to};
+use crate::{Chip8, Palette, Persistence, Phosphor};
+
+// GIF delays are in hundredths of a second, and most viewers play anything
+// shorter than two of them far too slowly
+const MIN_DELAY: u64 = 2;
+const FRAMES_PER_SECOND: u64 = 60;
+
+// Records the display as an animated GIF; call `capture` once every 60 Hz
+// frame and `finish` at the end.
+//
+// The image size is fixed by the first frame, so a later change between
+// lo-res and hi-res is stretched to fit. A frame that's the same as the one
+// before just holds that one on screen for longer, and one that would be on
+// screen for less than `MIN_DELAY` is dropped in favor of the next. With
+// persistence, the recording shows pixels fading as the terminal does.
+pub struct GifRecorder<W: Write> {
+    encoder: gif::Encoder<W>,
+    phosphor: Phosphor,
+    width: usize,
+    height: usize,
+    frames: u64,
+    // the last frame captured and the frame number it first appeared on;
+    // it's written once it's known how long it stays on screen
+    pending: Option<(Vec<u8>, u64)>,
+}
+
+impl<W: Write> GifRecorder<W> {
+    pub fn new(
+        output: W,
+        chip8: &Chip8,
+        scale: u32,
+        palette: &Palette,
+        persistence: Persistence,
+    ) -> io::Result<Self> {
+        let (width, height, _) = render(&chip8.framebuffer(), scale);
+        let colors = image_colors(palette, persistence != Persistence::Off);
+        let mut encoder =
+            gif::Encoder::new(output, width as u16, height as u16, &colors).map_err(gif_error)?;
+
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(gif_error)?;
+
+        Ok(GifRecorder {
+            encoder,
+            phosphor: Phosphor::new(persistence),
+            width,
+            height,
+            frames: 0,
+            pending: None,
+        })
+    }
+
+    // The number of frames captured so far.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn capture(&mut self, chip8: &Chip8) -> io::Result<()> {
+        self.phosphor.update(&chip8.framebuffer());
+
+        let pixels = render_to(&self.phosphor.framebuffer(), self.width, self.height);
+        let frame = self.frames;
+
+        self.frames += 1;
+
+        self.pending = match self.pending.take() {
+            Some((last, start)) if last == pixels => Some((last, start)),
+            Some((last, start)) if centiseconds(frame) - centiseconds(start) >= MIN_DELAY => {
+                self.write(last, start, frame)?;
+                Some((pixels, frame))
+            }
+            Some((_, start)) => Some((pixels, start)),
+            None => Some((pixels, frame)),
+        };
+
+        Ok(())
+    }
+
+    // Writes the last frame and the end of the GIF, and hands back the output.
+    pub fn finish(mut self) -> io::Result<W> {
+        if let Some((last, start)) = self.pending.take() {
+            let end = self.frames.max(start + 1);
+
+            self.write(last, start, end)?;
+        }
+
+        self.encoder.into_inner()
+    }
+
+    // writes a frame that was on screen from frame `start` until `end`
+    fn write(&mut self, pixels: Vec<u8>, start: u64, end: u64) -> io::Result<()> {
+        let delay = (centiseconds(end) - centiseconds(start)).max(MIN_DELAY);
+        let mut frame =
+            gif::Frame::from_indexed_pixels(self.width as u16, self.height as u16, pixels, None);
+
+        frame.delay = delay.min(u16::MAX as u64) as u16;
+        self.encoder.write_frame(&frame).map_err(gif_error)
+    }
+}
+
+// when frame `frame` starts, rounded to the nearest hundredth of a second
+fn centiseconds(frame: u64) -> u64 {
+    (frame * 100 + FRAMES_PER_SECOND / 2) / FRAMES_PER_SECOND
+}
+
+fn gif_error(error: gif::EncodingError) -> io::Error {
+    match error {
+        gif::EncodingError::Io(e) => e,
+        e => io::Error::other(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GifRecorder;
+    use crate::{Chip8, Instruction, Palette, Persistence, Quirks};
+
+    fn decode(gif: &[u8]) -> Vec<(u16, u16, u16)> {
+        let mut options = gif::DecodeOptions::new();
+
+        options.set_color_output(gif::ColorOutput::Indexed);
+
+        let mut decoder = options.read_info(gif).unwrap();
+        let mut frames = vec![];
+
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            frames.push((frame.width, frame.height, frame.delay));
+        }
+
+        frames
+    }
+
+    #[test]
+    fn test_recording() {
+        let mut chip8 = Chip8::new(Quirks::default());
+        let mut recorder =
+            GifRecorder::new(vec![], &chip8, 2, &Palette::default(), Persistence::Off).unwrap();
+
+        // a second of a blank screen, then a pixel that blinks every frame,
+        // which is too fast to keep every frame, then stays lit
+        for frame in 0..90 {
+            if (60..65).contains(&frame) {
+                chip8.gfx[0] ^= 1;
+            }
+
+            recorder.capture(&chip8).unwrap();
+        }
+
+        assert_eq!(recorder.frames(), 90);
+
+        let gif = recorder.finish().unwrap();
+
+        assert_eq!(
+            decode(&gif),
+            vec![
+                (128, 64, 100),
+                (128, 64, 2),
+                (128, 64, 3),
+                (128, 64, 2),
+                (128, 64, 43)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolution_change() {
+        let mut chip8 = Chip8::new(Quirks::default());
+        let mut recorder =
+            GifRecorder::new(vec![], &chip8, 1, &Palette::default(), Persistence::Off).unwrap();
+
+        recorder.capture(&chip8).unwrap();
+        chip8.execute(Instruction::decode(0x00FF).unwrap()).unwrap();
+        chip8.gfx[0] = 1;
+        recorder.capture(&chip8).unwrap();
+
+        let gif = recorder.finish().unwrap();
+
+        assert_eq!(decode(&gif), vec![(64, 32, 2), (64, 32, 2)]);
+    }
+
+    #[test]
+    fn test_persistence() {
+        let mut chip8 = Chip8::new(Quirks::default());
+        let mut recorder =
+            GifRecorder::new(vec![], &chip8, 1, &Palette::default(), Persistence::Blend).unwrap();
+
+        // a pixel lit for a single frame stays on screen for two
+        for frame in 0..30 {
+            chip8.gfx[0] = (frame == 0) as u8;
+            recorder.capture(&chip8).unwrap();
+        }
+
+        let gif = recorder.finish().unwrap();
+        let decoder = gif::DecodeOptions::new().read_info(&gif[..]).unwrap();
+
+        // recordings with persistence get the shaded colors
+        assert_eq!(decoder.global_palette().map(<[u8]>::len), Some(16 * 3));
+        assert_eq!(decode(&gif), vec![(64, 32, 3), (64, 32, 47)]);
+    }
+}
@@ -0,0 +1,313 @@
+use crate::persistence::SHADED_COLORS;
+use crate::Framebuffer;
+
+const UPPER_HALF: char = '\u{2580}';
+const LOWER_HALF: char = '\u{2584}';
+const FULL_BLOCK: char = '\u{2588}';
+const BRAILLE: u32 = 0x2800;
+
+// the braille dot for each pixel of a 2x4 cell, by row and then column
+const BRAILLE_DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+// How a terminal frontend packs pixels into character cells.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Renderer {
+    // a `*` for every lit pixel
+    #[default]
+    Ascii,
+    // Unicode half blocks, two rows of pixels to a cell
+    HalfBlock,
+    // braille patterns, 2x4 pixels to a cell, so a hi-res display fits in
+    // 80x24
+    Braille,
+}
+
+// A character cell: a glyph drawn in one shaded color on another; without
+// persistence, those are just the XO-CHIP colors (0-3).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub glyph: char,
+    pub foreground: u8,
+    pub background: u8,
+}
+
+impl Cell {
+    const BLANK: Cell = Cell {
+        glyph: ' ',
+        foreground: 0,
+        background: 0,
+    };
+
+    // never rendered, so it's replaced by whatever is
+    const NONE: Cell = Cell {
+        glyph: '\0',
+        foreground: 0,
+        background: 0,
+    };
+
+    fn new(glyph: char, foreground: u8) -> Cell {
+        Cell {
+            glyph,
+            foreground,
+            background: 0,
+        }
+    }
+}
+
+impl Renderer {
+    pub const NAMES: [&'static str; 3] = ["ascii", "half", "braille"];
+
+    pub fn from_name(name: &str) -> Option<Renderer> {
+        match name.to_lowercase().as_str() {
+            "ascii" => Some(Renderer::Ascii),
+            "half" => Some(Renderer::HalfBlock),
+            "braille" => Some(Renderer::Braille),
+            _ => None,
+        }
+    }
+
+    // The number of pixels across and down in each cell.
+    pub fn cell_size(self) -> (usize, usize) {
+        match self {
+            Renderer::Ascii => (1, 1),
+            Renderer::HalfBlock => (1, 2),
+            Renderer::Braille => (2, 4),
+        }
+    }
+
+    // The number of columns and rows of cells a display takes up.
+    pub fn cells(self, width: usize, height: usize) -> (usize, usize) {
+        let (across, down) = self.cell_size();
+
+        (width.div_ceil(across), height.div_ceil(down))
+    }
+
+    // The cell at `column`, `row`; pixels past the edge of the display are
+    // left unlit.
+    pub fn cell(self, framebuffer: &Framebuffer<'_>, column: usize, row: usize) -> Cell {
+        let (across, down) = self.cell_size();
+        let pixel = |x: usize, y: usize| -> u8 {
+            let (x, y) = (column * across + x, row * down + y);
+
+            if x < framebuffer.width && y < framebuffer.height {
+                framebuffer.pixel(x, y)
+            } else {
+                0
+            }
+        };
+
+        match self {
+            Renderer::Ascii => match pixel(0, 0) {
+                0 => Cell::BLANK,
+                color => Cell::new('*', color),
+            },
+            Renderer::HalfBlock => match (pixel(0, 0), pixel(0, 1)) {
+                (0, 0) => Cell::BLANK,
+                (top, bottom) if top == bottom => Cell::new(FULL_BLOCK, top),
+                (0, bottom) => Cell::new(LOWER_HALF, bottom),
+                (top, bottom) => Cell {
+                    glyph: UPPER_HALF,
+                    foreground: top,
+                    background: bottom,
+                },
+            },
+            Renderer::Braille => {
+                // a cell only has one color, so it takes the commonest one
+                let mut dots = 0;
+                let mut counts = [0; SHADED_COLORS];
+
+                for (y, row) in BRAILLE_DOTS.iter().enumerate() {
+                    for (x, dot) in row.iter().enumerate() {
+                        let color = pixel(x, y);
+
+                        if color != 0 {
+                            dots |= dot;
+                            counts[color as usize] += 1;
+                        }
+                    }
+                }
+
+                let color = (1..SHADED_COLORS)
+                    .rev()
+                    .max_by_key(|c| counts[*c])
+                    .unwrap_or(1);
+
+                match std::char::from_u32(BRAILLE + dots) {
+                    Some(glyph) if dots != 0 => Cell::new(glyph, color as u8),
+                    _ => Cell::BLANK,
+                }
+            }
+        }
+    }
+}
+
+// The cells a terminal frontend last drew, so it only has to redraw the ones
+// that change. Only cells over the framebuffer's dirty region are looked at,
+// except after a change of resolution or `invalidate`, when every cell is.
+pub struct CellGrid {
+    renderer: Renderer,
+    resolution: (usize, usize),
+    cells: Vec<Cell>,
+}
+
+impl CellGrid {
+    pub fn new(renderer: Renderer) -> Self {
+        CellGrid {
+            renderer,
+            resolution: (0, 0),
+            cells: vec![],
+        }
+    }
+
+    pub fn renderer(&self) -> Renderer {
+        self.renderer
+    }
+
+    // Forgets what was drawn, for when the screen has been cleared.
+    pub fn invalidate(&mut self) {
+        self.cells.clear();
+    }
+
+    // The cells that differ from the ones last drawn, by column and row.
+    // They're remembered as drawn.
+    pub fn update(&mut self, framebuffer: &Framebuffer<'_>) -> Vec<(usize, usize, Cell)> {
+        let renderer = self.renderer;
+        let (columns, rows) = renderer.cells(framebuffer.width, framebuffer.height);
+        let resolution = (framebuffer.width, framebuffer.height);
+        let mut changed = vec![];
+
+        // fresh cells are all `None`, so every one is drawn
+        let (left, top, right, bottom) = if self.cells.is_empty() || self.resolution != resolution {
+            self.resolution = resolution;
+            self.cells = vec![Cell::NONE; columns * rows];
+            (0, 0, columns, rows)
+        } else {
+            let dirty = framebuffer.dirty;
+            let (across, down) = renderer.cell_size();
+
+            (
+                dirty.x / across,
+                dirty.y / down,
+                (dirty.x + dirty.width).div_ceil(across).min(columns),
+                (dirty.y + dirty.height).div_ceil(down).min(rows),
+            )
+        };
+
+        for row in top..bottom {
+            for column in left..right {
+                let cell = renderer.cell(framebuffer, column, row);
+                let drawn = &mut self.cells[row * columns + column];
+
+                if *drawn != cell {
+                    *drawn = cell;
+                    changed.push((column, row, cell));
+                }
+            }
+        }
+
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cell, CellGrid, Renderer};
+    use crate::{Framebuffer, Region};
+
+    // a 4x4 display: two lit pixels in the top left, a plane 2 pixel below
+    // them, and a column on the right in both planes
+    const PIXELS: [u8; 16] = [1, 1, 0, 3, 2, 0, 0, 3, 0, 0, 0, 3, 0, 0, 0, 3];
+
+    fn framebuffer() -> Framebuffer<'static> {
+        Framebuffer {
+            width: 4,
+            height: 4,
+            pixels: &PIXELS,
+            dirty: Region::new(0, 0, 4, 4),
+        }
+    }
+
+    fn cells(renderer: Renderer) -> Vec<Cell> {
+        let (columns, rows) = renderer.cells(4, 4);
+        let mut cells = vec![];
+
+        for row in 0..rows {
+            for column in 0..columns {
+                cells.push(renderer.cell(&framebuffer(), column, row));
+            }
+        }
+
+        cells
+    }
+
+    fn glyphs(renderer: Renderer) -> String {
+        cells(renderer).iter().map(|cell| cell.glyph).collect()
+    }
+
+    #[test]
+    fn test_cells() {
+        assert_eq!(Renderer::Ascii.cells(64, 32), (64, 32));
+        assert_eq!(Renderer::HalfBlock.cells(128, 64), (128, 32));
+        assert_eq!(Renderer::Braille.cells(128, 64), (64, 16));
+        assert_eq!(Renderer::Braille.cells(3, 5), (2, 2));
+    }
+
+    #[test]
+    fn test_renderers() {
+        assert_eq!(glyphs(Renderer::Ascii), "** **  *   *   *");
+        assert_eq!(
+            glyphs(Renderer::HalfBlock),
+            "\u{2580}\u{2580} \u{2588}   \u{2588}"
+        );
+        assert_eq!(glyphs(Renderer::Braille), "\u{280B}\u{28B8}");
+
+        // the top of a half block is the foreground, the bottom the background
+        assert_eq!(
+            cells(Renderer::HalfBlock)[0],
+            Cell {
+                glyph: '\u{2580}',
+                foreground: 1,
+                background: 2
+            }
+        );
+        // braille takes the commonest color in the cell
+        assert_eq!(cells(Renderer::Braille)[0].foreground, 1);
+        assert_eq!(cells(Renderer::Braille)[1].foreground, 3);
+    }
+
+    #[test]
+    fn test_renderer_names() {
+        assert_eq!(Renderer::from_name("Braille"), Some(Renderer::Braille));
+        assert_eq!(Renderer::from_name("half"), Some(Renderer::HalfBlock));
+        assert_eq!(Renderer::from_name("sixel"), None);
+    }
+
+    #[test]
+    fn test_cell_grid() {
+        let mut pixels = PIXELS;
+        let mut grid = CellGrid::new(Renderer::HalfBlock);
+        let mut frame = |pixels: &[u8], dirty: Region| {
+            let framebuffer = Framebuffer {
+                width: 4,
+                height: 4,
+                pixels,
+                dirty,
+            };
+
+            grid.update(&framebuffer)
+                .iter()
+                .map(|(column, row, _)| (*column, *row))
+                .collect::<Vec<_>>()
+        };
+
+        // everything is drawn the first time, blank cells included
+        assert_eq!(frame(&pixels, Region::default()).len(), 8);
+        assert_eq!(frame(&pixels, Region::new(0, 0, 4, 4)), vec![]);
+
+        // a change is only seen inside the dirty region
+        pixels[4] = 0;
+        pixels[10] = 1;
+        assert_eq!(frame(&pixels, Region::new(0, 0, 1, 2)), vec![(0, 0)]);
+        assert_eq!(frame(&pixels, Region::new(1, 1, 2, 2)), vec![(2, 1)]);
+    }
+}
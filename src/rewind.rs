@@ -0,0 +1,255 @@
+use std::collections::VecDeque;
+
+use crate::Chip8;
+
+// 30 seconds at 60 frames per second
+pub const DEFAULT_REWIND_FRAMES: usize = 30 * 60;
+
+// A ring buffer of recent machine snapshots, for stepping a game backwards.
+//
+// Only the newest snapshot (a `save_state` blob) is kept whole. Every older
+// one is stored as a delta against the snapshot after it: the two are XORed,
+// which leaves mostly zeroes from one frame to the next, and the zero runs
+// are then run-length encoded. Once `capacity` deltas are held, the oldest
+// is dropped, so memory stays bounded.
+pub struct RewindBuffer {
+    capacity: usize,
+    latest: Option<Vec<u8>>,
+    deltas: VecDeque<Vec<u8>>,
+}
+
+impl Default for RewindBuffer {
+    fn default() -> Self {
+        RewindBuffer::new(DEFAULT_REWIND_FRAMES)
+    }
+}
+
+impl RewindBuffer {
+    pub fn new(capacity: usize) -> Self {
+        RewindBuffer {
+            capacity,
+            latest: None,
+            deltas: VecDeque::new(),
+        }
+    }
+
+    // Records the machine's current state as the newest snapshot.
+    pub fn push(&mut self, chip8: &Chip8) {
+        let snapshot = chip8.save_state();
+
+        if let Some(latest) = self.latest.take() {
+            if latest.len() == snapshot.len() {
+                self.deltas.push_back(encode_delta(&snapshot, &latest));
+
+                while self.deltas.len() > self.capacity {
+                    self.deltas.pop_front();
+                }
+            } else {
+                // the machine changed shape (e.g. a save state switched it to
+                // XO-CHIP), so older snapshots can't be reached from this one
+                self.deltas.clear();
+            }
+        }
+
+        self.latest = Some(snapshot);
+    }
+
+    // Steps back one snapshot and restores it into `chip8`. Returns false,
+    // leaving the machine alone, once there is nothing older to go back to.
+    pub fn rewind(&mut self, chip8: &mut Chip8) -> bool {
+        let delta = match self.deltas.pop_back() {
+            Some(delta) => delta,
+            None => return false,
+        };
+
+        let latest = match self.latest.as_mut() {
+            Some(latest) => latest,
+            None => return false,
+        };
+
+        apply_delta(latest, &delta);
+
+        chip8.load_state(latest).is_ok()
+    }
+
+    // the number of snapshots `rewind` can still step back through
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+    }
+
+    // bytes held by the buffer, for keeping an eye on memory use
+    pub fn memory_used(&self) -> usize {
+        self.latest.as_ref().map_or(0, |l| l.len())
+            + self.deltas.iter().map(|d| d.len()).sum::<usize>()
+    }
+}
+
+// Encodes `from XOR to` as a list of (zero run, literal count, literals...)
+// records, with the two counts stored as LEB128 varints.
+fn encode_delta(from: &[u8], to: &[u8]) -> Vec<u8> {
+    let mut out = vec![];
+    let mut i = 0;
+
+    while i < from.len() {
+        let zero_start = i;
+
+        while i < from.len() && from[i] == to[i] {
+            i += 1;
+        }
+
+        let literal_start = i;
+
+        while i < from.len() && from[i] != to[i] {
+            i += 1;
+        }
+
+        write_varint(&mut out, literal_start - zero_start);
+        write_varint(&mut out, i - literal_start);
+
+        for j in literal_start..i {
+            out.push(from[j] ^ to[j]);
+        }
+    }
+
+    out
+}
+
+// XORs an encoded delta into `data`, turning one snapshot into the other
+fn apply_delta(data: &mut [u8], delta: &[u8]) {
+    let mut position = 0;
+    let mut d = 0;
+
+    while d < delta.len() {
+        let zeroes = read_varint(delta, &mut d);
+        let literals = read_varint(delta, &mut d);
+
+        position += zeroes;
+
+        for b in &delta[d..d + literals] {
+            data[position] ^= b;
+            position += 1;
+        }
+
+        d += literals;
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+
+        out.push(byte | 0x80);
+    }
+}
+
+fn read_varint(data: &[u8], position: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+
+    loop {
+        let byte = data[*position];
+        *position += 1;
+
+        value |= ((byte & 0x7F) as usize) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            return value;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_delta, encode_delta};
+    use crate::{Chip8, RewindBuffer};
+
+    #[test]
+    fn test_delta_round_trip() {
+        let a: Vec<u8> = (0..1000).map(|i| (i % 251) as u8).collect();
+        let mut b = a.clone();
+
+        b[3] = 0;
+        b[500] ^= 0x55;
+        b[501] ^= 0x55;
+        b[999] = 7;
+
+        let delta = encode_delta(&a, &b);
+
+        assert!(delta.len() < 20);
+
+        let mut restored = b.clone();
+        apply_delta(&mut restored, &delta);
+
+        assert_eq!(restored, a);
+    }
+
+    #[test]
+    fn test_rewind_steps_back_through_frames() {
+        // 0x7001: add 1 to V0, then 0x1200: loop
+        let program: Vec<u8> = vec![0x70, 0x01, 0x12, 0x00];
+
+        let mut chip8 = Chip8::default();
+        chip8.load_program(program).unwrap();
+
+        let mut rewind = RewindBuffer::new(100);
+
+        for _ in 0..10 {
+            chip8.run_frame(2).unwrap();
+            rewind.push(&chip8);
+        }
+
+        assert_eq!(chip8.v()[0], 10);
+        assert_eq!(rewind.len(), 9);
+
+        assert!(rewind.rewind(&mut chip8));
+        assert_eq!(chip8.v()[0], 9);
+
+        assert!(rewind.rewind(&mut chip8));
+        assert_eq!(chip8.v()[0], 8);
+
+        // play forward again from the rewound point
+        chip8.run_frame(2).unwrap();
+        rewind.push(&chip8);
+
+        assert_eq!(chip8.v()[0], 9);
+        assert_eq!(rewind.len(), 8);
+    }
+
+    #[test]
+    fn test_capacity_is_bounded() {
+        let program: Vec<u8> = vec![0x70, 0x01, 0x12, 0x00];
+
+        let mut chip8 = Chip8::default();
+        chip8.load_program(program).unwrap();
+
+        let mut rewind = RewindBuffer::new(5);
+
+        for _ in 0..20 {
+            chip8.run_frame(2).unwrap();
+            rewind.push(&chip8);
+        }
+
+        assert_eq!(rewind.len(), 5);
+
+        while rewind.rewind(&mut chip8) {}
+
+        assert_eq!(chip8.v()[0], 15);
+        assert!(rewind.is_empty());
+    }
+}
@@ -0,0 +1,432 @@
+use crate::errors::StateError;
+use crate::{
+    Chip8, Quirks, ALL_PLANES, AUDIO_PATTERN_SIZE, GRAPHICS_ARRAY_SIZE, KEYBOARD_ARRAY_SIZE,
+    MEMORY_SIZE, REGISTERS, RPL_FLAGS, STACK_SIZE, XO_CHIP_MEMORY_SIZE,
+};
+
+// Save state layout, all integers little-endian:
+//
+//   magic     4 bytes  "C8ST"
+//   version   u16      STATE_VERSION
+//   length    u32      length of the payload
+//   payload            the machine, see `write_payload`
+//   checksum  u32      CRC-32 of everything before it
+//
+// Only STATE_VERSION is loadable, and its payload has to be exactly the
+// fields `write_payload` writes. A change to the payload needs a new version,
+// and `read_payload` taught to read the old one if it's to stay loadable.
+const MAGIC: &[u8; 4] = b"C8ST";
+const STATE_VERSION: u16 = 1;
+const HEADER_SIZE: usize = 10;
+const CHECKSUM_SIZE: usize = 4;
+const NO_KEY: u8 = 0xFF;
+
+impl Chip8 {
+    // Captures the whole machine as a versioned, checksummed binary blob.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut payload = Writer::default();
+
+        self.write_payload(&mut payload);
+
+        let mut out = Vec::with_capacity(HEADER_SIZE + payload.0.len() + CHECKSUM_SIZE);
+
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&STATE_VERSION.to_le_bytes());
+        out.extend_from_slice(&(payload.0.len() as u32).to_le_bytes());
+        out.extend_from_slice(&payload.0);
+
+        let checksum = crc32(&out);
+        out.extend_from_slice(&checksum.to_le_bytes());
+
+        out
+    }
+
+    // Restores a blob produced by `save_state`. The machine is left untouched
+    // if the blob can't be read. The random number source keeps its type;
+    // only its position in the sequence is restored.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        if data.len() < HEADER_SIZE + CHECKSUM_SIZE || &data[..4] != MAGIC {
+            return Err(StateError::BadMagic);
+        }
+
+        let version = u16::from_le_bytes([data[4], data[5]]);
+
+        if version == 0 || version > STATE_VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+
+        let length = u32::from_le_bytes([data[6], data[7], data[8], data[9]]) as usize;
+
+        if data.len() != HEADER_SIZE + length + CHECKSUM_SIZE {
+            return Err(StateError::Malformed);
+        }
+
+        let (body, checksum) = data.split_at(HEADER_SIZE + length);
+        let checksum = u32::from_le_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]);
+
+        if crc32(body) != checksum {
+            return Err(StateError::ChecksumMismatch);
+        }
+
+        self.read_payload(&mut Reader::new(&body[HEADER_SIZE..]))
+    }
+
+    fn write_payload(&self, w: &mut Writer) {
+        w.u8(self.hires as u8 | (self.xo_chip as u8) << 1);
+        w.u8(self.quirks.to_bits());
+        w.block(&self.memory);
+        w.bytes(&self.v);
+        w.u16(self.i);
+        w.u16(self.pc);
+
+        for s in self.stack.iter() {
+            w.u16(*s);
+        }
+
+        w.u8(self.sp);
+        w.u8(self.delay_timer);
+        w.u8(self.sound_timer);
+        w.block(&self.gfx);
+        w.bytes(&self.key);
+        w.u8(self.plane);
+        w.u8(self.pitch);
+        w.bytes(&self.rpl);
+        w.bytes(&self.audio_pattern);
+        w.u8(self.awaited_key.unwrap_or(NO_KEY));
+
+        match self.rng.state() {
+            Some(state) => {
+                w.u8(1);
+                w.u64(state);
+            }
+            None => w.u8(0),
+        }
+    }
+
+    fn read_payload(&mut self, r: &mut Reader) -> Result<(), StateError> {
+        let flags = r.u8()?;
+        let hires = flags & 1 != 0;
+        let xo_chip = flags & 1 << 1 != 0;
+        let quirks = Quirks::from_bits(r.u8()?);
+
+        let memory = r.block()?;
+        let expected_memory = if xo_chip {
+            XO_CHIP_MEMORY_SIZE
+        } else {
+            MEMORY_SIZE
+        };
+
+        if memory.len() != expected_memory {
+            return Err(StateError::Malformed);
+        }
+
+        let v = r.bytes(REGISTERS)?;
+        let i = r.u16()?;
+        let pc = r.u16()?;
+
+        let mut stack = [0; STACK_SIZE];
+
+        for s in stack.iter_mut() {
+            *s = r.u16()?;
+        }
+
+        let sp = r.u8()?;
+        let delay_timer = r.u8()?;
+        let sound_timer = r.u8()?;
+
+        let gfx = r.block()?;
+
+        if gfx.len() != GRAPHICS_ARRAY_SIZE
+            || gfx.iter().any(|pixel| *pixel > ALL_PLANES)
+            || sp as usize > STACK_SIZE
+        {
+            return Err(StateError::Malformed);
+        }
+
+        let key = r.bytes(KEYBOARD_ARRAY_SIZE)?;
+        let plane = r.u8()?;
+        let pitch = r.u8()?;
+        let rpl = r.bytes(RPL_FLAGS)?;
+        let audio_pattern = r.bytes(AUDIO_PATTERN_SIZE)?;
+        let awaited_key = r.u8()?;
+        let rng_state = if r.u8()? != 0 { Some(r.u64()?) } else { None };
+
+        if plane > ALL_PLANES
+            || (awaited_key as usize >= KEYBOARD_ARRAY_SIZE && awaited_key != NO_KEY)
+            || !r.is_done()
+        {
+            return Err(StateError::Malformed);
+        }
+
+        // everything parsed, so it's safe to start overwriting the machine
+        self.hires = hires;
+        self.xo_chip = xo_chip;
+        self.quirks = quirks;
+        self.memory = memory.to_vec();
+        self.v.copy_from_slice(v);
+        self.i = i;
+        self.pc = pc;
+        self.stack = stack;
+        self.sp = sp;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.gfx.copy_from_slice(gfx);
+        self.key.copy_from_slice(key);
+        self.plane = plane;
+        self.pitch = pitch;
+        self.rpl.copy_from_slice(rpl);
+        self.audio_pattern.copy_from_slice(audio_pattern);
+        self.awaited_key = if awaited_key == NO_KEY {
+            None
+        } else {
+            Some(awaited_key)
+        };
+
+        if let Some(state) = rng_state {
+            self.rng.set_state(state);
+        }
+
+        self.mark_all_dirty();
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+
+    // a length-prefixed run of bytes
+    fn block(&mut self, bytes: &[u8]) {
+        self.0
+            .extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        self.0.extend_from_slice(bytes);
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Reader { data, position: 0 }
+    }
+
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], StateError> {
+        let end = self.position + count;
+
+        if end > self.data.len() {
+            return Err(StateError::Malformed);
+        }
+
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, StateError> {
+        let b = self.bytes(2)?;
+
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, StateError> {
+        let b = self.bytes(4)?;
+
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, StateError> {
+        let b = self.bytes(8)?;
+        let mut le = [0; 8];
+        le.copy_from_slice(b);
+
+        Ok(u64::from_le_bytes(le))
+    }
+
+    fn block(&mut self) -> Result<&'a [u8], StateError> {
+        let length = self.u32()? as usize;
+
+        self.bytes(length)
+    }
+
+    // whether everything has been read
+    fn is_done(&self) -> bool {
+        self.position == self.data.len()
+    }
+}
+
+// the standard (IEEE 802.3) CRC-32
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+
+    for b in data {
+        crc ^= *b as u32;
+
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crc32, CHECKSUM_SIZE};
+    use crate::errors::StateError;
+    use crate::{Chip8, Quirks};
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        // 0xCXNN then 0x1202: keep drawing random numbers
+        let program: Vec<u8> = vec![0xC4, 0xFF, 0x12, 0x00];
+
+        let mut chip8 = Chip8::new(Quirks::COSMAC_VIP);
+        chip8.load_program(program.clone()).unwrap();
+        chip8.seed(7);
+
+        for _ in 0..5 {
+            chip8.execute_cycle().unwrap();
+        }
+
+        chip8.key_down(3);
+        chip8.gfx[100] = 1;
+
+        let state = chip8.save_state();
+
+        let mut restored = Chip8::default();
+        restored.load_state(&state).unwrap();
+
+        assert_eq!(restored.save_state(), state);
+        assert_eq!(restored.quirks(), Quirks::COSMAC_VIP);
+        assert!(restored.is_key_down(3));
+
+        // both machines should now draw the same random numbers
+        for _ in 0..6 {
+            chip8.execute_cycle().unwrap();
+            restored.execute_cycle().unwrap();
+        }
+
+        assert_eq!(chip8.v(), restored.v());
+    }
+
+    #[test]
+    fn test_load_xo_chip_state_into_plain_machine() {
+        let chip8 = Chip8::new_xo_chip(Quirks::default());
+
+        let mut restored = Chip8::default();
+        restored.load_state(&chip8.save_state()).unwrap();
+
+        assert!(restored.is_xo_chip());
+    }
+
+    #[test]
+    fn test_bad_magic() {
+        let mut chip8 = Chip8::default();
+
+        assert_eq!(
+            chip8.load_state(b"not a state at all"),
+            Err(StateError::BadMagic)
+        );
+    }
+
+    #[test]
+    fn test_newer_version_is_rejected() {
+        let mut chip8 = Chip8::default();
+        let mut state = chip8.save_state();
+
+        state[4] = 99;
+
+        assert_eq!(
+            chip8.load_state(&state),
+            Err(StateError::UnsupportedVersion(99))
+        );
+    }
+
+    // replaces the checksum of a state that's been tampered with
+    fn resign(state: &mut [u8]) {
+        let body = state.len() - CHECKSUM_SIZE;
+        let checksum = crc32(&state[..body]);
+
+        state[body..].copy_from_slice(&checksum.to_le_bytes());
+    }
+
+    #[test]
+    fn test_awaited_key_out_of_range_is_rejected() {
+        // 0xF00A: wait for a key
+        let mut chip8 = Chip8::default();
+        chip8.load_program(vec![0xF0, 0x0A]).unwrap();
+        chip8.seed(1);
+        chip8.key_down(4);
+        chip8.execute_cycle().unwrap();
+
+        let mut state = chip8.save_state();
+
+        // the awaited key comes just before the random number source's state
+        let awaited = state.len() - CHECKSUM_SIZE - 9 - 1;
+
+        assert_eq!(state[awaited], 4);
+
+        state[awaited] = 0x40;
+        resign(&mut state);
+
+        assert_eq!(chip8.load_state(&state), Err(StateError::Malformed));
+    }
+
+    #[test]
+    fn test_plane_out_of_range_is_rejected() {
+        let mut chip8 = Chip8::new_xo_chip(Quirks::default());
+        let mut state = chip8.save_state();
+
+        // working back from the end: the random number source's state, the
+        // awaited key, the audio pattern, the RPL flags, the pitch, the plane
+        let plane = state.len() - CHECKSUM_SIZE - 9 - 1 - 16 - 16 - 1 - 1;
+
+        assert_eq!(state[plane], 1);
+
+        state[plane] = 4;
+        resign(&mut state);
+
+        assert_eq!(chip8.load_state(&state), Err(StateError::Malformed));
+    }
+
+    #[test]
+    fn test_corruption_is_detected() {
+        let mut chip8 = Chip8::default();
+        let mut state = chip8.save_state();
+
+        state[600] ^= 0xFF;
+
+        assert_eq!(chip8.load_state(&state), Err(StateError::ChecksumMismatch));
+    }
+}
@@ -0,0 +1,202 @@
+use std::io::{self, Write};
+
+use serde_json::json;
+
+use crate::{mnemonic, Chip8, Instruction, Syntax};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    // one line of columns per instruction, for reading and diffing
+    Text,
+    // one JSON object per instruction, for scripts
+    JsonLines,
+}
+
+impl TraceFormat {
+    pub const NAMES: [&'static str; 2] = ["text", "jsonl"];
+
+    pub fn from_name(name: &str) -> Option<TraceFormat> {
+        match name.to_lowercase().as_str() {
+            "text" => Some(TraceFormat::Text),
+            "jsonl" | "json" => Some(TraceFormat::JsonLines),
+            _ => None,
+        }
+    }
+}
+
+// Writes a line for every instruction `Chip8::execute_cycle` runs, with the
+// machine as it was just before the instruction: the cycle number, PC, the
+// opcode and its mnemonic, V0-VF, I, SP and the timers. Tracing can be
+// limited to an inclusive range of PC values.
+//
+// Writing stops at the first error, which `finish` reports.
+pub struct Tracer {
+    output: Box<dyn Write>,
+    format: TraceFormat,
+    range: Option<(u16, u16)>,
+    error: Option<io::Error>,
+}
+
+impl Tracer {
+    pub fn new<W: Write + 'static>(
+        output: W,
+        format: TraceFormat,
+        range: Option<(u16, u16)>,
+    ) -> Tracer {
+        Tracer {
+            output: Box::new(output),
+            format,
+            range,
+            error: None,
+        }
+    }
+
+    // Flushes the trace, returning the first error writing it hit.
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.output.flush(),
+        }
+    }
+
+    pub(crate) fn trace(&mut self, cycle: u64, chip8: &Chip8) {
+        let pc = chip8.pc();
+
+        if self.error.is_some()
+            || self
+                .range
+                .is_some_and(|(start, end)| pc < start || pc > end)
+        {
+            return;
+        }
+
+        let word = |address: usize| -> Option<u16> {
+            let memory = chip8.memory();
+
+            Some((*memory.get(address)? as u16) << 8 | *memory.get(address + 1)? as u16)
+        };
+        let opcode = word(pc as usize);
+        let text = match opcode.map(Instruction::decode) {
+            Some(Ok(instruction)) if !instruction.is_xo_chip() || chip8.is_xo_chip() => {
+                let operand = word(pc as usize + 2).unwrap_or_default();
+
+                mnemonic(instruction, operand, Syntax::Octo)
+            }
+            _ => "???".to_string(),
+        };
+        // PC can run off the end of memory, in which case there's no opcode
+        let opcode = opcode.unwrap_or_default();
+
+        let result = match self.format {
+            TraceFormat::Text => {
+                let v: Vec<String> = chip8.v().iter().map(|v| format!("{:02X}", v)).collect();
+
+                writeln!(
+                    self.output,
+                    "{:>8} {:#05X} {:04X} {:<24} V {} I {:#05X} SP {:X} DT {:02X} ST {:02X}",
+                    cycle,
+                    pc,
+                    opcode,
+                    text,
+                    v.join(" "),
+                    chip8.i(),
+                    chip8.sp(),
+                    chip8.delay_timer(),
+                    chip8.sound_timer
+                )
+            }
+            TraceFormat::JsonLines => {
+                let line = json!({
+                    "cycle": cycle,
+                    "pc": pc,
+                    "opcode": opcode,
+                    "mnemonic": text,
+                    "v": chip8.v(),
+                    "i": chip8.i(),
+                    "sp": chip8.sp(),
+                    "dt": chip8.delay_timer(),
+                    "st": chip8.sound_timer,
+                });
+
+                writeln!(self.output, "{}", line)
+            }
+        };
+
+        self.error = result.err();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::io::{self, Write};
+    use std::rc::Rc;
+
+    use serde_json::Value;
+
+    use super::{TraceFormat, Tracer};
+    use crate::{Chip8, Quirks};
+
+    // v0 := 5; v1 += 1; jump back to the add
+    const PROGRAM: [u8; 6] = [0x60, 0x05, 0x71, 0x01, 0x12, 0x02];
+
+    // a buffer the test can still read once the tracer owns a handle to it
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn run(format: TraceFormat, range: Option<(u16, u16)>, cycles: usize) -> Vec<String> {
+        let buffer = Shared::default();
+        let mut chip8 = Chip8::new(Quirks::default());
+
+        chip8.load_program(PROGRAM.to_vec()).unwrap();
+        chip8.set_tracer(Some(Tracer::new(buffer.clone(), format, range)));
+
+        for _ in 0..cycles {
+            chip8.execute_cycle().unwrap();
+        }
+
+        chip8.set_tracer(None).unwrap().finish().unwrap();
+
+        let text = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+
+        text.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_text_trace() {
+        let lines = run(TraceFormat::Text, None, 3);
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[1],
+            "       1 0x202 7101 v1 += 0x01               \
+             V 05 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 I 0x000 SP 0 DT 00 ST 00"
+        );
+    }
+
+    #[test]
+    fn test_json_trace_with_range() {
+        // only the add, which runs every other cycle
+        let lines = run(TraceFormat::JsonLines, Some((0x202, 0x203)), 5);
+
+        assert_eq!(lines.len(), 2);
+
+        let line: Value = serde_json::from_str(&lines[1]).unwrap();
+
+        assert_eq!(line["cycle"], 3);
+        assert_eq!(line["pc"], 0x202);
+        assert_eq!(line["opcode"], 0x7101);
+        assert_eq!(line["mnemonic"], "v1 += 0x01");
+        assert_eq!(line["v"][1], 1);
+    }
+}